@@ -1,7 +1,8 @@
 //! Memory allocation benchmarks
 
-use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
-use hft_benchmarks::{calibrate_tsc_frequency, configure_for_desktop_memory_benchmarks, check_desktop_suitability};
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
+use hft_benchmarks::{calibrate_tsc_frequency, configure_for_desktop_memory_benchmarks, check_desktop_suitability, Topology};
+use hft_benchmarks::affinity::run_on_cpu;
 use hft_benchmarks::mock_core::{ObjectPool, NumaArenaAllocator};
 
 fn benchmark_allocators(c: &mut Criterion) {
@@ -25,10 +26,13 @@ fn benchmark_allocators(c: &mut Criterion) {
     });
     
     // NUMA allocation
-    let numa_alloc = NumaArenaAllocator::new(0);
+    let mut numa_alloc = NumaArenaAllocator::new(0).expect("map NUMA arena");
     group.bench_function("numa_alloc_1kb", |b| {
         b.iter(|| {
-            let _data = numa_alloc.allocate(1024);
+            if numa_alloc.remaining() < 1024 {
+                numa_alloc.reset();
+            }
+            let _data = std::hint::black_box(numa_alloc.allocate(1024));
         })
     });
     
@@ -104,10 +108,47 @@ fn benchmark_memory_patterns(c: &mut Criterion) {
     group.finish();
 }
 
+fn benchmark_numa_nodes(c: &mut Criterion) {
+    let topology = match Topology::detect() {
+        Ok(topology) => topology,
+        Err(e) => {
+            eprintln!("Skipping NUMA benchmarks: {}", e);
+            return;
+        }
+    };
+    let mut group = c.benchmark_group("numa_access");
+    const BYTES: usize = 64 << 20;
+
+    // every (cpu node, memory node) pair; one local pair on single-node machines
+    for cpu_node in &topology.nodes {
+        let Some(&cpu) = cpu_node.cpus.first() else { continue };
+        for memory_node in &topology.nodes {
+            let id = format!("cpu{}_mem{}", cpu_node.id, memory_node.id);
+            let Ok(arena) = NumaArenaAllocator::with_capacity(memory_node.id, BYTES) else { continue };
+            let buffer = arena.allocate(BYTES).unwrap();
+            let words: Vec<usize> = (0..BYTES / 64).map(|_| fastrand::usize(..BYTES)).collect();
+
+            run_on_cpu(cpu, || {
+                group.throughput(Throughput::Bytes(BYTES as u64));
+                group.bench_function(BenchmarkId::new("sequential_read", &id), |b| {
+                    b.iter(|| buffer.iter().fold(0u8, |acc, &x| acc ^ x))
+                });
+                group.throughput(Throughput::Elements(words.len() as u64));
+                group.bench_function(BenchmarkId::new("random_read", &id), |b| {
+                    b.iter(|| words.iter().fold(0u8, |acc, &i| acc ^ buffer[i]))
+                });
+            }).ok();
+        }
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     memory_benches,
     benchmark_allocators,
     benchmark_pool_sizes,
-    benchmark_memory_patterns
+    benchmark_memory_patterns,
+    benchmark_numa_nodes
 );
 criterion_main!(memory_benches);
\ No newline at end of file
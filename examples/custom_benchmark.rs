@@ -43,7 +43,7 @@ fn main() {
     // Benchmark 3: String operations
     println!("\n=== String Operations ===");
     SimpleBench::new("string_ops")
-        .bench(1000, || string_operations())
+        .bench(1000, string_operations)
         .report();
     
     // Benchmark 4: Compare two implementations
@@ -16,6 +16,7 @@ fn sum_with_formula(n: u32) -> u32 {
 }
 
 // Two different ways to check if number is even
+#[allow(clippy::manual_is_multiple_of)]
 fn is_even_modulo(n: u32) -> bool {
     n % 2 == 0
 }
@@ -80,6 +81,7 @@ fn main() {
     // Test 3: Vector vs Array
     println!("\n=== Vector vs Array Access ===");
     
+    #[allow(clippy::useless_vec)]
     let vec_data = vec![1, 2, 3, 4, 5];
     let array_data = [1, 2, 3, 4, 5];
     
@@ -0,0 +1,357 @@
+//! Thread-to-CPU pinning helpers
+
+use std::io;
+use std::sync::{Mutex, PoisonError};
+
+/// CPUs the calling thread may currently run on
+#[cfg(target_os = "linux")]
+pub fn allowed_cpus() -> io::Result<Vec<usize>> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize).filter(|&cpu| libc::CPU_ISSET(cpu, &set)).collect())
+    }
+}
+
+/// Restrict the calling thread to `cpus`
+#[cfg(target_os = "linux")]
+pub fn set_allowed_cpus(cpus: &[usize]) -> io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("CPU {cpu} out of range")));
+            }
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// CPU the calling thread is running on right now
+#[cfg(target_os = "linux")]
+pub fn current_cpu() -> Option<usize> {
+    let cpu = unsafe { libc::sched_getcpu() };
+    (cpu >= 0).then_some(cpu as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allowed_cpus() -> io::Result<Vec<usize>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "CPU affinity is only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_allowed_cpus(_cpus: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "CPU affinity is only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current_cpu() -> Option<usize> {
+    None
+}
+
+/// Pin the calling thread to a single CPU
+pub fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    set_allowed_cpus(&[cpu])
+}
+
+/// Run `f` pinned to `cpu`, then restore the thread's previous affinity
+///
+/// The affinity is restored even if `f` panics.
+pub fn run_on_cpu<F, R>(cpu: usize, f: F) -> io::Result<R>
+where
+    F: FnOnce() -> R,
+{
+    run_on_cpus(&[cpu], f)
+}
+
+/// Run `f` restricted to `cpus`, then restore the thread's previous affinity
+///
+/// The affinity is restored even if `f` panics.
+pub fn run_on_cpus<F, R>(cpus: &[usize], f: F) -> io::Result<R>
+where
+    F: FnOnce() -> R,
+{
+    let restore = RestoreAffinity { previous: Some(allowed_cpus()?) };
+    set_allowed_cpus(cpus)?;
+    let result = f();
+    restore.restore()?;
+    Ok(result)
+}
+
+/// Puts back a thread's affinity, reporting errors when restored
+/// explicitly and ignoring them when dropped during unwinding
+struct RestoreAffinity {
+    previous: Option<Vec<usize>>,
+}
+
+impl RestoreAffinity {
+    fn restore(mut self) -> io::Result<()> {
+        set_allowed_cpus(&self.previous.take().unwrap_or_default())
+    }
+}
+
+impl Drop for RestoreAffinity {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            let _ = set_allowed_cpus(&previous);
+        }
+    }
+}
+
+/// Scheduling class for a pinned benchmark thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedulingPolicy {
+    /// Leave the thread's policy as it is
+    #[default]
+    Unchanged,
+    /// `SCHED_FIFO` at `priority` (1-99); needs `CAP_SYS_NICE` or an
+    /// `RLIMIT_RTPRIO` allowance
+    Fifo { priority: i32 },
+}
+
+/// Where and how a benchmark thread runs
+///
+/// Also recorded in `BenchmarkAnalysis::pinning` so results carry the core
+/// and policy they were measured with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pinning {
+    pub cpu: usize,
+    pub policy: SchedulingPolicy,
+    /// Lock all current and future pages into RAM with `mlockall`
+    pub lock_memory: bool,
+}
+
+impl Pinning {
+    pub fn cpu(cpu: usize) -> Self {
+        Self { cpu, policy: SchedulingPolicy::Unchanged, lock_memory: false }
+    }
+
+    pub fn with_fifo(mut self, priority: i32) -> Self {
+        self.policy = SchedulingPolicy::Fifo { priority };
+        self
+    }
+
+    pub fn lock_memory(mut self) -> Self {
+        self.lock_memory = true;
+        self
+    }
+
+    /// Apply to the calling thread until the guard is dropped
+    ///
+    /// If any step fails the steps already taken are undone.
+    pub fn apply(&self) -> io::Result<PinGuard> {
+        let mut guard = PinGuard {
+            previous_cpus: allowed_cpus()?,
+            previous_policy: None,
+            holds_memory_lock: false,
+            _thread_bound: std::marker::PhantomData,
+        };
+        pin_current_thread(self.cpu)?;
+        if let SchedulingPolicy::Fifo { priority } = self.policy {
+            guard.previous_policy = Some(current_policy()?);
+            set_policy(RawPolicy { policy: SCHED_FIFO, priority })?;
+        }
+        if self.lock_memory {
+            let mut locks = MEMORY_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+            if locks.guards == 0 {
+                // Leave an `mlockall` (or `mlock`) someone else made in place
+                locks.unlock_when_released = locked_memory_kb().is_none_or(|kb| kb == 0);
+            }
+            lock_all_memory()?;
+            locks.guards += 1;
+            guard.holds_memory_lock = true;
+        }
+        Ok(guard)
+    }
+}
+
+/// `PinGuard`s sharing the process-wide `mlockall`
+struct MemoryLocks {
+    guards: usize,
+    /// Whether the process had nothing locked before the first guard, so the last one to drop unlocks
+    unlock_when_released: bool,
+}
+
+static MEMORY_LOCKS: Mutex<MemoryLocks> = Mutex::new(MemoryLocks { guards: 0, unlock_when_released: false });
+
+/// Restores the thread's affinity, policy and memory locking on drop
+///
+/// `mlockall` is process-wide, so memory stays locked until the last guard that locked it is
+/// dropped, whichever thread it is on. Memory locked outside these guards while one is live is
+/// unlocked with it.
+pub struct PinGuard {
+    previous_cpus: Vec<usize>,
+    previous_policy: Option<RawPolicy>,
+    /// Counted in `MEMORY_LOCKS`
+    holds_memory_lock: bool,
+    /// Restoring only makes sense on the thread that was pinned
+    _thread_bound: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for PinGuard {
+    fn drop(&mut self) {
+        if self.holds_memory_lock {
+            let mut locks = MEMORY_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+            locks.guards -= 1;
+            if locks.guards == 0 && locks.unlock_when_released {
+                unlock_all_memory();
+            }
+        }
+        if let Some(policy) = self.previous_policy {
+            let _ = set_policy(policy);
+        }
+        let _ = set_allowed_cpus(&self.previous_cpus);
+    }
+}
+
+/// Run `f` as `pinning` describes, then restore the thread's previous state
+pub fn run_pinned<F, R>(pinning: &Pinning, f: F) -> io::Result<R>
+where
+    F: FnOnce() -> R,
+{
+    let _guard = pinning.apply()?;
+    Ok(f())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RawPolicy {
+    policy: i32,
+    priority: i32,
+}
+
+#[cfg(target_os = "linux")]
+const SCHED_FIFO: i32 = libc::SCHED_FIFO;
+#[cfg(not(target_os = "linux"))]
+const SCHED_FIFO: i32 = 1;
+
+#[cfg(target_os = "linux")]
+fn current_policy() -> io::Result<RawPolicy> {
+    unsafe {
+        let policy = libc::sched_getscheduler(0);
+        let mut param: libc::sched_param = std::mem::zeroed();
+        if policy < 0 || libc::sched_getparam(0, &mut param) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawPolicy { policy, priority: param.sched_priority })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_policy(policy: RawPolicy) -> io::Result<()> {
+    let param = libc::sched_param { sched_priority: policy.priority };
+    if unsafe { libc::sched_setscheduler(0, policy.policy, &param) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn lock_all_memory() -> io::Result<()> {
+    if unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// `VmLck` from /proc/self/status: memory the process has locked
+#[cfg(target_os = "linux")]
+fn locked_memory_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("VmLck:"))?;
+    line.trim().trim_end_matches("kB").trim().parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn unlock_all_memory() {
+    unsafe {
+        libc::munlockall();
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_policy() -> io::Result<RawPolicy> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "scheduling policies are only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_policy(_policy: RawPolicy) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "scheduling policies are only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lock_all_memory() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "mlockall is only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn locked_memory_kb() -> Option<u64> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unlock_all_memory() {}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_and_restore() {
+        let before = allowed_cpus().unwrap();
+        assert!(!before.is_empty());
+
+        let cpu = before[0];
+        let ran_on = run_on_cpu(cpu, current_cpu).unwrap();
+        assert_eq!(ran_on, Some(cpu));
+        assert_eq!(allowed_cpus().unwrap(), before);
+
+        assert!(set_allowed_cpus(&[usize::MAX]).is_err());
+
+        let panicked = std::panic::catch_unwind(|| run_on_cpu(cpu, || panic!("benchmark failed")));
+        assert!(panicked.is_err());
+        assert_eq!(allowed_cpus().unwrap(), before);
+    }
+
+    #[test]
+    fn test_run_pinned_restores_state() {
+        let before = allowed_cpus().unwrap();
+        let policy_before = current_policy().unwrap();
+        let pinning = Pinning::cpu(before[0]).with_fifo(10).lock_memory();
+
+        // Real-time scheduling and mlockall need privileges the test may lack
+        match run_pinned(&pinning, || (current_cpu(), current_policy().unwrap())) {
+            Ok((cpu, policy)) => {
+                assert_eq!(cpu, Some(before[0]));
+                assert_eq!((policy.policy, policy.priority), (libc::SCHED_FIFO, 10));
+            }
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::PermissionDenied, "{e}"),
+        }
+
+        assert_eq!(allowed_cpus().unwrap(), before);
+        assert_eq!(current_policy().unwrap().policy, policy_before.policy);
+        assert!(Pinning::cpu(usize::MAX).apply().is_err());
+
+        // A lock the process already held survives the guard
+        if lock_all_memory().is_ok() {
+            run_pinned(&Pinning::cpu(before[0]).lock_memory(), || ()).unwrap();
+            assert!(locked_memory_kb().is_some_and(|kb| kb > 0));
+            unlock_all_memory();
+        }
+
+        // Memory stays locked until the last of several guards is dropped
+        if let Ok(outer) = Pinning::cpu(before[0]).lock_memory().apply() {
+            std::thread::spawn(move || drop(Pinning::cpu(before[0]).lock_memory().apply().unwrap()))
+                .join()
+                .unwrap();
+            assert!(locked_memory_kb().is_some_and(|kb| kb > 0));
+            drop(outer);
+            assert_eq!(locked_memory_kb(), Some(0));
+        }
+    }
+}
@@ -1,7 +1,9 @@
 //! Memory allocation benchmarking utilities
 
+use std::io;
+
 use jemallocator::Jemalloc;
-use crate::BenchmarkResults;
+use crate::{BenchmarkAnalysis, BenchmarkResults};
 
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
@@ -9,13 +11,15 @@ static GLOBAL: Jemalloc = Jemalloc;
 const DEFAULT_ITERATIONS: usize = 10_000;
 const ALLOCATION_SIZES: [usize; 6] = [64, 128, 256, 512, 1024, 4096];
 
-pub fn benchmark_allocations() {
+pub fn benchmark_allocations() -> Vec<BenchmarkAnalysis> {
     benchmark_allocations_with_iterations(DEFAULT_ITERATIONS)
 }
 
-pub fn benchmark_allocations_with_iterations(iterations: usize) {
+/// Benchmark allocations of each size in `ALLOCATION_SIZES`, one analysis per size
+pub fn benchmark_allocations_with_iterations(iterations: usize) -> Vec<BenchmarkAnalysis> {
     println!("Benchmarking memory allocations ({iterations} iterations per size)...");
     
+    let mut analyses = Vec::with_capacity(ALLOCATION_SIZES.len());
     for &size in &ALLOCATION_SIZES {
         let mut results = BenchmarkResults::new(format!("allocation_{size}B"));
         
@@ -24,8 +28,11 @@ pub fn benchmark_allocations_with_iterations(iterations: usize) {
             results.record(elapsed);
         }
         
-        println!("{}", results.analyze().summary());
+        let analysis = results.analyze();
+        println!("{}", analysis.summary());
+        analyses.push(analysis);
     }
+    analyses
 }
 
 pub struct SimpleObjectPool<T> {
@@ -60,12 +67,14 @@ impl<T> SimpleObjectPool<T> {
 }
 
 /// Benchmark object pools vs direct allocation
-pub fn benchmark_object_pools() {
+pub fn benchmark_object_pools() -> Vec<BenchmarkAnalysis> {
     benchmark_object_pools_with_iterations(DEFAULT_ITERATIONS)
 }
 
 /// Benchmark object pools with custom iteration count
-pub fn benchmark_object_pools_with_iterations(iterations: usize) {
+///
+/// Returns the pool and direct allocation analyses, in that order.
+pub fn benchmark_object_pools_with_iterations(iterations: usize) -> Vec<BenchmarkAnalysis> {
     println!("Benchmarking object pools vs direct allocation...");
     
     let mut pool = SimpleObjectPool::<u64>::new();
@@ -81,17 +90,22 @@ pub fn benchmark_object_pools_with_iterations(iterations: usize) {
         direct_results.record(elapsed);
     }
     
-    println!("Pool allocation: {}", pool_results.analyze().summary());
-    println!("Direct allocation: {}", direct_results.analyze().summary());
+    let pool_analysis = pool_results.analyze();
+    let direct_analysis = direct_results.analyze();
+    println!("Pool allocation: {}", pool_analysis.summary());
+    println!("Direct allocation: {}", direct_analysis.summary());
+    vec![pool_analysis, direct_analysis]
 }
 
 /// Benchmark allocation alignment impact
-pub fn benchmark_aligned_allocations() {
+pub fn benchmark_aligned_allocations() -> Vec<BenchmarkAnalysis> {
     benchmark_aligned_allocations_with_iterations(DEFAULT_ITERATIONS / 2)
 }
 
 /// Benchmark aligned allocations with custom iteration count
-pub fn benchmark_aligned_allocations_with_iterations(iterations: usize) {
+///
+/// Returns the aligned and unaligned allocation analyses, in that order.
+pub fn benchmark_aligned_allocations_with_iterations(iterations: usize) -> Vec<BenchmarkAnalysis> {
     println!("Benchmarking aligned vs unaligned allocations...");
     
     let mut aligned_results = BenchmarkResults::new("aligned_allocation".to_string());
@@ -118,8 +132,281 @@ pub fn benchmark_aligned_allocations_with_iterations(iterations: usize) {
         unaligned_results.record(elapsed);
     }
     
-    println!("Aligned allocation: {}", aligned_results.analyze().summary());
-    println!("Unaligned allocation: {}", unaligned_results.analyze().summary());
+    let aligned_analysis = aligned_results.analyze();
+    let unaligned_analysis = unaligned_results.analyze();
+    println!("Aligned allocation: {}", aligned_analysis.summary());
+    println!("Unaligned allocation: {}", unaligned_analysis.summary());
+    vec![aligned_analysis, unaligned_analysis]
+}
+
+/// Huge page size used for hugetlb mappings and THP alignment
+const HUGE_PAGE_BYTES: usize = 2 << 20;
+/// Stride for touching every base page
+const BASE_PAGE_BYTES: usize = 4096;
+const DEFAULT_REGION_BYTES: usize = 64 << 20;
+
+/// Page size backing a benchmarked region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageBacking {
+    /// 4 KiB pages; transparent huge pages are disabled with `MADV_NOHUGEPAGE`
+    Small,
+    /// `MADV_HUGEPAGE` on a 2 MiB-aligned region
+    Transparent,
+    /// Reserved 2 MiB pages from the hugetlb pool (`MAP_HUGETLB`); needs
+    /// `hugepages-2048kB/nr_hugepages` > 0 (`vm.nr_hugepages` when 2 MiB is
+    /// the default size)
+    HugeTlb,
+}
+
+impl PageBacking {
+    pub const ALL: [PageBacking; 3] = [PageBacking::Small, PageBacking::Transparent, PageBacking::HugeTlb];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PageBacking::Small => "4k",
+            PageBacking::Transparent => "thp",
+            PageBacking::HugeTlb => "hugetlb",
+        }
+    }
+}
+
+/// When a benchmarked region's pages are faulted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faulting {
+    /// On first touch
+    Lazy,
+    /// While mapping (`MAP_POPULATE`); THP regions populate after their
+    /// advice with `MADV_POPULATE_WRITE`, or by touching every page on
+    /// kernels before 5.14
+    Populate,
+    /// By `mlock`, which also keeps the pages resident
+    Locked,
+}
+
+impl Faulting {
+    pub const ALL: [Faulting; 3] = [Faulting::Lazy, Faulting::Populate, Faulting::Locked];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Faulting::Lazy => "lazy",
+            Faulting::Populate => "populate",
+            Faulting::Locked => "mlock",
+        }
+    }
+}
+
+/// An anonymous mapping, unmapped on drop
+struct Mapping {
+    base: *mut u8,
+    len: usize,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    raw: (*mut u8, usize),
+}
+
+impl Mapping {
+    /// Map at least `bytes` (rounded up to whole huge pages, so every
+    /// backing covers the same range)
+    #[cfg(target_os = "linux")]
+    fn new(bytes: usize, backing: PageBacking, faulting: Faulting) -> io::Result<Self> {
+        let len = bytes.max(1).div_ceil(HUGE_PAGE_BYTES) * HUGE_PAGE_BYTES;
+        let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+        // Ask for 2 MiB pages explicitly; the default hugetlb size may be 1 GiB
+        if backing == PageBacking::HugeTlb {
+            flags |= libc::MAP_HUGETLB | libc::MAP_HUGE_2MB;
+        }
+        // THP needs its advice before the pages are faulted. 4 KiB regions
+        // aren't huge page aligned, but under THP `always` the kernel may
+        // still back an aligned 2 MiB stretch of them with a huge page here
+        if faulting == Faulting::Populate && backing != PageBacking::Transparent {
+            flags |= libc::MAP_POPULATE;
+        }
+        // Over-map so a THP region can start on a huge page boundary
+        let raw_len = if backing == PageBacking::Transparent { len + HUGE_PAGE_BYTES } else { len };
+
+        unsafe {
+            let raw = libc::mmap(std::ptr::null_mut(), raw_len, libc::PROT_READ | libc::PROT_WRITE, flags, -1, 0);
+            if raw == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            let raw = raw as *mut u8;
+            let base = raw.add(raw.align_offset(HUGE_PAGE_BYTES).min(raw_len - len));
+            let mapping = Self { base, len, raw: (raw, raw_len) };
+
+            match backing {
+                // Kernels without THP reject the advice; their pages are 4 KiB anyway
+                PageBacking::Small => match mapping.madvise(libc::MADV_NOHUGEPAGE) {
+                    Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+                    result => result?,
+                },
+                PageBacking::Transparent => mapping.madvise(libc::MADV_HUGEPAGE)?,
+                PageBacking::HugeTlb => {}
+            }
+            match faulting {
+                // `MADV_POPULATE_WRITE` needs Linux 5.14
+                Faulting::Populate if backing == PageBacking::Transparent => match mapping.madvise(libc::MADV_POPULATE_WRITE) {
+                    Err(e) if e.raw_os_error() == Some(libc::EINVAL) => mapping.touch_all(),
+                    result => result?,
+                },
+                Faulting::Locked if libc::mlock(base as *const libc::c_void, len) != 0 => {
+                    return Err(io::Error::last_os_error());
+                }
+                _ => {}
+            }
+            Ok(mapping)
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new(_bytes: usize, _backing: PageBacking, _faulting: Faulting) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "page backing benchmarks need Linux"))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn madvise(&self, advice: libc::c_int) -> io::Result<()> {
+        if unsafe { libc::madvise(self.base as *mut libc::c_void, self.len, advice) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Write one byte in every 4 KiB page
+    fn touch_all(&self) {
+        for offset in (0..self.len).step_by(BASE_PAGE_BYTES) {
+            unsafe { self.base.add(offset).write_volatile(1) };
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::munmap(self.raw.0 as *mut libc::c_void, self.raw.1);
+        }
+    }
+}
+
+/// Benchmark getting a usable multi-MB region: mapping it, faulting it in
+/// as `Faulting` says, and writing every page
+pub fn benchmark_page_backings() -> Vec<BenchmarkAnalysis> {
+    benchmark_page_backings_with(DEFAULT_REGION_BYTES, 20)
+}
+
+/// One analysis per available backing and faulting combination, named
+/// `region_<backing>_<faulting>`; combinations that cannot be mapped (no
+/// reserved huge pages, `mlock` limits) are skipped
+pub fn benchmark_page_backings_with(region_bytes: usize, iterations: usize) -> Vec<BenchmarkAnalysis> {
+    println!("Benchmarking {} MiB region setup per page backing ({iterations} iterations)...", region_bytes >> 20);
+
+    let mut analyses = Vec::new();
+    for backing in PageBacking::ALL {
+        for faulting in Faulting::ALL {
+            let name = format!("region_{}_{}", backing.label(), faulting.label());
+            let mut results = BenchmarkResults::new(name.clone());
+            for _ in 0..iterations {
+                let (mapping, elapsed) = crate::timing::time_function(|| {
+                    Mapping::new(region_bytes, backing, faulting).inspect(|mapping| mapping.touch_all())
+                });
+                match mapping {
+                    Ok(_) => results.record(elapsed),
+                    Err(e) => {
+                        println!("{name}: skipped ({e})");
+                        break;
+                    }
+                }
+            }
+            if results.is_empty() {
+                continue;
+            }
+            let analysis = results.analyze();
+            println!("{}", analysis.summary());
+            analyses.push(analysis);
+        }
+    }
+    analyses
+}
+
+/// Benchmark the first write to each 4 KiB page of a lazily faulted region
+pub fn benchmark_first_touch() -> Vec<BenchmarkAnalysis> {
+    benchmark_first_touch_with(DEFAULT_REGION_BYTES)
+}
+
+/// One analysis per available backing, named `first_touch_<backing>`, with
+/// one sample per 4 KiB page
+///
+/// With huge pages only the first touch of each 2 MiB page faults, so the
+/// tail percentiles carry the fault cost and the median the TLB-hit cost.
+pub fn benchmark_first_touch_with(region_bytes: usize) -> Vec<BenchmarkAnalysis> {
+    println!("Benchmarking first touch of each page in a {} MiB region...", region_bytes >> 20);
+
+    let mut analyses = Vec::new();
+    for backing in PageBacking::ALL {
+        let name = format!("first_touch_{}", backing.label());
+        let mapping = match Mapping::new(region_bytes, backing, Faulting::Lazy) {
+            Ok(mapping) => mapping,
+            Err(e) => {
+                println!("{name}: skipped ({e})");
+                continue;
+            }
+        };
+        let mut results = BenchmarkResults::new(name);
+        for offset in (0..mapping.len).step_by(BASE_PAGE_BYTES) {
+            let (_, elapsed) = crate::timing::time_function(|| unsafe { mapping.base.add(offset).write_volatile(1) });
+            results.record(elapsed);
+        }
+        let analysis = results.analyze();
+        println!("{}", analysis.summary());
+        analyses.push(analysis);
+    }
+    analyses
+}
+
+/// Benchmark random reads across a prefaulted region, where 4 KiB pages
+/// overflow the TLB long before huge pages do
+pub fn benchmark_random_access() -> Vec<BenchmarkAnalysis> {
+    benchmark_random_access_with(DEFAULT_REGION_BYTES, 1_000)
+}
+
+/// One analysis per available backing, named `random_read_<backing>`; each
+/// sample is the mean of 1000 dependent reads at random pages
+pub fn benchmark_random_access_with(region_bytes: usize, samples: usize) -> Vec<BenchmarkAnalysis> {
+    const READS: u64 = 1000;
+    println!("Benchmarking random reads in a {} MiB region per page backing...", region_bytes >> 20);
+
+    let mut analyses = Vec::new();
+    for backing in PageBacking::ALL {
+        let name = format!("random_read_{}", backing.label());
+        let mapping = match Mapping::new(region_bytes, backing, Faulting::Populate) {
+            Ok(mapping) => mapping,
+            Err(e) => {
+                println!("{name}: skipped ({e})");
+                continue;
+            }
+        };
+        mapping.touch_all();
+        let pages = mapping.len / BASE_PAGE_BYTES;
+
+        let mut results = BenchmarkResults::new(name);
+        let mut carry = 0usize;
+        for _ in 0..samples {
+            let (sum, elapsed) = crate::timing::time_function(|| {
+                let mut page = carry;
+                for _ in 0..READS {
+                    // Depend on the previous load so the reads can't overlap;
+                    // with an odd increment this visits every page
+                    let value = unsafe { mapping.base.add(page * BASE_PAGE_BYTES).read_volatile() } as usize;
+                    page = page.wrapping_mul(2_654_435_761).wrapping_add(value | 1) % pages;
+                }
+                page
+            });
+            carry = sum;
+            results.record((elapsed + READS / 2) / READS);
+        }
+        let analysis = results.analyze();
+        println!("{}", analysis.summary());
+        analyses.push(analysis);
+    }
+    analyses
 }
 
 #[cfg(test)]
@@ -148,11 +435,44 @@ mod tests {
     fn test_allocation_benchmarks() {
         crate::quick_calibrate_tsc_frequency();
         
-        // These tests just ensure benchmarks don't panic
-        // Actual performance will vary by system
-        benchmark_object_pools_with_iterations(10);
-        benchmark_allocations_with_iterations(10);
-        benchmark_aligned_allocations_with_iterations(10);
+        // Actual performance will vary by system, so only check the shape
+        let pools = benchmark_object_pools_with_iterations(10);
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[0].name, "pool_allocation");
+        assert_eq!(pools[1].count, 10);
+        
+        let allocations = benchmark_allocations_with_iterations(10);
+        assert_eq!(allocations.len(), ALLOCATION_SIZES.len());
+        assert_eq!(allocations[0].name, "allocation_64B");
+        
+        let aligned = benchmark_aligned_allocations_with_iterations(10);
+        assert_eq!(aligned.len(), 2);
+        assert_eq!(aligned[0].name, "aligned_allocation");
+    }
+    
+    #[test]
+    fn test_page_backing_benchmarks() {
+        crate::quick_calibrate_tsc_frequency();
+        let region = 4 << 20;
+
+        let setups = benchmark_page_backings_with(region, 2);
+        let first_touch = benchmark_first_touch_with(region);
+        let random = benchmark_random_access_with(region, 10);
+        if cfg!(not(target_os = "linux")) {
+            assert!(setups.is_empty() && first_touch.is_empty() && random.is_empty());
+            return;
+        }
+
+        // Lazy and populated 4 KiB pages are always available; mlock depends
+        // on RLIMIT_MEMLOCK and hugetlb on the pool
+        let names: Vec<_> = setups.iter().map(|a| a.name.as_str()).collect();
+        assert!(names.starts_with(&["region_4k_lazy", "region_4k_populate"]), "{names:?}");
+        assert!(setups.iter().all(|a| a.count == 2));
+
+        assert_eq!(first_touch[0].name, "first_touch_4k");
+        assert_eq!(first_touch[0].count, region / BASE_PAGE_BYTES);
+        assert_eq!(random[0].name, "random_read_4k");
+        assert_eq!(random[0].count, 10);
     }
     
     #[test]
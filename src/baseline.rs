@@ -0,0 +1,694 @@
+//! Persisted baselines and regression detection between benchmark runs
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::confidence::{ConfidenceIntervals, Interval, IntervalMethod};
+use crate::stats::{welch_t_test, BenchmarkAnalysis, CycleAnalysis, PerCallAnalysis};
+
+/// A saved analysis together with the environment it was measured on
+#[derive(Debug, Clone)]
+pub struct BaselineEntry {
+    pub analysis: BenchmarkAnalysis,
+    pub environment: BTreeMap<String, String>,
+    pub recorded_at_unix: u64,
+}
+
+/// Named baselines stored in a plain-text file
+///
+/// The file holds one `[name]` section per benchmark followed by
+/// `key=value` lines, so it diffs cleanly when committed alongside code.
+pub struct BaselineStore {
+    path: PathBuf,
+    entries: BTreeMap<String, BaselineEntry>,
+}
+
+impl BaselineStore {
+    /// Load baselines from `path`; a missing file yields an empty store
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => parse_entries(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self { path, entries })
+    }
+
+    /// Write all baselines back to the store's file
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, format_entries(&self.entries))?;
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    /// Store `analysis` under its name with the current environment metadata
+    pub fn insert(&mut self, analysis: &BenchmarkAnalysis) {
+        self.insert_with_environment(analysis, capture_environment_metadata());
+    }
+
+    pub fn insert_with_environment(&mut self, analysis: &BenchmarkAnalysis, environment: BTreeMap<String, String>) {
+        let recorded_at_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.entries.insert(analysis.name.clone(), BaselineEntry {
+            analysis: analysis.clone(),
+            environment,
+            recorded_at_unix,
+        });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BaselineEntry> {
+        self.entries.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<BaselineEntry> {
+        self.entries.remove(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Compare `current` against the baseline of the same name
+    ///
+    /// Returns `None` when no baseline has been stored for that name yet.
+    pub fn compare(&self, current: &BenchmarkAnalysis, thresholds: &RegressionThresholds) -> Option<ComparisonReport> {
+        let entry = self.entries.get(&current.name)?;
+        let mut report = compare_analyses(&entry.analysis, current, thresholds);
+
+        let environment = capture_environment_metadata();
+        for key in ["arch", "cpu_model", "cpu_count"] {
+            if let (Some(before), Some(after)) = (entry.environment.get(key), environment.get(key)) {
+                if before != after {
+                    report.environment_changes.push(format!("{key}: {before} -> {after}"));
+                }
+            }
+        }
+        if let Some(change) = tsc_frequency_change(&entry.environment, &environment) {
+            report.environment_changes.push(change);
+        }
+        if report.verdict == Verdict::Pass && !report.environment_changes.is_empty() {
+            report.verdict = Verdict::EnvironmentChanged;
+        }
+
+        Some(report)
+    }
+}
+
+/// Stored results are nanoseconds either way, but a TSC rate that moved
+/// beyond both readings' error means different hardware or firmware
+/// settings (e.g. a changed base clock)
+///
+/// Only hardware and kernel readings are recorded, so sleep-calibration
+/// jitter never counts as a change.
+fn tsc_frequency_change(baseline: &BTreeMap<String, String>, current: &BTreeMap<String, String>) -> Option<String> {
+    let key = "tsc_frequency_khz";
+    let reading = |env: &BTreeMap<String, String>| -> Option<(u64, u64)> {
+        let khz = env.get(key)?.parse().ok()?;
+        let error_khz = env.get("tsc_frequency_error_khz").and_then(|e| e.parse().ok()).unwrap_or(0);
+        Some((khz, error_khz))
+    };
+    let ((before, before_error), (after, after_error)) = (reading(baseline)?, reading(current)?);
+    let tolerance = before_error + after_error + before / 1000;
+    (before.abs_diff(after) > tolerance).then(|| format!("{key}: {before} -> {after}"))
+}
+
+/// Limits a metric may move before it counts as a regression
+///
+/// A metric regresses only when it exceeds *both* the relative and the
+/// absolute threshold, so tiny absolute moves on very fast operations and
+/// small relative moves on slow ones are ignored.
+#[derive(Debug, Clone)]
+pub struct RegressionThresholds {
+    /// Allowed relative increase, e.g. `0.05` for 5%
+    pub relative: f64,
+    /// Allowed absolute increase in nanoseconds
+    pub absolute_ns: u64,
+    /// Significance level for Welch's t-test on the means
+    pub significance: f64,
+    /// Only report a mean regression when the t-test is also significant
+    ///
+    /// Percentiles are never gated on the t-test: a tail can regress while
+    /// the mean stays put. With confidence intervals on both analyses a
+    /// percentile only moves when its intervals don't overlap; without them
+    /// the thresholds alone decide.
+    pub require_significance: bool,
+}
+
+impl Default for RegressionThresholds {
+    fn default() -> Self {
+        Self {
+            relative: 0.05,
+            absolute_ns: 5,
+            significance: 0.01,
+            require_significance: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Improved,
+    Unchanged,
+    Regressed,
+}
+
+/// Movement of a single metric between baseline and current run
+#[derive(Debug, Clone)]
+pub struct MetricChange {
+    pub metric: &'static str,
+    pub baseline: u64,
+    pub current: u64,
+    pub delta_ns: i64,
+    pub relative: f64,
+    pub status: ChangeStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Regression,
+    /// No regression, but the baseline was recorded on a different machine
+    /// or TSC rate, so the comparison can't be trusted either way
+    EnvironmentChanged,
+}
+
+/// Structured result of comparing a run against its baseline
+#[derive(Debug, Clone)]
+pub struct ComparisonReport {
+    pub name: String,
+    pub changes: Vec<MetricChange>,
+    /// Two-sided p-value of Welch's t-test on the means
+    pub p_value: f64,
+    pub significant: bool,
+    pub verdict: Verdict,
+    /// Environment keys that differ from when the baseline was recorded
+    pub environment_changes: Vec<String>,
+}
+
+impl ComparisonReport {
+    pub fn is_regression(&self) -> bool {
+        self.verdict == Verdict::Regression
+    }
+
+    /// No regression, against a baseline from a comparable environment
+    pub fn passed(&self) -> bool {
+        self.verdict == Verdict::Pass
+    }
+
+    pub fn regressions(&self) -> impl Iterator<Item = &MetricChange> {
+        self.changes.iter().filter(|c| c.status == ChangeStatus::Regressed)
+    }
+
+    pub fn summary(&self) -> String {
+        let changes: Vec<String> = self.changes.iter()
+            .map(|c| format!("{}={}ns->{}ns ({:+.1}%)", c.metric, c.baseline, c.current, c.relative * 100.0))
+            .collect();
+
+        let mut summary = format!(
+            "{}: {:?} (p={:.4}) {}",
+            self.name, self.verdict, self.p_value, changes.join(", ")
+        );
+        if !self.environment_changes.is_empty() {
+            summary.push_str(&format!(" [environment changed: {}]", self.environment_changes.join("; ")));
+        }
+        summary
+    }
+}
+
+/// Compare two analyses directly, without a store
+pub fn compare_analyses(baseline: &BenchmarkAnalysis, current: &BenchmarkAnalysis, thresholds: &RegressionThresholds) -> ComparisonReport {
+    let metrics = [
+        ("mean", baseline.mean, current.mean),
+        ("p50", baseline.p50, current.p50),
+        ("p95", baseline.p95, current.p95),
+        ("p99", baseline.p99, current.p99),
+        ("p99.9", baseline.p999, current.p999),
+    ];
+
+    // With intervals on both sides a metric only moves if they don't overlap
+    let intervals = |ci: &Option<ConfidenceIntervals>| ci.as_ref().map(|ci| [ci.mean, ci.p50, ci.p95, ci.p99, ci.p999]);
+    let overlapping: [bool; 5] = match (intervals(&baseline.confidence), intervals(&current.confidence)) {
+        (Some(before), Some(after)) => std::array::from_fn(|i| before[i].overlaps(&after[i])),
+        _ => [false; 5],
+    };
+
+    let changes: Vec<MetricChange> = metrics.iter()
+        .zip(overlapping)
+        .map(|(&(metric, before, after), overlapping)| {
+            let delta_ns = after as i64 - before as i64;
+            let relative = if before == 0 { 0.0 } else { delta_ns as f64 / before as f64 };
+            let beyond = !overlapping
+                && delta_ns.unsigned_abs() > thresholds.absolute_ns
+                && relative.abs() > thresholds.relative;
+
+            let status = match (beyond, delta_ns > 0) {
+                (true, true) => ChangeStatus::Regressed,
+                (true, false) => ChangeStatus::Improved,
+                (false, _) => ChangeStatus::Unchanged,
+            };
+
+            MetricChange { metric, baseline: before, current: after, delta_ns, relative, status }
+        })
+        .collect();
+
+    let p_value = welch_t_test(baseline, current);
+    let significant = p_value < thresholds.significance;
+    // Percentile changes were already tested against their own intervals
+    let regressed = changes.iter()
+        .filter(|c| c.status == ChangeStatus::Regressed)
+        .any(|c| c.metric != "mean" || significant || !thresholds.require_significance);
+
+    let verdict = if regressed {
+        Verdict::Regression
+    } else {
+        Verdict::Pass
+    };
+
+    ComparisonReport {
+        name: current.name.clone(),
+        changes,
+        p_value,
+        significant,
+        verdict,
+        environment_changes: Vec::new(),
+    }
+}
+
+/// Metadata describing the machine a baseline was recorded on
+pub fn capture_environment_metadata() -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::new();
+
+    metadata.insert("arch".to_string(), std::env::consts::ARCH.to_string());
+    metadata.insert("os".to_string(), std::env::consts::OS.to_string());
+    metadata.insert("cpu_count".to_string(), num_cpus::get().to_string());
+    // Read from hardware or the kernel rather than the global calibration,
+    // which may be a sleep estimate or the uncalibrated default
+    if let Some(estimate) = crate::calibration::frequency_from_hardware() {
+        metadata.insert("tsc_frequency_khz".to_string(), estimate.khz.to_string());
+        metadata.insert("tsc_frequency_error_khz".to_string(), estimate.error_khz.to_string());
+        metadata.insert("tsc_frequency_source".to_string(), format!("{:?}", estimate.source));
+    }
+
+    if let Ok(cpuinfo) = fs::read_to_string("/proc/cpuinfo") {
+        if let Some(model) = cpuinfo.lines()
+            .find(|line| line.starts_with("model name"))
+            .and_then(|line| line.split(':').nth(1))
+        {
+            metadata.insert("cpu_model".to_string(), model.trim().to_string());
+        }
+    }
+
+    if let Ok(release) = fs::read_to_string("/proc/sys/kernel/osrelease") {
+        metadata.insert("kernel".to_string(), release.trim().to_string());
+    }
+
+    metadata
+}
+
+fn format_entries(entries: &BTreeMap<String, BaselineEntry>) -> String {
+    let mut out = String::new();
+
+    for (name, entry) in entries {
+        let a = &entry.analysis;
+        out.push_str(&format!("[{}]\n", sanitize(name)));
+        out.push_str(&format!("recorded_at={}\n", entry.recorded_at_unix));
+        out.push_str(&format!("count={}\nmin={}\nmax={}\nmean={}\n", a.count, a.min, a.max, a.mean));
+        out.push_str(&format!("p50={}\np95={}\np99={}\np999={}\n", a.p50, a.p95, a.p99, a.p999));
+        out.push_str(&format!("std_dev={}\n", a.std_dev));
+        if let Some(ci) = &a.confidence {
+            out.push_str(&format!("ci_level={}\n", ci.level));
+            out.push_str(&format!("ci_mean={},{}\n", ci.mean.lower, ci.mean.upper));
+            out.push_str(&format!("ci_p50={},{}\nci_p95={},{}\n", ci.p50.lower, ci.p50.upper, ci.p95.lower, ci.p95.upper));
+            out.push_str(&format!("ci_p99={},{}\nci_p999={},{}\n", ci.p99.lower, ci.p99.upper, ci.p999.lower, ci.p999.upper));
+            let flagged: Vec<String> = ci.insufficient_samples.iter().map(f64::to_string).collect();
+            out.push_str(&format!("ci_insufficient={}\n", flagged.join(",")));
+        }
+        if let Some(p) = &a.per_call {
+            out.push_str(&format!(
+                "per_call={},{},{},{},{},{},{},{},{}\n",
+                p.batch_size, p.min, p.max, p.mean, p.p50, p.p95, p.p99, p.p999, p.std_dev
+            ));
+        }
+        if let Some(c) = &a.cycles {
+            out.push_str(&format!(
+                "cycles={},{},{},{},{},{},{},{},{}\n",
+                c.frequency_khz, c.min, c.max, c.mean, c.p50, c.p95, c.p99, c.p999, c.std_dev
+            ));
+        }
+        for (key, value) in &entry.environment {
+            out.push_str(&format!("env.{}={}\n", sanitize(key), sanitize(value)));
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+fn parse_entries(contents: &str) -> io::Result<BTreeMap<String, BaselineEntry>> {
+    let mut entries = BTreeMap::new();
+    let mut current: Option<BaselineEntry> = None;
+
+    let invalid = |line_no: usize, msg: &str| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("baseline line {}: {}", line_no + 1, msg))
+    };
+
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            if let Some(entry) = current.take() {
+                entries.insert(entry.analysis.name.clone(), entry);
+            }
+            current = Some(BaselineEntry {
+                analysis: BenchmarkAnalysis::empty(line[1..line.len() - 1].to_string()),
+                environment: BTreeMap::new(),
+                recorded_at_unix: 0,
+            });
+            continue;
+        }
+
+        let entry = current.as_mut().ok_or_else(|| invalid(line_no, "value outside of a [section]"))?;
+        let (key, value) = line.split_once('=').ok_or_else(|| invalid(line_no, "expected key=value"))?;
+
+        if let Some(env_key) = key.strip_prefix("env.") {
+            entry.environment.insert(env_key.to_string(), value.to_string());
+            continue;
+        }
+
+        let parse_u64 = |v: &str| v.parse::<u64>().map_err(|_| invalid(line_no, "expected integer"));
+        let parse_interval = |v: &str| -> io::Result<Interval> {
+            let (lower, upper) = v.split_once(',').ok_or_else(|| invalid(line_no, "expected lower,upper"))?;
+            Ok(Interval { lower: parse_u64(lower)?, upper: parse_u64(upper)? })
+        };
+        // A unit (batch size or frequency) followed by the eight statistics
+        let parse_stats = |v: &str| -> io::Result<(u64, [f64; 8])> {
+            let mut fields = v.split(',');
+            let unit = parse_u64(fields.next().unwrap_or_default())?;
+            let mut stats = [0.0; 8];
+            for stat in &mut stats {
+                *stat = fields.next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(|| invalid(line_no, "expected nine comma-separated numbers"))?;
+            }
+            Ok((unit, stats))
+        };
+        let a = &mut entry.analysis;
+        if key.starts_with("ci_") {
+            let ci = a.confidence.get_or_insert_with(|| ConfidenceIntervals {
+                level: 0.0,
+                method: IntervalMethod::OrderStatistic,
+                mean: Interval::default(),
+                p50: Interval::default(),
+                p95: Interval::default(),
+                p99: Interval::default(),
+                p999: Interval::default(),
+                insufficient_samples: Vec::new(),
+            });
+            match key {
+                "ci_level" => ci.level = value.parse().map_err(|_| invalid(line_no, "expected number"))?,
+                "ci_mean" => ci.mean = parse_interval(value)?,
+                "ci_p50" => ci.p50 = parse_interval(value)?,
+                "ci_p95" => ci.p95 = parse_interval(value)?,
+                "ci_p99" => ci.p99 = parse_interval(value)?,
+                "ci_p999" => ci.p999 = parse_interval(value)?,
+                "ci_insufficient" => {
+                    ci.insufficient_samples = value.split(',')
+                        .filter(|p| !p.is_empty())
+                        .map(|p| p.parse().map_err(|_| invalid(line_no, "expected number")))
+                        .collect::<io::Result<_>>()?;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match key {
+            "recorded_at" => entry.recorded_at_unix = parse_u64(value)?,
+            "count" => a.count = parse_u64(value)? as usize,
+            "min" => a.min = parse_u64(value)?,
+            "max" => a.max = parse_u64(value)?,
+            "mean" => a.mean = parse_u64(value)?,
+            "p50" => a.p50 = parse_u64(value)?,
+            "p95" => a.p95 = parse_u64(value)?,
+            "p99" => a.p99 = parse_u64(value)?,
+            "p999" => a.p999 = parse_u64(value)?,
+            "std_dev" => a.std_dev = value.parse().map_err(|_| invalid(line_no, "expected number"))?,
+            "per_call" => {
+                let (batch_size, [min, max, mean, p50, p95, p99, p999, std_dev]) = parse_stats(value)?;
+                a.batch_size = Some(batch_size);
+                a.per_call = Some(PerCallAnalysis { batch_size, min, max, mean, p50, p95, p99, p999, std_dev });
+            }
+            "cycles" => {
+                let (frequency_khz, [min, max, mean, p50, p95, p99, p999, std_dev]) = parse_stats(value)?;
+                a.cycles = Some(CycleAnalysis {
+                    frequency_khz, min, max, mean, p50, p95, p99, p999, std_dev,
+                    percentiles: Vec::new(),
+                });
+            }
+            _ => {} // Unknown keys are ignored for forward compatibility
+        }
+    }
+
+    if let Some(entry) = current.take() {
+        entries.insert(entry.analysis.name.clone(), entry);
+    }
+
+    Ok(entries)
+}
+
+fn sanitize(value: &str) -> String {
+    value.replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::BenchmarkResults;
+    use crate::test_util::TempPath;
+
+    fn analysis_from(name: &str, base: u64, spread: u64) -> BenchmarkAnalysis {
+        let mut results = BenchmarkResults::new(name.to_string());
+        for i in 0..1000 {
+            results.record(base + (i * 7919) % spread);
+        }
+        results.analyze()
+    }
+
+    #[test]
+    fn test_store_roundtrip() {
+        let path = TempPath::new("roundtrip.baseline");
+        let analysis = analysis_from("order_entry", 100, 50);
+
+        let mut store = BaselineStore::open(&path).unwrap();
+        assert!(store.is_empty());
+        store.insert(&analysis);
+        store.save().unwrap();
+
+        let reloaded = BaselineStore::open(&path).unwrap();
+        let entry = reloaded.get("order_entry").expect("baseline saved");
+        assert_eq!(entry.analysis.count, analysis.count);
+        assert_eq!(entry.analysis.p99, analysis.p99);
+        assert_eq!(entry.analysis.p999, analysis.p999);
+        assert!((entry.analysis.std_dev - analysis.std_dev).abs() < 1e-9);
+        assert_eq!(entry.environment.get("arch").map(String::as_str), Some(std::env::consts::ARCH));
+    }
+
+    #[test]
+    fn test_confidence_roundtrip_and_overlap() {
+        use crate::confidence::ConfidenceConfig;
+        use crate::stats::AnalysisOptions;
+
+        let options = AnalysisOptions::default().with_confidence(ConfidenceConfig::order_statistic(0.95));
+        let mut results = BenchmarkResults::new("ci_path".to_string());
+        for i in 0..200 {
+            results.record(100 + (i * 7919) % 400);
+        }
+        let baseline = results.analyze_with(&options);
+
+        let path = TempPath::new("confidence.baseline");
+        let mut store = BaselineStore::open(&path).unwrap();
+        store.insert(&baseline);
+        store.save().unwrap();
+        let reloaded = BaselineStore::open(&path).unwrap();
+
+        let ci = reloaded.get("ci_path").unwrap().analysis.confidence.clone().expect("intervals persisted");
+        let original = baseline.confidence.as_ref().unwrap();
+        assert_eq!(ci.p99, original.p99);
+        assert_eq!(ci.insufficient_samples, original.insufficient_samples);
+
+        // Point estimates 10% slower but still inside the baseline's intervals
+        let current = BenchmarkAnalysis {
+            mean: baseline.mean * 11 / 10,
+            p50: baseline.p50 * 11 / 10,
+            p95: baseline.p95 * 11 / 10,
+            p99: baseline.p99 * 11 / 10,
+            p999: baseline.p999 * 11 / 10,
+            ..baseline.clone()
+        };
+        let thresholds = RegressionThresholds { require_significance: false, ..Default::default() };
+
+        assert!(compare_analyses(&baseline, &current, &thresholds).changes.iter()
+            .all(|c| c.status == ChangeStatus::Unchanged));
+        let strip = |a: &BenchmarkAnalysis| BenchmarkAnalysis { confidence: None, ..a.clone() };
+        assert!(compare_analyses(&strip(&baseline), &strip(&current), &thresholds).is_regression());
+    }
+
+    #[test]
+    fn test_detects_regression() {
+        let baseline = analysis_from("hot_path", 100, 50);
+        let slower = analysis_from("hot_path", 200, 50);
+
+        let report = compare_analyses(&baseline, &slower, &RegressionThresholds::default());
+        assert!(report.is_regression());
+        assert!(report.significant);
+        assert!(report.regressions().any(|c| c.metric == "p99"));
+    }
+
+    #[test]
+    fn test_tail_only_regression() {
+        use crate::confidence::ConfidenceConfig;
+        use crate::stats::AnalysisOptions;
+
+        let options = AnalysisOptions::default().with_confidence(ConfidenceConfig::order_statistic(0.95));
+        let mut before = BenchmarkResults::new("tail".to_string());
+        let mut after = BenchmarkResults::new("tail".to_string());
+        for i in 0..1000 {
+            let value = 100 + (i * 7919) % 50;
+            before.record(value);
+            // 2% of calls hit a slow path; the rest got slightly faster, so
+            // the mean barely moves
+            after.record(if i % 50 == 0 { 370 } else { value - 5 });
+        }
+        let baseline = before.analyze_with(&options);
+        let current = after.analyze_with(&options);
+
+        let report = compare_analyses(&baseline, &current, &RegressionThresholds::default());
+        assert!(!report.significant, "p={}", report.p_value);
+        assert!(report.is_regression(), "{}", report.summary());
+        assert!(report.regressions().all(|c| c.metric == "p99" || c.metric == "p99.9"));
+
+        // Same without intervals: the thresholds decide the percentiles
+        let strip = |a: &BenchmarkAnalysis| BenchmarkAnalysis { confidence: None, ..a.clone() };
+        assert!(compare_analyses(&strip(&baseline), &strip(&current), &RegressionThresholds::default()).is_regression());
+    }
+
+    #[test]
+    fn test_tsc_frequency_change_is_reported() {
+        let reading = |khz: u64, error_khz: u64| BTreeMap::from([
+            ("tsc_frequency_khz".to_string(), khz.to_string()),
+            ("tsc_frequency_error_khz".to_string(), error_khz.to_string()),
+        ]);
+        // Within a per mille, or within the readings' own error
+        assert_eq!(tsc_frequency_change(&reading(3_000_000, 0), &reading(3_002_000, 0)), None);
+        assert_eq!(tsc_frequency_change(&reading(2_995_000, 5_000), &reading(3_004_000, 5_000)), None);
+        assert!(tsc_frequency_change(&reading(3_000_000, 0), &reading(2_400_000, 0)).is_some());
+        // Baselines without a hardware reading (or from older versions) aren't checked
+        assert_eq!(tsc_frequency_change(&BTreeMap::new(), &reading(3_000_000, 0)), None);
+
+        let path = TempPath::new("tsc.baseline");
+        let analysis = analysis_from("tsc_path", 100, 50);
+        let mut environment = capture_environment_metadata();
+        if !environment.contains_key("tsc_frequency_khz") {
+            // No hardware reading on this machine, so nothing to compare
+            return;
+        }
+        environment.insert("tsc_frequency_khz".to_string(), "1".to_string());
+
+        let mut store = BaselineStore::open(&path).unwrap();
+        store.insert_with_environment(&analysis, environment);
+        let report = store.compare(&analysis, &RegressionThresholds::default()).unwrap();
+        assert!(report.environment_changes.iter().any(|c| c.starts_with("tsc_frequency_khz: 1 -> ")));
+        assert_eq!(report.verdict, Verdict::EnvironmentChanged);
+        assert!(!report.passed() && !report.is_regression());
+    }
+
+    #[test]
+    fn test_per_call_and_cycles_roundtrip() {
+        let path = TempPath::new("precision.baseline");
+        let results = |name: &str| {
+            let mut results = BenchmarkResults::new(name.to_string());
+            for i in 0..100 {
+                results.record(1_000 + i % 7);
+            }
+            results
+        };
+        let batched = results("batched").per_call(1_000).analyze();
+        let ticks = results("ticks").in_cycles(2_999_500).analyze();
+
+        let mut store = BaselineStore::open(&path).unwrap();
+        store.insert(&batched);
+        store.insert(&ticks);
+        store.save().unwrap();
+        let reloaded = BaselineStore::open(&path).unwrap();
+
+        let per_call = reloaded.get("batched").unwrap().analysis.per_call.clone().expect("per-call persisted");
+        assert_eq!(per_call.batch_size, 1_000);
+        assert_eq!(per_call.p50, batched.per_call.as_ref().unwrap().p50);
+        assert_eq!(reloaded.get("batched").unwrap().analysis.batch_size, Some(1_000));
+        let cycles = reloaded.get("ticks").unwrap().analysis.cycles.clone().expect("cycles persisted");
+        assert_eq!(cycles.frequency_khz, 2_999_500);
+        assert_eq!(cycles.mean, ticks.cycles.as_ref().unwrap().mean);
+        assert!(parse_entries("[a]\ncycles=1,2,3\n").is_err());
+    }
+
+    #[test]
+    fn test_identical_runs_pass() {
+        let baseline = analysis_from("hot_path", 100, 50);
+        let current = analysis_from("hot_path", 100, 50);
+
+        let report = compare_analyses(&baseline, &current, &RegressionThresholds::default());
+        assert_eq!(report.verdict, Verdict::Pass);
+        assert!(report.changes.iter().all(|c| c.status == ChangeStatus::Unchanged));
+    }
+
+    #[test]
+    fn test_thresholds_ignore_small_changes() {
+        let baseline = analysis_from("tiny", 10, 4);
+        let current = analysis_from("tiny", 13, 4);
+
+        // +30% but only +3ns, below the 5ns absolute threshold
+        let report = compare_analyses(&baseline, &current, &RegressionThresholds::default());
+        assert_eq!(report.verdict, Verdict::Pass);
+
+        let strict = RegressionThresholds { absolute_ns: 1, ..Default::default() };
+        assert!(compare_analyses(&baseline, &current, &strict).is_regression());
+    }
+
+    #[test]
+    fn test_compare_without_baseline() {
+        let store = BaselineStore::open(TempPath::new("missing.baseline")).unwrap();
+        let analysis = analysis_from("unknown", 100, 10);
+        assert!(store.compare(&analysis, &RegressionThresholds::default()).is_none());
+    }
+
+    #[test]
+    fn test_rejects_malformed_file() {
+        assert!(parse_entries("count=5\n").is_err());
+        assert!(parse_entries("[a]\ncount=abc\n").is_err());
+        assert!(parse_entries("[a]\nfuture_field=1\n").is_ok());
+    }
+}
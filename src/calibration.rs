@@ -3,37 +3,376 @@
 extern crate std;
 use std::time::{Duration, Instant};
 
+/// Where a counter frequency came from, most trustworthy first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrequencySource {
+    /// CPUID leaf 0x15: crystal clock times the TSC/crystal ratio
+    CpuidTscLeaf,
+    /// The kernel's `tsc_khz`, from sysfs (out-of-tree kernels only)
+    KernelSysfs,
+    /// The kernel's `tsc_khz`, derived from the perf mmap page's time conversion
+    PerfMmapPage,
+    /// CPUID leaf 0x16: nominal base frequency in whole MHz, which is close
+    /// to but not necessarily the TSC rate
+    CpuidBaseFrequency,
+    /// aarch64 `cntfrq_el0`
+    CounterFrequencyRegister,
+    /// Median of repeated sleeps timed against `Instant`
+    SleepCalibration,
+}
+
+/// A counter frequency and how far it may be off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrequencyEstimate {
+    pub khz: u64,
+    pub source: FrequencySource,
+    /// Uncertainty of `khz`; for sleep calibration the median absolute
+    /// deviation across trials
+    pub error_khz: u64,
+}
+
+impl FrequencyEstimate {
+    pub fn mhz(&self) -> u64 {
+        (self.khz + 500) / 1000
+    }
+}
+
 pub fn calibrate_tsc_frequency() -> u64 {
-    calibrate_with_duration_ms(1000)
+    let estimate = detect_frequency(5, 200);
+    
+    #[cfg(target_arch = "x86_64")]
+    println!("Calibrated TSC frequency: {} MHz (from {:?}, ±{} kHz)", estimate.mhz(), estimate.source, estimate.error_khz);
+    #[cfg(target_arch = "aarch64")]
+    println!("Calibrated ARM counter frequency: {} MHz (from {:?})", estimate.mhz(), estimate.source);
+    
+    estimate.mhz()
 }
 
 pub fn quick_calibrate_tsc_frequency() -> u64 {
-    calibrate_with_duration_ms(100)
+    detect_frequency(5, 20).mhz()
+}
+
+/// Find the counter frequency from the first source that provides one
+///
+/// Hardware and kernel sources are read directly; only when none is
+/// available does this fall back to sleep calibration (five 200ms trials).
+/// The result is stored for `PrecisionTimer`.
+pub fn detect_tsc_frequency() -> FrequencyEstimate {
+    detect_frequency(5, 200)
+}
+
+/// Like `detect_tsc_frequency`, without storing the result globally
+pub fn estimate_tsc_frequency() -> FrequencyEstimate {
+    estimate_frequency(5, 200)
+}
+
+fn estimate_frequency(fallback_trials: usize, fallback_trial_ms: u64) -> FrequencyEstimate {
+    frequency_from_hardware()
+        .unwrap_or_else(|| calibrate_by_sleep(fallback_trials, fallback_trial_ms))
+}
+
+fn detect_frequency(fallback_trials: usize, fallback_trial_ms: u64) -> FrequencyEstimate {
+    let estimate = estimate_frequency(fallback_trials, fallback_trial_ms);
+    crate::mock_core::set_cpu_frequency_khz(estimate.khz);
+    estimate
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn frequency_from_hardware() -> Option<FrequencyEstimate> {
+    // Anything outside this range is a hypervisor or firmware artefact
+    let plausible = |e: &FrequencyEstimate| (100_000..=10_000_000).contains(&e.khz);
+    
+    frequency_from_tsc_leaf().filter(plausible)
+        .or_else(|| frequency_from_sysfs().filter(plausible))
+        .or_else(|| frequency_from_perf_mmap().filter(plausible))
+        .or_else(|| frequency_from_base_leaf().filter(plausible))
+}
+
+#[cfg(target_arch = "aarch64")]
+pub(crate) fn frequency_from_hardware() -> Option<FrequencyEstimate> {
+    let counter_freq = get_counter_frequency();
+    (counter_freq > 0).then(|| FrequencyEstimate {
+        khz: counter_freq / 1000,
+        source: FrequencySource::CounterFrequencyRegister,
+        error_khz: 0,
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+fn frequency_from_tsc_leaf() -> Option<FrequencyEstimate> {
+    use core::arch::x86_64::__cpuid;
+    
+    if __cpuid(0).eax < 0x15 {
+        return None;
+    }
+    let leaf = __cpuid(0x15);
+    let khz = khz_from_tsc_leaf(leaf.eax, leaf.ebx, leaf.ecx)?;
+    Some(FrequencyEstimate { khz, source: FrequencySource::CpuidTscLeaf, error_khz: 0 })
 }
 
-fn calibrate_with_duration_ms(duration_ms: u64) -> u64 {
-    let start_time = Instant::now();
-    let start_counter = read_timestamp();
+/// Last resort before sleeping: the base frequency is only a proxy for the
+/// TSC rate, so it ranks below the kernel's measured `tsc_khz`
+#[cfg(target_arch = "x86_64")]
+fn frequency_from_base_leaf() -> Option<FrequencyEstimate> {
+    use core::arch::x86_64::__cpuid;
     
-    std::thread::sleep(Duration::from_millis(duration_ms));
+    if __cpuid(0).eax < 0x16 {
+        return None;
+    }
+    estimate_from_base_mhz((__cpuid(0x16).eax & 0xffff) as u64)
+}
+
+/// Parts whose TSC doesn't run at the nominal base frequency are commonly a
+/// few tenths of a percent off and occasionally more, so allow 2%
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+fn estimate_from_base_mhz(base_mhz: u64) -> Option<FrequencyEstimate> {
+    (base_mhz > 0).then(|| FrequencyEstimate {
+        khz: base_mhz * 1000,
+        source: FrequencySource::CpuidBaseFrequency,
+        error_khz: base_mhz * 1000 / 50,
+    })
+}
+
+/// TSC frequency from CPUID leaf 0x15 (EAX denominator, EBX numerator, ECX crystal Hz)
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+fn khz_from_tsc_leaf(denominator: u32, numerator: u32, crystal_hz: u32) -> Option<u64> {
+    if denominator == 0 || numerator == 0 || crystal_hz == 0 {
+        return None;
+    }
+    Some(crystal_hz as u64 * numerator as u64 / denominator as u64 / 1000)
+}
+
+/// Read `tsc_freq_khz` from sysfs
+///
+/// Mainline kernels don't expose this file; only kernels carrying an
+/// out-of-tree patch do. Elsewhere the perf mmap page supplies the same
+/// `tsc_khz`.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn frequency_from_sysfs() -> Option<FrequencyEstimate> {
+    let contents = std::fs::read_to_string("/sys/devices/system/cpu/cpu0/tsc_freq_khz").ok()?;
+    let khz = contents.trim().parse().ok()?;
+    Some(FrequencyEstimate { khz, source: FrequencySource::KernelSysfs, error_khz: 1 })
+}
+
+#[cfg(all(target_arch = "x86_64", not(target_os = "linux")))]
+fn frequency_from_sysfs() -> Option<FrequencyEstimate> {
+    None
+}
+
+/// Recover `tsc_khz` from the `time_mult`/`time_shift` pair the kernel
+/// publishes for converting TSC cycles to nanoseconds in user space
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn frequency_from_perf_mmap() -> Option<FrequencyEstimate> {
+    /// The first 64 bytes of `struct perf_event_attr` (PERF_ATTR_SIZE_VER0)
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+    }
     
-    let end_time = Instant::now();
-    let end_counter = read_timestamp();
+    const PERF_TYPE_SOFTWARE: u32 = 1;
+    const PERF_COUNT_SW_DUMMY: u64 = 9;
+    const FLAG_DISABLED: u64 = 1 << 0;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
     
-    let elapsed_ns = end_time.duration_since(start_time).as_nanos() as u64;
-    let counter_cycles = end_counter - start_counter;
+    // Offsets into `struct perf_event_mmap_page`
+    const CAPABILITIES_OFFSET: usize = 40;
+    const TIME_SHIFT_OFFSET: usize = 50;
+    const TIME_MULT_OFFSET: usize = 52;
+    const CAP_USER_TIME: u64 = 1 << 3;
     
-    let frequency_mhz = calculate_frequency_mhz(counter_cycles, elapsed_ns);
+    let attr = PerfEventAttr {
+        type_: PERF_TYPE_SOFTWARE,
+        size: std::mem::size_of::<PerfEventAttr>() as u32,
+        config: PERF_COUNT_SW_DUMMY,
+        flags: FLAG_DISABLED | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+        ..Default::default()
+    };
     
-    if duration_ms >= 1000 {
-        #[cfg(target_arch = "x86_64")]
-        println!("Calibrated TSC frequency: {frequency_mhz} MHz");
-        #[cfg(target_arch = "aarch64")]
-        println!("Calibrated ARM counter frequency: {} MHz", frequency_mhz);
+    unsafe {
+        let fd = libc::syscall(libc::SYS_perf_event_open, &attr as *const PerfEventAttr, 0, -1, -1, 0) as libc::c_int;
+        if fd < 0 {
+            return None;
+        }
+        
+        let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+        let page = libc::mmap(std::ptr::null_mut(), page_size, libc::PROT_READ, libc::MAP_SHARED, fd, 0);
+        if page == libc::MAP_FAILED {
+            libc::close(fd);
+            return None;
+        }
+        
+        let base = page as *const u8;
+        let capabilities = std::ptr::read_volatile(base.add(CAPABILITIES_OFFSET) as *const u64);
+        let time_shift = std::ptr::read_volatile(base.add(TIME_SHIFT_OFFSET) as *const u16);
+        let time_mult = std::ptr::read_volatile(base.add(TIME_MULT_OFFSET) as *const u32);
+        
+        libc::munmap(page, page_size);
+        libc::close(fd);
+        
+        if capabilities & CAP_USER_TIME == 0 {
+            return None;
+        }
+        let khz = khz_from_perf_time(time_mult, time_shift)?;
+        Some(FrequencyEstimate { khz, source: FrequencySource::PerfMmapPage, error_khz: 1 })
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(target_os = "linux")))]
+fn frequency_from_perf_mmap() -> Option<FrequencyEstimate> {
+    None
+}
+
+/// ns = cycles * mult >> shift, so cycles per ms = 10^6 * 2^shift / mult
+#[cfg_attr(not(all(target_arch = "x86_64", target_os = "linux")), allow(dead_code))]
+fn khz_from_perf_time(time_mult: u32, time_shift: u16) -> Option<u64> {
+    if time_mult == 0 || time_shift >= 64 {
+        return None;
     }
+    Some((((1_000_000u128) << time_shift) / time_mult as u128) as u64)
+}
+
+/// Time `trials` sleeps of `trial_ms` against `Instant` and take the median
+pub fn calibrate_by_sleep(trials: usize, trial_ms: u64) -> FrequencyEstimate {
+    let mut trial_khz: Vec<u64> = (0..trials.max(1))
+        .map(|_| sleep_trial_khz(trial_ms))
+        .collect();
+    trial_khz.sort_unstable();
+    let median = trial_khz[trial_khz.len() / 2];
+    
+    let mut deviations: Vec<u64> = trial_khz.iter().map(|&khz| khz.abs_diff(median)).collect();
+    deviations.sort_unstable();
     
-    crate::mock_core::set_cpu_frequency_mhz(frequency_mhz);
-    frequency_mhz
+    FrequencyEstimate {
+        khz: median,
+        source: FrequencySource::SleepCalibration,
+        error_khz: deviations[deviations.len() / 2],
+    }
+}
+
+fn sleep_trial_khz(duration_ms: u64) -> u64 {
+    loop {
+        let start_time = Instant::now();
+        let start_counter = read_timestamp();
+        
+        std::thread::sleep(Duration::from_millis(duration_ms));
+        
+        let end_time = Instant::now();
+        let end_counter = read_timestamp();
+        
+        // The thread woke on a core whose counter lags; discard and retry
+        let Some(counter_cycles) = end_counter.checked_sub(start_counter) else { continue };
+        let elapsed_ns = end_time.duration_since(start_time).as_nanos() as u64;
+        return calculate_frequency_khz(counter_cycles, elapsed_ns);
+    }
+}
+
+/// Cost and granularity of `PrecisionTimer`, in nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerCalibration {
+    pub overhead_min_ns: u64,
+    pub overhead_median_ns: u64,
+    pub overhead_p99_ns: u64,
+    /// Smallest nonzero difference between back-to-back counter reads
+    pub resolution_ns: u64,
+}
+
+/// Measure empty start/stop pairs and the counter's resolution
+///
+/// The median overhead and the resolution are stored next to the calibrated
+/// frequency, where `time_function_compensated` and `SimpleBench` pick them
+/// up. Run after frequency calibration so both are converted correctly.
+pub fn calibrate_timer_overhead() -> TimerCalibration {
+    calibrate_timer_overhead_with_samples(10_000)
+}
+
+fn calibrate_timer_overhead_with_samples(samples: usize) -> TimerCalibration {
+    let calibration = measure_timer(&crate::clock::Clock::global(), samples);
+    crate::mock_core::set_timer_overhead_ns(calibration.overhead_median_ns);
+    crate::mock_core::set_timer_resolution_ns(calibration.resolution_ns);
+    calibration
+}
+
+/// Measure timer overhead and resolution using `clock`'s frequency and
+/// serialization, without storing anything
+pub(crate) fn measure_timer(clock: &crate::clock::Clock, samples: usize) -> TimerCalibration {
+    let samples = samples.max(1);
+    
+    let mut overheads: Vec<u64> = (0..samples)
+        .map(|_| crate::timing::PrecisionTimer::start_on(clock).stop())
+        .collect();
+    overheads.sort_unstable();
+    
+    let mut min_ticks = u64::MAX;
+    for _ in 0..samples {
+        let first = read_timestamp();
+        let mut second = read_timestamp();
+        while second == first {
+            second = read_timestamp();
+        }
+        min_ticks = min_ticks.min(second.wrapping_sub(first));
+    }
+    
+    TimerCalibration {
+        overhead_min_ns: overheads[0],
+        overhead_median_ns: overheads[samples / 2],
+        overhead_p99_ns: overheads[(samples * 99 / 100).min(samples - 1)],
+        resolution_ns: ticks_to_ns_ceil(min_ticks, clock.frequency_khz()).max(1),
+    }
+}
+
+/// Empty start/stop cost of one `TimestampSerialization` mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializationOverhead {
+    pub serialization: crate::timing::TimestampSerialization,
+    pub min_ns: u64,
+    pub median_ns: u64,
+    pub p99_ns: u64,
+}
+
+/// Measure the empty-timer overhead of every serialization mode
+pub fn compare_serialization_overhead(samples: usize) -> Vec<SerializationOverhead> {
+    use crate::timing::{PrecisionTimer, TimestampSerialization};
+    
+    let samples = samples.max(1);
+    TimestampSerialization::ALL.iter().map(|&serialization| {
+        let mut overheads: Vec<u64> = (0..samples)
+            .map(|_| PrecisionTimer::start_with(serialization).stop())
+            .collect();
+        overheads.sort_unstable();
+        
+        SerializationOverhead {
+            serialization,
+            min_ns: overheads[0],
+            median_ns: overheads[samples / 2],
+            p99_ns: overheads[(samples * 99 / 100).min(samples - 1)],
+        }
+    }).collect()
+}
+
+#[cfg(target_arch = "x86_64")]
+fn ticks_to_ns_ceil(ticks: u64, frequency_khz: u64) -> u64 {
+    (ticks * 1_000_000).div_ceil(frequency_khz.max(1))
+}
+
+#[cfg(target_arch = "aarch64")]
+fn ticks_to_ns_ceil(ticks: u64, frequency_khz: u64) -> u64 {
+    let counter_freq = get_counter_frequency();
+    if counter_freq > 0 {
+        (ticks * 1_000_000_000).div_ceil(counter_freq)
+    } else {
+        (ticks * 1_000_000).div_ceil(frequency_khz.max(1))
+    }
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -48,27 +387,23 @@ fn read_timestamp() -> u64 {
     crate::timing::read_timestamp()
 }
 
-fn calculate_frequency_mhz(counter_cycles: u64, elapsed_ns: u64) -> u64 {
-    #[cfg(target_arch = "x86_64")]
-    {
-        (counter_cycles * 1000) / elapsed_ns
-    }
-    #[cfg(target_arch = "aarch64")]
-    {
-        let counter_freq = unsafe {
-            let freq: u64;
-            std::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack));
-            freq
-        };
-        
-        if counter_freq > 0 {
-            counter_freq / 1_000_000
-        } else {
-            (counter_cycles * 1000) / elapsed_ns
-        }
+fn calculate_frequency_khz(counter_cycles: u64, elapsed_ns: u64) -> u64 {
+    (counter_cycles as u128 * 1_000_000 / elapsed_ns.max(1) as u128) as u64
+}
+
+#[cfg(target_arch = "aarch64")]
+fn get_counter_frequency() -> u64 {
+    unsafe {
+        let freq: u64;
+        std::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack));
+        freq
     }
 }
 
+/// Current clock of the first core in `/proc/cpuinfo`
+///
+/// This follows frequency scaling and is not the TSC rate; use
+/// `detect_tsc_frequency` for converting cycles.
 #[cfg(target_os = "linux")]
 pub fn get_cpu_frequency_from_proc() -> Option<u64> {
     use std::fs;
@@ -99,7 +434,8 @@ mod tests {
     
     #[test]
     fn test_tsc_calibration() {
-        let freq = calibrate_tsc_frequency();
+        // A local clock: other tests rewrite the global calibration in parallel
+        let freq = crate::clock::Clock::from_estimate(estimate_tsc_frequency()).frequency_mhz();
         
         #[cfg(target_arch = "x86_64")]
         {
@@ -111,8 +447,6 @@ mod tests {
             assert!(freq >= 10, "Counter frequency too low: {} MHz", freq);
             assert!(freq <= 100, "Counter frequency too high: {} MHz", freq);
         }
-        
-        assert_eq!(crate::mock_core::cpu_frequency_mhz(), freq);
     }
     
     #[test]
@@ -131,6 +465,56 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_timer_overhead_calibration() {
+        let clock = crate::clock::Clock::from_estimate(estimate_frequency(5, 20));
+        let calibration = measure_timer(&clock, 1000);
+        
+        assert!(calibration.overhead_min_ns <= calibration.overhead_median_ns);
+        assert!(calibration.overhead_median_ns <= calibration.overhead_p99_ns);
+        assert!(calibration.resolution_ns >= 1);
+    }
+    
+    #[test]
+    fn test_serialization_overhead_comparison() {
+        use crate::timing::TimestampSerialization;
+        
+        quick_calibrate_tsc_frequency();
+        let comparison = compare_serialization_overhead(200);
+        
+        assert_eq!(comparison.len(), TimestampSerialization::ALL.len());
+        for overhead in &comparison {
+            assert!(overhead.min_ns <= overhead.median_ns);
+            assert!(overhead.median_ns <= overhead.p99_ns);
+        }
+    }
+    
+    #[test]
+    fn test_frequency_source_chain() {
+        let estimate = estimate_tsc_frequency();
+        
+        let clock = crate::clock::Clock::from_estimate(estimate);
+        assert_eq!(clock.frequency_khz(), estimate.khz);
+        assert_eq!(clock.source(), Some(estimate.source));
+        if estimate.source == FrequencySource::SleepCalibration {
+            assert!(estimate.error_khz < estimate.khz / 10, "noisy calibration: {:?}", estimate);
+        }
+        
+        let sleep = calibrate_by_sleep(3, 10);
+        assert_eq!(sleep.source, FrequencySource::SleepCalibration);
+        assert!(sleep.khz.abs_diff(estimate.khz) < estimate.khz / 10);
+    }
+    
+    #[test]
+    fn test_frequency_decoding() {
+        assert_eq!(khz_from_tsc_leaf(2, 250, 24_000_000), Some(3_000_000));
+        assert_eq!(estimate_from_base_mhz(3000).map(|e| e.error_khz), Some(60_000));
+        assert!(estimate_from_base_mhz(0).is_none());
+        assert_eq!(khz_from_tsc_leaf(2, 250, 0), None);
+        assert_eq!(khz_from_perf_time(512, 10), Some(2_000_000));
+        assert_eq!(khz_from_perf_time(0, 10), None);
+    }
+    
     #[cfg(target_os = "linux")]
     #[test]
     fn test_proc_cpuinfo_parsing() {
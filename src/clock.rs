@@ -0,0 +1,256 @@
+//! Explicit calibration handle for timers and benchmarks
+
+use std::io;
+
+use crate::calibration::{self, FrequencyEstimate, FrequencySource, TimerCalibration};
+use crate::timing::{read_timestamp_and_cpu, TimestampSerialization};
+
+/// Counter frequency, timer cost and per-core TSC offsets in one value
+///
+/// Unlike the process-wide calibration in `mock_core`, a `Clock` is owned
+/// by whoever created it, so parallel tests or benchmarks with different
+/// settings don't overwrite each other. `Clock::global()` snapshots the
+/// process-wide values for code that doesn't care.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    frequency_khz: u64,
+    source: Option<FrequencySource>,
+    timer_overhead_ns: u64,
+    resolution_ns: u64,
+    serialization: TimestampSerialization,
+    /// TSC of each CPU minus the TSC of the reference CPU, in cycles
+    core_offsets: Vec<i64>,
+}
+
+impl Clock {
+    /// A clock with a known frequency and no timer calibration
+    pub fn from_frequency_mhz(frequency_mhz: u64) -> Self {
+        Self::from_frequency_khz(frequency_mhz * 1000)
+    }
+
+    pub fn from_frequency_khz(frequency_khz: u64) -> Self {
+        Self {
+            frequency_khz,
+            source: None,
+            timer_overhead_ns: 0,
+            resolution_ns: 0,
+            serialization: TimestampSerialization::default(),
+            core_offsets: Vec::new(),
+        }
+    }
+
+    pub fn from_estimate(estimate: FrequencyEstimate) -> Self {
+        Self {
+            source: Some(estimate.source),
+            ..Self::from_frequency_khz(estimate.khz)
+        }
+    }
+
+    /// Snapshot of the process-wide calibration
+    pub fn global() -> Self {
+        Self {
+            timer_overhead_ns: crate::mock_core::timer_overhead_ns(),
+            resolution_ns: crate::mock_core::timer_resolution_ns(),
+            ..Self::from_frequency_khz(crate::mock_core::cpu_frequency_khz())
+        }
+    }
+
+    /// Detect the frequency and measure the timer, leaving the global alone
+    pub fn detect() -> Self {
+        let clock = Self::from_estimate(calibration::estimate_tsc_frequency());
+        let timer = calibration::measure_timer(&clock, 10_000);
+        clock.with_timer_calibration(timer)
+    }
+
+    pub fn with_timer_calibration(mut self, timer: TimerCalibration) -> Self {
+        self.timer_overhead_ns = timer.overhead_median_ns;
+        self.resolution_ns = timer.resolution_ns;
+        self
+    }
+
+    /// Serialization used by timers started on this clock; remeasures the
+    /// timer overhead if one was measured before
+    pub fn with_serialization(mut self, serialization: TimestampSerialization) -> Self {
+        self.serialization = serialization;
+        if self.timer_overhead_ns > 0 {
+            let timer = calibration::measure_timer(&self, 10_000);
+            self = self.with_timer_calibration(timer);
+        }
+        self
+    }
+
+    /// Measure every allowed CPU's TSC offset from the first allowed CPU
+    ///
+    /// Only needed on machines whose TSCs are not synchronized. Fails if a
+    /// thread cannot be pinned to one of the CPUs.
+    pub fn with_core_offsets(mut self, rounds: usize) -> io::Result<Self> {
+        let cpus = crate::affinity::allowed_cpus()?;
+        let Some(&reference) = cpus.first() else {
+            return Ok(self);
+        };
+
+        let max_cpu = cpus.iter().copied().max().unwrap_or(0);
+        self.core_offsets = vec![0; max_cpu + 1];
+        for &cpu in &cpus[1..] {
+            self.core_offsets[cpu] = measure_core_offset(reference, cpu, rounds)?;
+        }
+        Ok(self)
+    }
+
+    /// Make this the process-wide default used by `PrecisionTimer::start`
+    pub fn install_global(&self) {
+        crate::mock_core::set_cpu_frequency_khz(self.frequency_khz);
+        crate::mock_core::set_timer_overhead_ns(self.timer_overhead_ns);
+        crate::mock_core::set_timer_resolution_ns(self.resolution_ns);
+    }
+
+    pub fn frequency_khz(&self) -> u64 {
+        self.frequency_khz
+    }
+
+    pub fn frequency_mhz(&self) -> u64 {
+        (self.frequency_khz + 500) / 1000
+    }
+
+    /// Where the frequency came from, when detected
+    pub fn source(&self) -> Option<FrequencySource> {
+        self.source
+    }
+
+    pub fn timer_overhead_ns(&self) -> u64 {
+        self.timer_overhead_ns
+    }
+
+    pub fn resolution_ns(&self) -> u64 {
+        self.resolution_ns
+    }
+
+    pub fn serialization(&self) -> TimestampSerialization {
+        self.serialization
+    }
+
+    /// TSC offset of `cpu` from the reference CPU, 0 if not measured
+    pub fn core_offset(&self, cpu: usize) -> i64 {
+        self.core_offsets.get(cpu).copied().unwrap_or(0)
+    }
+
+    pub fn core_offsets(&self) -> &[i64] {
+        &self.core_offsets
+    }
+
+    /// Read the counter, corrected into the reference CPU's time base so
+    /// stamps taken on different cores can be subtracted
+    #[inline(always)]
+    pub fn now(&self) -> u64 {
+        self.now_and_cpu().0
+    }
+
+    /// `now` together with the CPU the counter was read on
+    #[inline(always)]
+    pub fn now_and_cpu(&self) -> (u64, usize) {
+        let (tsc, cpu) = read_timestamp_and_cpu();
+        (tsc.wrapping_sub(self.core_offset(cpu) as u64), cpu)
+    }
+
+    pub fn cycles_to_ns(&self, cycles: u64) -> u64 {
+        (cycles as u128 * 1_000_000 / self.frequency_khz.max(1) as u128) as u64
+    }
+
+    /// Inverse of `cycles_to_ns`
+    pub fn ns_to_cycles(&self, ns: u64) -> u64 {
+        (ns as u128 * self.frequency_khz as u128 / 1_000_000) as u64
+    }
+
+    /// Time `f` with this clock's serialization, in raw ticks
+    pub fn time_cycles<F, R>(&self, f: F) -> (R, u64)
+    where
+        F: FnOnce() -> R,
+    {
+        let timer = crate::timing::PrecisionTimer::start_on(self);
+        let result = f();
+        (result, timer.stop_cycles())
+    }
+
+    /// Time `f` with this clock's frequency and serialization
+    pub fn time<F, R>(&self, f: F) -> (R, u64)
+    where
+        F: FnOnce() -> R,
+    {
+        let timer = crate::timing::PrecisionTimer::start_on(self);
+        let result = f();
+        (result, timer.stop())
+    }
+
+    /// `time` minus this clock's measured timer overhead
+    pub fn time_compensated<F, R>(&self, f: F) -> (R, u64)
+    where
+        F: FnOnce() -> R,
+    {
+        let (result, elapsed) = self.time(f);
+        (result, elapsed.saturating_sub(self.timer_overhead_ns))
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::global()
+    }
+}
+
+/// TSC of `cpu` minus TSC of `reference` at the same instant, in cycles
+///
+/// See `tsc_sync::measure_skew` for how it is measured.
+pub fn measure_core_offset(reference: usize, cpu: usize, rounds: usize) -> io::Result<i64> {
+    if reference == cpu {
+        return Ok(0);
+    }
+    Ok(crate::tsc_sync::measure_skew(reference, cpu, rounds)?.skew_cycles())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_is_independent_of_global() {
+        let clock = Clock::from_frequency_mhz(2500);
+        assert_eq!(clock.frequency_mhz(), 2500);
+        assert_eq!(clock.cycles_to_ns(2500), 1000);
+        assert_eq!(clock.ns_to_cycles(1000), 2500);
+        assert_eq!(clock.core_offset(7), 0);
+
+        let (result, elapsed) = clock.time(|| (0..10).sum::<i32>());
+        assert_eq!(result, 45);
+        assert!(elapsed < 1_000_000);
+
+        // The global calibration is rewritten by other tests, so only check
+        // that a snapshot of it is usable
+        assert!(Clock::global().frequency_khz() > 0);
+    }
+
+    #[test]
+    fn test_detect_and_core_offsets() {
+        let clock = Clock::detect();
+        assert!(clock.source().is_some());
+        assert!(clock.resolution_ns() >= 1);
+
+        #[cfg(target_os = "linux")]
+        {
+            let clock = clock.with_core_offsets(50).unwrap();
+            let cpus = crate::affinity::allowed_cpus().unwrap();
+            assert_eq!(clock.core_offset(cpus[0]), 0);
+
+            let start = clock.now();
+            let end = clock.now();
+            assert!(end >= start);
+        
+            assert!(measure_core_offset(cpus[0], 100_000, 10).is_err());
+            if cpus.len() > 1 {
+                let offset = measure_core_offset(cpus[0], cpus[1], 100).unwrap();
+                assert!(offset.unsigned_abs() < 10_000_000, "implausible offset {offset}");
+            }
+        }
+
+        assert_eq!(measure_core_offset(3, 3, 10).unwrap(), 0);
+    }
+}
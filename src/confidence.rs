@@ -0,0 +1,361 @@
+//! Confidence intervals for the mean and percentiles of a benchmark run
+
+use crate::histogram::Histogram;
+use crate::stats::{percentile_with, PercentileMethod};
+
+/// Percentiles carried by `BenchmarkAnalysis`, in field order
+pub const REPORTED_PERCENTILES: [f64; 4] = [50.0, 95.0, 99.0, 99.9];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntervalMethod {
+    /// Percentile bootstrap over `resamples` resamples drawn with replacement
+    Bootstrap { resamples: usize, seed: u64 },
+    /// Distribution-free interval from binomial order statistics
+    OrderStatistic,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfidenceConfig {
+    /// Coverage probability, e.g. `0.95`
+    pub level: f64,
+    pub method: IntervalMethod,
+}
+
+impl Default for ConfidenceConfig {
+    fn default() -> Self {
+        Self::bootstrap(0.95, 1000)
+    }
+}
+
+impl ConfidenceConfig {
+    pub fn bootstrap(level: f64, resamples: usize) -> Self {
+        Self {
+            level,
+            method: IntervalMethod::Bootstrap { resamples, seed: 0x5eed_cafe },
+        }
+    }
+
+    pub fn order_statistic(level: f64) -> Self {
+        Self {
+            level,
+            method: IntervalMethod::OrderStatistic,
+        }
+    }
+}
+
+/// Closed interval in nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Interval {
+    pub lower: u64,
+    pub upper: u64,
+}
+
+impl Interval {
+    pub fn width(&self) -> u64 {
+        self.upper - self.lower
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        self.lower <= value && value <= self.upper
+    }
+
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.lower <= other.upper && other.lower <= self.upper
+    }
+}
+
+/// Confidence intervals for every statistic reported by `BenchmarkAnalysis`
+#[derive(Debug, Clone)]
+pub struct ConfidenceIntervals {
+    pub level: f64,
+    pub method: IntervalMethod,
+    pub mean: Interval,
+    pub p50: Interval,
+    pub p95: Interval,
+    pub p99: Interval,
+    pub p999: Interval,
+    /// Percentiles whose upper bound is the sample maximum because there are
+    /// too few samples to resolve them
+    pub insufficient_samples: Vec<f64>,
+}
+
+impl ConfidenceIntervals {
+    /// Interval for one of the `REPORTED_PERCENTILES`
+    pub fn for_percentile(&self, p: f64) -> Option<Interval> {
+        REPORTED_PERCENTILES.iter()
+            .zip([self.p50, self.p95, self.p99, self.p999])
+            .find(|(&reported, _)| reported == p)
+            .map(|(_, interval)| interval)
+    }
+
+    pub fn is_sufficient_for(&self, p: f64) -> bool {
+        !self.insufficient_samples.contains(&p)
+    }
+}
+
+/// Smallest sample count whose order-statistic interval for percentile `p`
+/// at `level` does not run past the sample maximum
+pub fn min_samples_for_percentile(p: f64, level: f64) -> usize {
+    let z = normal_quantile(0.5 + level / 2.0);
+    let (mut lo, mut hi) = (1usize, 1usize);
+    while order_statistic_ranks(hi, p, z).2 {
+        lo = hi;
+        hi *= 2;
+        if hi > 1 << 40 {
+            return hi;
+        }
+    }
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if order_statistic_ranks(mid, p, z).2 {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    hi
+}
+
+pub(crate) fn from_sorted(
+    sorted: &[u64],
+    mean: f64,
+    std_dev: f64,
+    config: &ConfidenceConfig,
+    method: PercentileMethod,
+) -> ConfidenceIntervals {
+    let n = sorted.len();
+    let z = normal_quantile(0.5 + config.level / 2.0);
+
+    let mut insufficient_samples = Vec::new();
+    let mut order_intervals = [Interval::default(); 4];
+    for (slot, &p) in order_intervals.iter_mut().zip(REPORTED_PERCENTILES.iter()) {
+        let (lower, upper, insufficient) = order_statistic_ranks(n, p, z);
+        if insufficient {
+            insufficient_samples.push(p);
+        }
+        *slot = Interval { lower: sorted[lower], upper: sorted[upper] };
+    }
+
+    let (mean_interval, percentile_intervals) = match config.method {
+        IntervalMethod::OrderStatistic => (normal_mean_interval(n, mean, std_dev, z), order_intervals),
+        IntervalMethod::Bootstrap { resamples, seed } => bootstrap(sorted, resamples.max(1), seed, config.level, method),
+    };
+
+    ConfidenceIntervals {
+        level: config.level,
+        method: config.method,
+        mean: mean_interval,
+        p50: percentile_intervals[0],
+        p95: percentile_intervals[1],
+        p99: percentile_intervals[2],
+        p999: percentile_intervals[3],
+        insufficient_samples,
+    }
+}
+
+/// Order-statistic interval for percentile `p` of sorted data at `level`,
+/// or `None` when there are too few samples to bound it
+pub(crate) fn percentile_interval_sorted(sorted: &[u64], p: f64, level: f64) -> Option<Interval> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let z = normal_quantile(0.5 + level / 2.0);
+    let (lower, upper, insufficient) = order_statistic_ranks(sorted.len(), p, z);
+    (!insufficient).then(|| Interval { lower: sorted[lower], upper: sorted[upper] })
+}
+
+/// `percentile_interval_sorted` over a histogram's buckets
+pub(crate) fn percentile_interval_histogram(histogram: &Histogram, p: f64, level: f64) -> Option<Interval> {
+    let n = histogram.len() as usize;
+    if n == 0 {
+        return None;
+    }
+    let z = normal_quantile(0.5 + level / 2.0);
+    let (lower, upper, insufficient) = order_statistic_ranks(n, p, z);
+    let value_at_rank = |rank: usize| histogram.value_at_percentile((rank + 1) as f64 / n as f64 * 100.0);
+    (!insufficient).then(|| Interval { lower: value_at_rank(lower), upper: value_at_rank(upper) })
+}
+
+/// Histogram storage keeps no samples to resample, so intervals always use
+/// order statistics over the bucketed distribution
+pub(crate) fn from_histogram(histogram: &Histogram, config: &ConfidenceConfig) -> ConfidenceIntervals {
+    let n = histogram.len() as usize;
+    let z = normal_quantile(0.5 + config.level / 2.0);
+    let value_at_rank = |rank: usize| histogram.value_at_percentile((rank + 1) as f64 / n as f64 * 100.0);
+
+    let mut insufficient_samples = Vec::new();
+    let mut intervals = [Interval::default(); 4];
+    for (slot, &p) in intervals.iter_mut().zip(REPORTED_PERCENTILES.iter()) {
+        let (lower, upper, insufficient) = order_statistic_ranks(n, p, z);
+        if insufficient {
+            insufficient_samples.push(p);
+        }
+        *slot = Interval { lower: value_at_rank(lower), upper: value_at_rank(upper) };
+    }
+
+    ConfidenceIntervals {
+        level: config.level,
+        method: IntervalMethod::OrderStatistic,
+        mean: normal_mean_interval(n, histogram.mean(), histogram.std_dev(), z),
+        p50: intervals[0],
+        p95: intervals[1],
+        p99: intervals[2],
+        p999: intervals[3],
+        insufficient_samples,
+    }
+}
+
+/// Zero-based ranks bounding percentile `p`, and whether the upper rank
+/// had to be clamped to the sample maximum
+fn order_statistic_ranks(n: usize, p: f64, z: f64) -> (usize, usize, bool) {
+    let q = p / 100.0;
+    let np = n as f64 * q;
+    let spread = z * (np * (1.0 - q)).sqrt();
+
+    let lower = (np - spread).floor().max(1.0) as usize;
+    let upper = (np + spread).ceil() as usize + 1;
+    let insufficient = upper > n;
+
+    (lower.min(n) - 1, upper.min(n) - 1, insufficient)
+}
+
+fn normal_mean_interval(n: usize, mean: f64, std_dev: f64, z: f64) -> Interval {
+    let half_width = if n > 1 { z * std_dev / ((n - 1) as f64).sqrt() } else { 0.0 };
+    Interval {
+        lower: (mean - half_width).max(0.0).floor() as u64,
+        upper: (mean + half_width).ceil() as u64,
+    }
+}
+
+fn bootstrap(sorted: &[u64], resamples: usize, seed: u64, level: f64, method: PercentileMethod) -> (Interval, [Interval; 4]) {
+    let n = sorted.len();
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let mut resample = vec![0u64; n];
+
+    let mut means = Vec::with_capacity(resamples);
+    let mut estimates: [Vec<u64>; 4] = Default::default();
+
+    for _ in 0..resamples {
+        for slot in resample.iter_mut() {
+            *slot = sorted[rng.usize(..n)];
+        }
+        resample.sort_unstable();
+
+        means.push(resample.iter().map(|&x| x as u128).sum::<u128>() as f64 / n as f64);
+        for (estimate, &p) in estimates.iter_mut().zip(REPORTED_PERCENTILES.iter()) {
+            estimate.push(percentile_with(&resample, p, method).round() as u64);
+        }
+    }
+
+    let alpha = (1.0 - level) / 2.0;
+    means.sort_unstable_by(f64::total_cmp);
+    let mean_lower = means[((resamples - 1) as f64 * alpha).floor() as usize];
+    let mean_upper = means[((resamples - 1) as f64 * (1.0 - alpha)).ceil() as usize];
+
+    let mut intervals = [Interval::default(); 4];
+    for (slot, estimate) in intervals.iter_mut().zip(estimates.iter_mut()) {
+        estimate.sort_unstable();
+        *slot = Interval {
+            lower: estimate[((resamples - 1) as f64 * alpha).floor() as usize],
+            upper: estimate[((resamples - 1) as f64 * (1.0 - alpha)).ceil() as usize],
+        };
+    }
+
+    (Interval { lower: mean_lower.floor() as u64, upper: mean_upper.ceil() as u64 }, intervals)
+}
+
+/// Inverse of the standard normal CDF (Acklam's rational approximation)
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969_683_028_665_376e1, 2.209_460_984_245_205e2, -2.759_285_104_469_687e2,
+                         1.383_577_518_672_69e2, -3.066_479_806_614_716e1, 2.506_628_277_459_239];
+    const B: [f64; 5] = [-5.447_609_879_822_406e1, 1.615_858_368_580_409e2, -1.556_989_798_598_866e2,
+                         6.680_131_188_771_972e1, -1.328_068_155_288_572e1];
+    const C: [f64; 6] = [-7.784_894_002_430_293e-3, -3.223_964_580_411_365e-1, -2.400_758_277_161_838,
+                         -2.549_732_539_343_734, 4.374_664_141_464_968, 2.938_163_982_698_783];
+    const D: [f64; 4] = [7.784_695_709_041_462e-3, 3.224_671_290_700_398e-1, 2.445_134_137_142_996,
+                         3.754_408_661_907_416];
+    const P_LOW: f64 = 0.02425;
+
+    let p = p.clamp(1e-300, 1.0 - 1e-16);
+    if p < P_LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -normal_quantile(1.0 - p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform_sorted(n: u64) -> Vec<u64> {
+        (1..=n).collect()
+    }
+
+    #[test]
+    fn test_normal_quantile() {
+        assert!((normal_quantile(0.975) - 1.959_964).abs() < 1e-5);
+        assert!((normal_quantile(0.5)).abs() < 1e-9);
+        assert!((normal_quantile(0.005) + 2.575_829).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_order_statistic_intervals_bracket_estimate() {
+        let sorted = uniform_sorted(10_000);
+        let ci = from_sorted(&sorted, 5000.5, 2886.9, &ConfidenceConfig::order_statistic(0.95), PercentileMethod::NearestRank);
+
+        assert!(ci.p50.contains(5000));
+        assert!(ci.p99.contains(9900));
+        assert!(ci.p50.width() < 300);
+        assert!(ci.mean.contains(5000));
+        assert!(ci.insufficient_samples.is_empty());
+    }
+
+    #[test]
+    fn test_flags_insufficient_samples() {
+        let sorted = uniform_sorted(100);
+        let ci = from_sorted(&sorted, 50.5, 28.9, &ConfidenceConfig::order_statistic(0.95), PercentileMethod::NearestRank);
+
+        assert!(ci.is_sufficient_for(50.0));
+        assert!(!ci.is_sufficient_for(99.9));
+        assert!(!ci.is_sufficient_for(99.0));
+        assert_eq!(ci.p999.upper, 100);
+
+        let needed = min_samples_for_percentile(99.9, 0.95);
+        assert!(needed > 1000 && needed < 10_000, "needed = {}", needed);
+    }
+
+    #[test]
+    fn test_bootstrap_is_deterministic_and_brackets_estimate() {
+        let sorted = uniform_sorted(2000);
+        let config = ConfidenceConfig::bootstrap(0.95, 200);
+
+        let a = from_sorted(&sorted, 1000.5, 577.3, &config, PercentileMethod::NearestRank);
+        let b = from_sorted(&sorted, 1000.5, 577.3, &config, PercentileMethod::NearestRank);
+
+        assert_eq!(a.p99, b.p99);
+        assert!(a.p50.contains(1001));
+        assert!(a.mean.contains(1000));
+        assert!(a.mean.lower > 900 && a.mean.upper < 1100);
+    }
+
+    #[test]
+    fn test_histogram_intervals() {
+        let mut histogram = Histogram::new(3, 1_000_000);
+        for v in 1..=10_000 {
+            histogram.record(v);
+        }
+
+        let ci = from_histogram(&histogram, &ConfidenceConfig::default());
+        assert_eq!(ci.method, IntervalMethod::OrderStatistic);
+        assert!(ci.p99.lower <= 9900 && ci.p99.upper >= 9900);
+        assert!(ci.insufficient_samples.is_empty());
+    }
+}
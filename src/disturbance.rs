@@ -0,0 +1,132 @@
+//! Per-sample context switch and page fault accounting
+//!
+//! A preemption or page fault inside the timed region shows up as a tail
+//! sample with nothing to explain it. Reading `getrusage(RUSAGE_THREAD)`
+//! around a sample tells whether the thread was disturbed while it ran.
+
+use crate::clock::Clock;
+
+/// Cumulative scheduler and memory events of the calling thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThreadUsage {
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+}
+
+impl ThreadUsage {
+    /// Current counts for the calling thread; `None` where unsupported
+    #[cfg(target_os = "linux")]
+    pub fn now() -> Option<Self> {
+        unsafe {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            if libc::getrusage(libc::RUSAGE_THREAD, &mut usage) != 0 {
+                return None;
+            }
+            Some(Self {
+                voluntary_switches: usage.ru_nvcsw as u64,
+                involuntary_switches: usage.ru_nivcsw as u64,
+                minor_faults: usage.ru_minflt as u64,
+                major_faults: usage.ru_majflt as u64,
+            })
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn now() -> Option<Self> {
+        None
+    }
+
+    /// Events between `earlier` and this reading
+    pub fn since(&self, earlier: &ThreadUsage) -> Disturbance {
+        Disturbance {
+            voluntary_switches: self.voluntary_switches.saturating_sub(earlier.voluntary_switches),
+            involuntary_switches: self.involuntary_switches.saturating_sub(earlier.involuntary_switches),
+            minor_faults: self.minor_faults.saturating_sub(earlier.minor_faults),
+            major_faults: self.major_faults.saturating_sub(earlier.major_faults),
+        }
+    }
+}
+
+/// Context switches and page faults during one sample (or summed over many)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Disturbance {
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+}
+
+impl Disturbance {
+    pub fn context_switches(&self) -> u64 {
+        self.voluntary_switches + self.involuntary_switches
+    }
+
+    pub fn page_faults(&self) -> u64 {
+        self.minor_faults + self.major_faults
+    }
+
+    /// No switch or fault happened
+    pub fn is_clean(&self) -> bool {
+        self.context_switches() == 0 && self.page_faults() == 0
+    }
+
+    pub fn add(&mut self, other: &Disturbance) {
+        self.voluntary_switches += other.voluntary_switches;
+        self.involuntary_switches += other.involuntary_switches;
+        self.minor_faults += other.minor_faults;
+        self.major_faults += other.major_faults;
+    }
+}
+
+/// Time `f` with `clock`, also returning what disturbed the thread meanwhile
+///
+/// `getrusage` is called outside the timed region. Without `RUSAGE_THREAD`
+/// support the disturbance is reported as clean.
+pub fn time_with_disturbance<F, R>(clock: &Clock, f: F) -> (R, u64, Disturbance)
+where
+    F: FnOnce() -> R,
+{
+    let before = ThreadUsage::now();
+    let (result, elapsed) = clock.time(f);
+    let disturbance = before.zip(ThreadUsage::now())
+        .map(|(before, after)| after.since(&before))
+        .unwrap_or_default();
+    (result, elapsed, disturbance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_delta() {
+        let before = ThreadUsage { voluntary_switches: 3, involuntary_switches: 1, minor_faults: 10, major_faults: 0 };
+        let after = ThreadUsage { voluntary_switches: 3, involuntary_switches: 2, minor_faults: 14, major_faults: 0 };
+        let delta = after.since(&before);
+        assert_eq!(delta.context_switches(), 1);
+        assert_eq!(delta.page_faults(), 4);
+        assert!(!delta.is_clean());
+        assert!(before.since(&before).is_clean());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sleep_and_fault_are_seen() {
+        let clock = Clock::global();
+
+        let (_, _, slept) = time_with_disturbance(&clock, || std::thread::sleep(std::time::Duration::from_millis(1)));
+        assert!(slept.voluntary_switches >= 1, "{slept:?}");
+
+        // Touching freshly mapped pages faults them in
+        let (_, _, faulted) = time_with_disturbance(&clock, || {
+            let mut pages = vec![0u8; 256 * 4096];
+            for page in pages.chunks_mut(4096) {
+                page[0] = 1;
+            }
+            std::hint::black_box(pages)
+        });
+        assert!(faulted.minor_faults >= 1, "{faulted:?}");
+    }
+}
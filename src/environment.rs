@@ -2,6 +2,8 @@
 
 use std::fs;
 
+use crate::tsc_sync::{SyncVerdict, TscSyncReport};
+
 /// Environment validation result
 #[derive(Debug, Clone)]
 pub struct EnvironmentReport {
@@ -9,6 +11,9 @@ pub struct EnvironmentReport {
     pub power_state: PowerState, 
     pub memory_pressure: MemoryPressure,
     pub cpu_usage: f64,
+    pub tsc_state: TscState,
+    /// Cross-core TSC check, attached with `with_tsc_sync`
+    pub tsc_sync: Option<TscSyncReport>,
     pub warnings: Vec<String>,
     pub errors: Vec<String>,
 }
@@ -37,6 +42,20 @@ pub enum MemoryPressure {
     Critical,
 }
 
+/// Whether the timestamp counter ticks at a fixed rate in all states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TscState {
+    /// Constant rate, keeps running in deep C-states (or aarch64 generic timer)
+    Invariant,
+    /// Constant rate, but may stop in deep C-states
+    ConstantRate,
+    /// Rate follows the core clock; cycle counts do not map to time
+    Variable,
+    /// No timestamp counter
+    Unavailable,
+    Unknown,
+}
+
 impl EnvironmentReport {
     /// Check if environment is suitable for reliable benchmarking
     pub fn is_suitable_for_benchmarking(&self) -> bool {
@@ -47,6 +66,15 @@ impl EnvironmentReport {
             && self.cpu_usage < 50.0
     }
     
+    /// Attach a cross-core TSC check; unsynchronized TSCs are an error
+    pub fn with_tsc_sync(mut self, report: TscSyncReport) -> Self {
+        if report.verdict == SyncVerdict::Unsynchronized {
+            self.errors.push(report.summary());
+        }
+        self.tsc_sync = Some(report);
+        self
+    }
+    
     /// Get a summary message about the environment
     pub fn summary(&self) -> String {
         let mut parts = vec![
@@ -54,8 +82,13 @@ impl EnvironmentReport {
             format!("Power: {:?}", self.power_state),
             format!("Memory: {:?}", self.memory_pressure),
             format!("CPU: {:.1}%", self.cpu_usage),
+            format!("TSC: {:?}", self.tsc_state),
         ];
         
+        if let Some(sync) = &self.tsc_sync {
+            parts.push(format!("TSC sync: {:?}", sync.verdict));
+        }
+        
         if !self.warnings.is_empty() {
             parts.push(format!("Warnings: {}", self.warnings.len()));
         }
@@ -75,10 +108,15 @@ pub fn validate_benchmark_environment() -> EnvironmentReport {
         power_state: PowerState::Unknown,
         memory_pressure: MemoryPressure::Normal,
         cpu_usage: 0.0,
+        tsc_state: TscState::Unknown,
+        tsc_sync: None,
         warnings: Vec::new(),
         errors: Vec::new(),
     };
     
+    // Check the timestamp counter every measurement depends on
+    report.tsc_state = check_tsc_state(&mut report.warnings, &mut report.errors);
+    
     // Check thermal state
     report.thermal_state = check_thermal_state(&mut report.warnings, &mut report.errors);
     
@@ -100,6 +138,81 @@ pub fn validate_benchmark_environment() -> EnvironmentReport {
     report
 }
 
+/// Check via CPUID (and `/proc/cpuinfo` flags, if CPUID hides it) that the
+/// TSC is invariant
+pub fn check_tsc_invariance() -> TscState {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use core::arch::x86_64::__cpuid;
+        
+        const TSC_PRESENT: u32 = 1 << 4;    // CPUID.01H:EDX[4]
+        const INVARIANT_TSC: u32 = 1 << 8;  // CPUID.80000007H:EDX[8]
+        
+        if __cpuid(1).edx & TSC_PRESENT == 0 {
+            return TscState::Unavailable;
+        }
+        if __cpuid(0x8000_0000).eax >= 0x8000_0007 && __cpuid(0x8000_0007).edx & INVARIANT_TSC != 0 {
+            return TscState::Invariant;
+        }
+        
+        #[cfg(target_os = "linux")]
+        if let Ok(cpuinfo) = fs::read_to_string("/proc/cpuinfo") {
+            return tsc_state_from_cpu_flags(&cpuinfo);
+        }
+        
+        TscState::Unknown
+    }
+    
+    // The generic timer runs at a fixed frequency by architecture
+    #[cfg(target_arch = "aarch64")]
+    {
+        TscState::Invariant
+    }
+    
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        TscState::Unknown
+    }
+}
+
+#[cfg_attr(not(all(target_arch = "x86_64", target_os = "linux")), allow(dead_code))]
+fn tsc_state_from_cpu_flags(cpuinfo: &str) -> TscState {
+    let Some(flags) = cpuinfo.lines().find(|line| line.starts_with("flags")) else {
+        return TscState::Unknown;
+    };
+    let has = |flag: &str| flags.split_whitespace().any(|f| f == flag);
+    
+    if !has("tsc") {
+        TscState::Unavailable
+    } else if has("constant_tsc") && has("nonstop_tsc") {
+        TscState::Invariant
+    } else if has("constant_tsc") {
+        TscState::ConstantRate
+    } else {
+        TscState::Variable
+    }
+}
+
+fn check_tsc_state(warnings: &mut Vec<String>, errors: &mut Vec<String>) -> TscState {
+    let state = check_tsc_invariance();
+    match state {
+        TscState::Invariant => {}
+        TscState::ConstantRate => {
+            warnings.push("TSC is constant-rate but not invariant; it may stop in deep C-states".to_string());
+        }
+        TscState::Variable => {
+            errors.push("TSC rate varies with CPU frequency; timings are meaningless".to_string());
+        }
+        TscState::Unavailable => {
+            errors.push("No timestamp counter available".to_string());
+        }
+        TscState::Unknown => {
+            warnings.push("Could not determine whether the TSC is invariant".to_string());
+        }
+    }
+    state
+}
+
 /// Check thermal state
 fn check_thermal_state(warnings: &mut Vec<String>, errors: &mut Vec<String>) -> ThermalState {
     #[cfg(target_os = "macos")]
@@ -153,16 +266,14 @@ fn check_thermal_state(warnings: &mut Vec<String>, errors: &mut Vec<String>) ->
         for entry in fs::read_dir("/sys/class/thermal").unwrap_or_else(|_| {
             warnings.push("Could not access thermal information".to_string());
             fs::read_dir("/tmp").unwrap()// Empty fallback
-        }) {
-            if let Ok(entry) = entry {
-                let path = entry.path().join("temp");
-                if let Ok(temp_str) = fs::read_to_string(&path) {
-                    if let Ok(temp) = temp_str.trim().parse::<u32>() {
-                        // Temperatures in millidegrees Celsius
-                        let temp_c = temp / 1000;
-                        max_temp = max_temp.max(temp_c);
-                        found_temp = true;
-                    }
+        }).flatten() {
+            let path = entry.path().join("temp");
+            if let Ok(temp_str) = fs::read_to_string(&path) {
+                if let Ok(temp) = temp_str.trim().parse::<u32>() {
+                    // Temperatures in millidegrees Celsius
+                    let temp_c = temp / 1000;
+                    max_temp = max_temp.max(temp_c);
+                    found_temp = true;
                 }
             }
         }
@@ -194,6 +305,7 @@ fn check_thermal_state(warnings: &mut Vec<String>, errors: &mut Vec<String>) ->
 }
 
 /// Check power state
+#[cfg_attr(not(target_os = "macos"), allow(unused_variables, clippy::ptr_arg))]
 fn check_power_state(warnings: &mut Vec<String>, _errors: &mut Vec<String>) -> PowerState {
     #[cfg(target_os = "macos")]
     {
@@ -449,6 +561,13 @@ pub fn print_environment_report(report: &EnvironmentReport) {
     println!("Power State: {:?}", report.power_state);
     println!("Memory Pressure: {:?}", report.memory_pressure);
     println!("CPU Usage: {:.1}%", report.cpu_usage);
+    println!("TSC: {:?}", report.tsc_state);
+    if let Some(sync) = &report.tsc_sync {
+        println!("{}", sync.summary());
+        if sync.cpus.len() > 1 {
+            println!("{}", sync.matrix());
+        }
+    }
     
     if !report.warnings.is_empty() {
         println!("\nWarnings:");
@@ -492,6 +611,8 @@ mod tests {
             power_state: PowerState::AC,
             memory_pressure: MemoryPressure::Normal,
             cpu_usage: 25.5,
+            tsc_state: TscState::Invariant,
+            tsc_sync: None,
             warnings: vec!["Test warning".to_string()],
             errors: vec![],
         };
@@ -501,6 +622,7 @@ mod tests {
         assert!(summary.contains("Power: AC"));
         assert!(summary.contains("Memory: Normal"));
         assert!(summary.contains("CPU: 25.5%"));
+        assert!(summary.contains("TSC: Invariant"));
         assert!(summary.contains("Warnings: 1"));
     }
     
@@ -512,20 +634,53 @@ mod tests {
             power_state: PowerState::AC,
             memory_pressure: MemoryPressure::Normal,
             cpu_usage: 10.0,
+            tsc_state: TscState::Invariant,
+            tsc_sync: None,
             warnings: vec![],
             errors: vec![],
         };
         assert!(good_report.is_suitable_for_benchmarking());
         
+        // Unsynchronized TSCs across cores
+        let sync = crate::tsc_sync::check_tsc_sync_on(&[], &Default::default()).unwrap();
+        assert!(good_report.clone().with_tsc_sync(sync).is_suitable_for_benchmarking());
+        let skewed = TscSyncReport {
+            cpus: vec![0, 1],
+            skew: vec![vec![crate::tsc_sync::SkewEstimate::ZERO; 2]; 2],
+            tolerance_cycles: 0,
+            verdict: SyncVerdict::Unsynchronized,
+        };
+        let skewed_report = good_report.clone().with_tsc_sync(skewed);
+        assert!(!skewed_report.is_suitable_for_benchmarking());
+        assert!(skewed_report.summary().contains("TSC sync: Unsynchronized"));
+        
         // Bad environment
         let bad_report = EnvironmentReport {
             thermal_state: ThermalState::Critical,
             power_state: PowerState::LowBattery,
             memory_pressure: MemoryPressure::Critical,
             cpu_usage: 90.0,
+            tsc_state: TscState::Variable,
+            tsc_sync: None,
             warnings: vec![],
             errors: vec!["Critical error".to_string()],
         };
         assert!(!bad_report.is_suitable_for_benchmarking());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_tsc_state_from_cpu_flags() {
+        let flags = |f: &str| format!("processor\t: 0\nflags\t\t: fpu {f} sse2\n");
+        
+        assert_eq!(tsc_state_from_cpu_flags(&flags("tsc constant_tsc nonstop_tsc")), TscState::Invariant);
+        assert_eq!(tsc_state_from_cpu_flags(&flags("tsc constant_tsc")), TscState::ConstantRate);
+        assert_eq!(tsc_state_from_cpu_flags(&flags("tsc")), TscState::Variable);
+        assert_eq!(tsc_state_from_cpu_flags(&flags("pae")), TscState::Unavailable);
+        assert_eq!(tsc_state_from_cpu_flags("processor\t: 0\n"), TscState::Unknown);
+        
+        let mut warnings = Vec::new();
+        let mut errors = Vec::new();
+        let state = check_tsc_state(&mut warnings, &mut errors);
+        assert_eq!(errors.is_empty(), !matches!(state, TscState::Variable | TscState::Unavailable));
+    }
+}
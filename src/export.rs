@@ -0,0 +1,539 @@
+//! Machine-readable export of benchmark results (JSON, CSV, JUnit XML)
+
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+use crate::environment::EnvironmentReport;
+use crate::stats::{BenchmarkAnalysis, BenchmarkResults};
+
+const ANALYSIS_CSV_HEADER: &str = "name,count,min_ns,max_ns,mean_ns,p50_ns,p95_ns,p99_ns,p999_ns,std_dev_ns";
+
+/// Serialize an analysis as a JSON object
+pub fn analysis_to_json(analysis: &BenchmarkAnalysis) -> String {
+    let mut json = format!(
+        "{{\"name\":{},\"count\":{},\"min_ns\":{},\"max_ns\":{},\"mean_ns\":{},\"p50_ns\":{},\"p95_ns\":{},\"p99_ns\":{},\"p999_ns\":{},\"std_dev_ns\":{}",
+        json_string(&analysis.name), analysis.count, analysis.min, analysis.max, analysis.mean,
+        analysis.p50, analysis.p95, analysis.p99, analysis.p999, json_number(analysis.std_dev)
+    );
+
+    if !analysis.percentiles.is_empty() {
+        let values: Vec<String> = analysis.percentiles.iter()
+            .map(|v| format!("{{\"percentile\":{},\"value_ns\":{}}}", json_number(v.percentile), json_number(v.value)))
+            .collect();
+        let _ = write!(json, ",\"percentiles\":[{}]", values.join(","));
+    }
+
+    if let Some(uncorrected) = &analysis.uncorrected {
+        json.push_str(",\"uncorrected\":");
+        json.push_str(&analysis_to_json(uncorrected));
+    }
+
+    if let Some(ci) = &analysis.confidence {
+        let interval = |i: &crate::confidence::Interval| format!("[{},{}]", i.lower, i.upper);
+        let flagged: Vec<String> = ci.insufficient_samples.iter().map(|p| json_number(*p)).collect();
+        let _ = write!(
+            json,
+            ",\"confidence\":{{\"level\":{},\"mean_ns\":{},\"p50_ns\":{},\"p95_ns\":{},\"p99_ns\":{},\"p999_ns\":{},\"insufficient_samples\":[{}]}}",
+            json_number(ci.level), interval(&ci.mean), interval(&ci.p50), interval(&ci.p95),
+            interval(&ci.p99), interval(&ci.p999), flagged.join(",")
+        );
+    }
+
+    if let Some(cycles) = &analysis.cycles {
+        let _ = write!(
+            json,
+            ",\"cycles\":{{\"frequency_khz\":{},\"min\":{},\"max\":{},\"mean\":{},\"p50\":{},\"p95\":{},\"p99\":{},\"p999\":{},\"std_dev\":{}}}",
+            cycles.frequency_khz, json_number(cycles.min), json_number(cycles.max), json_number(cycles.mean),
+            json_number(cycles.p50), json_number(cycles.p95), json_number(cycles.p99), json_number(cycles.p999),
+            json_number(cycles.std_dev)
+        );
+    }
+
+    if let Some(resolution) = analysis.resolution_ns {
+        let _ = write!(json, ",\"resolution_ns\":{},\"below_resolution\":{}", resolution, analysis.below_resolution());
+    }
+
+    if let Some(batch) = analysis.batch_size {
+        let _ = write!(json, ",\"batch_size\":{batch}");
+    }
+
+    if let Some(per_call) = &analysis.per_call {
+        let _ = write!(
+            json,
+            ",\"per_call_ns\":{{\"min\":{},\"max\":{},\"mean\":{},\"p50\":{},\"p95\":{},\"p99\":{},\"p999\":{},\"std_dev\":{}}}",
+            json_number(per_call.min), json_number(per_call.max), json_number(per_call.mean), json_number(per_call.p50),
+            json_number(per_call.p95), json_number(per_call.p99), json_number(per_call.p999), json_number(per_call.std_dev)
+        );
+    }
+
+    if let Some(migrated) = analysis.migrated_samples {
+        let _ = write!(json, ",\"migrated_samples\":{migrated}");
+    }
+
+    if let Some(synthetic) = analysis.synthetic_samples {
+        let _ = write!(json, ",\"synthetic_samples\":{synthetic}");
+    }
+
+    if let Some(overhead) = analysis.overhead_subtracted_ns {
+        let _ = write!(json, ",\"overhead_subtracted_ns\":{overhead}");
+    }
+
+    if let Some(pinning) = &analysis.pinning {
+        let priority = match pinning.policy {
+            crate::affinity::SchedulingPolicy::Fifo { priority } => priority.to_string(),
+            crate::affinity::SchedulingPolicy::Unchanged => "null".to_string(),
+        };
+        let _ = write!(
+            json,
+            ",\"pinning\":{{\"cpu\":{},\"fifo_priority\":{},\"memory_locked\":{}}}",
+            pinning.cpu, priority, pinning.lock_memory
+        );
+    }
+    if let Some(error) = &analysis.pinning_error {
+        let _ = write!(json, ",\"pinning_error\":{}", json_string(error));
+    }
+
+    if let Some(reason) = analysis.stop_reason {
+        let reason = match reason {
+            crate::StopReason::TimeBudget => "time_budget",
+            crate::StopReason::PrecisionReached => "precision_reached",
+            crate::StopReason::MaxSamples => "max_samples",
+        };
+        let _ = write!(json, ",\"stop_reason\":\"{reason}\"");
+    }
+
+    if let Some(warmup) = &analysis.warmup {
+        let trajectory: Vec<String> = warmup.trajectory.iter().map(|m| m.to_string()).collect();
+        let converged = warmup.converged.map_or("null".to_string(), |c| c.to_string());
+        let _ = write!(
+            json,
+            ",\"warmup\":{{\"discarded\":{},\"converged\":{},\"trajectory_ns\":[{}]}}",
+            warmup.discarded, converged, trajectory.join(",")
+        );
+    }
+
+    if let Some(report) = &analysis.disturbances {
+        let totals = &report.totals;
+        let _ = write!(
+            json,
+            ",\"disturbances\":{{\"clean_samples\":{},\"disturbed_samples\":{},\"voluntary_switches\":{},\"involuntary_switches\":{},\"minor_faults\":{},\"major_faults\":{},\"clean\":{},\"disturbed\":{}}}",
+            report.clean_samples, report.disturbed_samples, totals.voluntary_switches, totals.involuntary_switches,
+            totals.minor_faults, totals.major_faults, analysis_to_json(&report.clean), analysis_to_json(&report.disturbed)
+        );
+    }
+
+    if let Some(report) = &analysis.hardware_counters {
+        let counters: Vec<String> = report.counters.iter()
+            .map(|c| format!(
+                "{{\"name\":{},\"mean\":{},\"p50\":{},\"p99\":{},\"max\":{}}}",
+                json_string(&c.name), json_number(c.mean), json_number(c.p50), json_number(c.p99), json_number(c.max)
+            ))
+            .collect();
+        let unavailable: Vec<String> = report.unavailable.iter().map(|u| json_string(u)).collect();
+        let _ = write!(
+            json,
+            ",\"hardware_counters\":{{\"counters\":[{}],\"unavailable\":[{}]}}",
+            counters.join(","), unavailable.join(",")
+        );
+    }
+
+    if let Some(outliers) = &analysis.outliers {
+        let _ = write!(
+            json,
+            ",\"outliers\":{{\"low_severe\":{},\"low_mild\":{},\"high_mild\":{},\"high_severe\":{}}}",
+            outliers.low_severe, outliers.low_mild, outliers.high_mild, outliers.high_severe
+        );
+    }
+
+    if let Some(trimmed) = &analysis.trimmed {
+        json.push_str(",\"trimmed\":");
+        json.push_str(&analysis_to_json(trimmed));
+    }
+
+    json.push('}');
+    json
+}
+
+/// Serialize several analyses as a JSON array
+pub fn analyses_to_json(analyses: &[BenchmarkAnalysis]) -> String {
+    let items: Vec<String> = analyses.iter().map(analysis_to_json).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Serialize analyses as CSV with a header row, one row per analysis
+pub fn analyses_to_csv(analyses: &[BenchmarkAnalysis]) -> String {
+    let mut csv = String::from(ANALYSIS_CSV_HEADER);
+    csv.push('\n');
+
+    for a in analyses {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&a.name), a.count, a.min, a.max, a.mean, a.p50, a.p95, a.p99, a.p999, a.std_dev
+        );
+    }
+
+    csv
+}
+
+/// Serialize recorded samples as JSON
+///
+/// Raw mode emits `"samples": [...]` in recording order; histogram mode
+/// emits `"histogram": [[value, count], ...]` for non-empty buckets.
+/// `"unit"` is `"ticks"` for results recorded `in_cycles` (with the counter
+/// frequency alongside) and `"ns"` otherwise. `"count"` is the measured
+/// samples; values backfilled by `record_corrected` are counted in
+/// `"synthetic_samples"`, left out of `"samples"` and included in the
+/// histogram buckets.
+pub fn results_to_json(results: &BenchmarkResults) -> String {
+    let synthetic = results.synthetic_samples();
+    let mut json = format!("{{\"name\":{},\"count\":{},", json_string(results.name()), results.len() as u64 - synthetic);
+    if synthetic > 0 {
+        let _ = write!(json, "\"synthetic_samples\":{synthetic},");
+    }
+    match results.tick_frequency_khz() {
+        Some(khz) => { let _ = write!(json, "\"unit\":\"ticks\",\"tick_frequency_khz\":{khz},"); }
+        None => json.push_str("\"unit\":\"ns\","),
+    }
+
+    if let Some(samples) = results.samples() {
+        let values: Vec<String> = samples.iter().map(u64::to_string).collect();
+        let _ = write!(json, "\"samples\":[{}]", values.join(","));
+    } else if let Some(histogram) = results.histogram() {
+        let buckets: Vec<String> = histogram.recorded_buckets()
+            .map(|(value, count)| format!("[{value},{count}]"))
+            .collect();
+        let _ = write!(json, "\"histogram\":[{}]", buckets.join(","));
+    }
+
+    json.push('}');
+    json
+}
+
+/// Serialize recorded samples as CSV
+///
+/// Raw mode emits `index,value_ns` for measured samples only; histogram
+/// mode emits `value_ns,count`, whose counts include backfilled values.
+/// Results recorded `in_cycles` use a `value_ticks` column instead.
+pub fn results_to_csv(results: &BenchmarkResults) -> String {
+    let mut csv = String::new();
+    let column = if results.tick_frequency_khz().is_some() { "value_ticks" } else { "value_ns" };
+
+    if let Some(samples) = results.samples() {
+        let _ = writeln!(csv, "index,{column}");
+        for (i, value) in samples.iter().enumerate() {
+            let _ = writeln!(csv, "{i},{value}");
+        }
+    } else if let Some(histogram) = results.histogram() {
+        let _ = writeln!(csv, "{column},count");
+        for (value, count) in histogram.recorded_buckets() {
+            let _ = writeln!(csv, "{value},{count}");
+        }
+    }
+
+    csv
+}
+
+/// Serialize an environment report as a JSON object
+pub fn environment_to_json(report: &EnvironmentReport) -> String {
+    let list = |items: &[String]| -> String {
+        let items: Vec<String> = items.iter().map(|s| json_string(s)).collect();
+        format!("[{}]", items.join(","))
+    };
+
+    format!(
+        "{{\"thermal_state\":{},\"power_state\":{},\"memory_pressure\":{},\"cpu_usage\":{},\"tsc_state\":{},\"tsc_sync\":{},\"suitable\":{},\"warnings\":{},\"errors\":{}}}",
+        json_string(&format!("{:?}", report.thermal_state)),
+        json_string(&format!("{:?}", report.power_state)),
+        json_string(&format!("{:?}", report.memory_pressure)),
+        json_number(report.cpu_usage),
+        json_string(&format!("{:?}", report.tsc_state)),
+        report.tsc_sync.as_ref().map_or("null".to_string(), tsc_sync_to_json),
+        report.is_suitable_for_benchmarking(),
+        list(&report.warnings),
+        list(&report.errors),
+    )
+}
+
+/// Serialize a TSC sync check, with the skew matrix as `[lower, upper]` pairs
+pub fn tsc_sync_to_json(report: &crate::tsc_sync::TscSyncReport) -> String {
+    let cpus: Vec<String> = report.cpus.iter().map(|cpu| cpu.to_string()).collect();
+    let rows: Vec<String> = report.skew.iter()
+        .map(|row| {
+            let cells: Vec<String> = row.iter().map(|s| format!("[{},{}]", s.lower_cycles, s.upper_cycles)).collect();
+            format!("[{}]", cells.join(","))
+        })
+        .collect();
+    format!(
+        "{{\"verdict\":{},\"tolerance_cycles\":{},\"cpus\":[{}],\"skew_cycles\":[{}]}}",
+        json_string(&format!("{:?}", report.verdict)), report.tolerance_cycles, cpus.join(","), rows.join(",")
+    )
+}
+
+/// Serialize an environment report as `key,value` CSV rows
+pub fn environment_to_csv(report: &EnvironmentReport) -> String {
+    let mut csv = String::from("key,value\n");
+    let _ = writeln!(csv, "thermal_state,{:?}", report.thermal_state);
+    let _ = writeln!(csv, "power_state,{:?}", report.power_state);
+    let _ = writeln!(csv, "memory_pressure,{:?}", report.memory_pressure);
+    let _ = writeln!(csv, "cpu_usage,{}", report.cpu_usage);
+    let _ = writeln!(csv, "tsc_state,{:?}", report.tsc_state);
+    if let Some(sync) = &report.tsc_sync {
+        let _ = writeln!(csv, "tsc_sync,{:?}", sync.verdict);
+    }
+    let _ = writeln!(csv, "suitable,{}", report.is_suitable_for_benchmarking());
+    for warning in &report.warnings {
+        let _ = writeln!(csv, "warning,{}", csv_field(warning));
+    }
+    for error in &report.errors {
+        let _ = writeln!(csv, "error,{}", csv_field(error));
+    }
+    csv
+}
+
+/// JUnit-XML reporter where each latency target check is a test case
+pub struct JUnitReport {
+    suite_name: String,
+    cases: Vec<JUnitCase>,
+}
+
+struct JUnitCase {
+    name: String,
+    summary: String,
+    time_secs: f64,
+    failure: Option<String>,
+}
+
+impl JUnitReport {
+    pub fn new(suite_name: &str) -> Self {
+        Self {
+            suite_name: suite_name.to_string(),
+            cases: Vec::new(),
+        }
+    }
+
+    /// Run `meets_target` on `analysis` and record the outcome as a test case
+    pub fn check_target(&mut self, analysis: &BenchmarkAnalysis, target_p99_ns: u64) -> bool {
+        let passed = analysis.meets_target(target_p99_ns);
+        let failure = (!passed).then(|| {
+            format!("p99 = {}ns exceeds target {}ns", analysis.p99, target_p99_ns)
+        });
+
+        self.cases.push(JUnitCase {
+            name: format!("{} p99 <= {}ns", analysis.name, target_p99_ns),
+            summary: analysis.summary(),
+            time_secs: analysis.mean as f64 * analysis.count as f64 / 1e9,
+            failure,
+        });
+        passed
+    }
+
+    pub fn failures(&self) -> usize {
+        self.cases.iter().filter(|c| c.failure.is_some()).count()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cases.is_empty()
+    }
+
+    pub fn to_xml(&self) -> String {
+        let total_time: f64 = self.cases.iter().map(|c| c.time_secs).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.6}\">",
+            xml_escape(&self.suite_name), self.cases.len(), self.failures(), total_time
+        );
+
+        for case in &self.cases {
+            let _ = writeln!(
+                xml,
+                "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.6}\">",
+                xml_escape(&self.suite_name), xml_escape(&case.name), case.time_secs
+            );
+            if let Some(message) = &case.failure {
+                let _ = writeln!(
+                    xml,
+                    "    <failure message=\"{}\" type=\"LatencyTarget\">{}</failure>",
+                    xml_escape(message), xml_escape(&case.summary)
+                );
+            }
+            let _ = writeln!(xml, "    <system-out>{}</system-out>", xml_escape(&case.summary));
+            xml.push_str("  </testcase>\n");
+        }
+
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.to_xml())
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_number(value: f64) -> String {
+    if value.is_finite() { value.to_string() } else { "null".to_string() }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_analysis(name: &str) -> BenchmarkAnalysis {
+        let mut results = BenchmarkResults::new(name.to_string());
+        for i in 1..=100 {
+            results.record(i * 10);
+        }
+        results.analyze()
+    }
+
+    #[test]
+    fn test_analysis_json() {
+        let json = analysis_to_json(&sample_analysis("quote \"parse\""));
+
+        assert!(json.starts_with("{\"name\":\"quote \\\"parse\\\"\""));
+        assert!(json.contains("\"count\":100"));
+        assert!(json.contains("\"p50_ns\":510"));
+        assert!(!json.contains("uncorrected"));
+        assert!(json.ends_with('}'));
+
+        let mut results = BenchmarkResults::new("ci".to_string());
+        for i in 1..=100 {
+            results.record(i);
+        }
+        let options = crate::stats::AnalysisOptions::default()
+            .with_confidence(crate::confidence::ConfidenceConfig::order_statistic(0.95));
+        let json = analysis_to_json(&results.analyze_with(&options.with_percentiles(&[99.99])));
+        assert!(json.contains("\"percentiles\":[{\"percentile\":99.99,\"value_ns\":100}]"));
+        assert!(json.contains("\"confidence\":{\"level\":0.95,"));
+        assert!(json.contains("\"insufficient_samples\":[95,99,99.9]"));
+    }
+
+    #[test]
+    fn test_analyses_csv() {
+        let csv = analyses_to_csv(&[sample_analysis("a,b"), sample_analysis("c")]);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], ANALYSIS_CSV_HEADER);
+        assert!(lines[1].starts_with("\"a,b\",100,10,1000,505,510,"));
+        assert!(lines[2].starts_with("c,100,"));
+    }
+
+    #[test]
+    fn test_results_export() {
+        let mut raw = BenchmarkResults::new("raw".to_string());
+        raw.record(5);
+        raw.record(7);
+        assert_eq!(results_to_json(&raw), "{\"name\":\"raw\",\"count\":2,\"unit\":\"ns\",\"samples\":[5,7]}");
+        assert_eq!(results_to_csv(&raw), "index,value_ns\n0,5\n1,7\n");
+
+        let mut hist = BenchmarkResults::with_histogram("hist".to_string(), 3, 1_000_000);
+        hist.record(5);
+        hist.record(5);
+        assert_eq!(results_to_json(&hist), "{\"name\":\"hist\",\"count\":2,\"unit\":\"ns\",\"histogram\":[[5,2]]}");
+        assert_eq!(results_to_csv(&hist), "value_ns,count\n5,2\n");
+
+        let mut ticks = BenchmarkResults::new("ticks".to_string()).in_cycles(3_000_000);
+        ticks.record(9);
+        assert_eq!(
+            results_to_json(&ticks),
+            "{\"name\":\"ticks\",\"count\":1,\"unit\":\"ticks\",\"tick_frequency_khz\":3000000,\"samples\":[9]}"
+        );
+        assert_eq!(results_to_csv(&ticks), "index,value_ticks\n0,9\n");
+
+        let mut corrected = BenchmarkResults::new("corrected".to_string());
+        corrected.record_corrected(300, 100);
+        assert_eq!(
+            results_to_json(&corrected),
+            "{\"name\":\"corrected\",\"count\":1,\"synthetic_samples\":2,\"unit\":\"ns\",\"samples\":[300]}"
+        );
+        assert_eq!(results_to_csv(&corrected), "index,value_ns\n0,300\n");
+    }
+
+    #[test]
+    fn test_environment_export() {
+        use crate::environment::{MemoryPressure, PowerState, ThermalState, TscState};
+
+        let report = EnvironmentReport {
+            thermal_state: ThermalState::Normal,
+            power_state: PowerState::AC,
+            memory_pressure: MemoryPressure::Normal,
+            cpu_usage: 12.5,
+            tsc_state: TscState::Invariant,
+            tsc_sync: None,
+            warnings: vec!["load, high".to_string()],
+            errors: vec![],
+        };
+
+        let json = environment_to_json(&report);
+        assert!(json.contains("\"thermal_state\":\"Normal\""));
+        assert!(json.contains("\"cpu_usage\":12.5"));
+        assert!(json.contains("\"tsc_state\":\"Invariant\",\"tsc_sync\":null"));
+        assert!(json.contains("\"warnings\":[\"load, high\"]"));
+        assert!(json.contains("\"suitable\":true"));
+
+        let csv = environment_to_csv(&report);
+        assert!(csv.contains("power_state,AC\n"));
+        assert!(csv.contains("warning,\"load, high\"\n"));
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let analysis = sample_analysis("order<entry>");
+        let mut report = JUnitReport::new("latency");
+
+        assert!(report.check_target(&analysis, 2000));
+        assert!(!report.check_target(&analysis, 100));
+        assert_eq!(report.len(), 2);
+        assert_eq!(report.failures(), 1);
+
+        let xml = report.to_xml();
+        assert!(xml.contains("tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("name=\"order&lt;entry&gt; p99 &lt;= 100ns\""));
+        assert!(xml.contains("<failure message=\"p99 = 990ns exceeds target 100ns\""));
+        assert_eq!(xml.matches("<testcase").count(), 2);
+    }
+}
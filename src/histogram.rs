@@ -0,0 +1,299 @@
+//! Fixed-size high-dynamic-range histogram for bounded-memory recording
+//!
+//! Values are bucketed the same way as HdrHistogram: every power-of-two range
+//! is split into enough linear sub-buckets to keep the configured number of
+//! significant decimal digits. The bucket array is allocated once in `new()`,
+//! so `record()` is O(1) and never allocates.
+
+/// High-dynamic-range histogram of nanosecond latencies
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    significant_digits: u8,
+    highest_trackable: u64,
+    sub_bucket_half_count_magnitude: u32,
+    sub_bucket_half_count: usize,
+    sub_bucket_mask: u64,
+    leading_zero_count_base: u32,
+    total_count: u64,
+    total_sum: u128,
+    min: u64,
+    max: u64,
+    saturated: u64,
+}
+
+impl Histogram {
+    /// Create a histogram tracking values in `[1, highest_trackable]`
+    ///
+    /// `significant_digits` is clamped to `1..=5`; values above
+    /// `highest_trackable` are recorded as `highest_trackable` and counted
+    /// in `saturated_count()`.
+    pub fn new(significant_digits: u8, highest_trackable: u64) -> Self {
+        let significant_digits = significant_digits.clamp(1, 5);
+
+        let largest_single_unit = 2 * 10u64.pow(significant_digits as u32);
+        let sub_bucket_count_magnitude = 64 - (largest_single_unit - 1).leading_zeros();
+        let sub_bucket_half_count_magnitude = sub_bucket_count_magnitude.max(1) - 1;
+        let sub_bucket_count = 1u64 << (sub_bucket_half_count_magnitude + 1);
+        let sub_bucket_half_count = (sub_bucket_count / 2) as usize;
+        let highest_trackable = highest_trackable.max(2 * sub_bucket_count);
+
+        let mut smallest_untrackable = sub_bucket_count;
+        let mut bucket_count = 1usize;
+        while smallest_untrackable <= highest_trackable {
+            if smallest_untrackable > u64::MAX / 2 {
+                bucket_count += 1;
+                break;
+            }
+            smallest_untrackable <<= 1;
+            bucket_count += 1;
+        }
+
+        Self {
+            counts: vec![0; (bucket_count + 1) * sub_bucket_half_count],
+            significant_digits,
+            highest_trackable,
+            sub_bucket_half_count_magnitude,
+            sub_bucket_half_count,
+            sub_bucket_mask: sub_bucket_count - 1,
+            leading_zero_count_base: 64 - sub_bucket_half_count_magnitude - 1,
+            total_count: 0,
+            total_sum: 0,
+            min: u64::MAX,
+            max: 0,
+            saturated: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn record(&mut self, value: u64) {
+        self.record_n(value, 1);
+    }
+
+    /// Record `count` occurrences of `value`
+    #[inline(always)]
+    pub fn record_n(&mut self, value: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+
+        let value = if value > self.highest_trackable {
+            self.saturated += count;
+            self.highest_trackable
+        } else {
+            value
+        };
+
+        let index = self.counts_index(value);
+        self.counts[index] += count;
+        self.total_count += count;
+        self.total_sum += value as u128 * count as u128;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn len(&self) -> u64 {
+        self.total_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_count == 0
+    }
+
+    pub fn min(&self) -> u64 {
+        if self.is_empty() { 0 } else { self.min }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        self.total_sum as f64 / self.total_count as f64
+    }
+
+    /// Standard deviation using the midpoint of each bucket
+    pub fn std_dev(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let mean = self.mean();
+        let sum_sq: f64 = self.recorded_buckets()
+            .map(|(value, count)| {
+                let diff = self.median_equivalent(value) as f64 - mean;
+                diff * diff * count as f64
+            })
+            .sum();
+        (sum_sq / self.total_count as f64).sqrt()
+    }
+
+    /// Value at percentile `p` (0-100), reported as the highest value
+    /// equivalent to the selected bucket and clamped to the recorded range
+    pub fn value_at_percentile(&self, p: f64) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let p = p.clamp(0.0, 100.0);
+        let target = (((p / 100.0) * self.total_count as f64).ceil() as u64).max(1);
+
+        let mut cumulative = 0u64;
+        for (index, &count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= target {
+                let value = self.value_from_index(index);
+                return self.highest_equivalent(value).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Iterate `(lowest_equivalent_value, count)` over non-empty buckets
+    pub fn recorded_buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.counts.iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(index, &count)| (self.value_from_index(index), count))
+    }
+
+    pub fn significant_digits(&self) -> u8 {
+        self.significant_digits
+    }
+
+    pub fn highest_trackable(&self) -> u64 {
+        self.highest_trackable
+    }
+
+    /// Number of recorded values that exceeded `highest_trackable`
+    pub fn saturated_count(&self) -> u64 {
+        self.saturated
+    }
+
+    /// Size of the bucket array, fixed at construction
+    pub fn bucket_len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        self.total_count = 0;
+        self.total_sum = 0;
+        self.min = u64::MAX;
+        self.max = 0;
+        self.saturated = 0;
+    }
+
+    #[inline(always)]
+    fn bucket_index(&self, value: u64) -> u32 {
+        self.leading_zero_count_base - (value | self.sub_bucket_mask).leading_zeros()
+    }
+
+    #[inline(always)]
+    fn counts_index(&self, value: u64) -> usize {
+        let bucket_index = self.bucket_index(value);
+        let sub_bucket_index = (value >> bucket_index) as usize;
+        ((bucket_index as usize + 1) << self.sub_bucket_half_count_magnitude)
+            + sub_bucket_index
+            - self.sub_bucket_half_count
+    }
+
+    fn value_from_index(&self, index: usize) -> u64 {
+        let mut bucket_index = (index >> self.sub_bucket_half_count_magnitude) as i64 - 1;
+        let mut sub_bucket_index = (index & (self.sub_bucket_half_count - 1)) + self.sub_bucket_half_count;
+        if bucket_index < 0 {
+            sub_bucket_index -= self.sub_bucket_half_count;
+            bucket_index = 0;
+        }
+        (sub_bucket_index as u64) << bucket_index
+    }
+
+    fn equivalent_range(&self, value: u64) -> u64 {
+        let bucket_index = self.bucket_index(value);
+        let sub_bucket_index = value >> bucket_index;
+        let adjusted = if sub_bucket_index > self.sub_bucket_mask { bucket_index + 1 } else { bucket_index };
+        1u64 << adjusted
+    }
+
+    fn lowest_equivalent(&self, value: u64) -> u64 {
+        let bucket_index = self.bucket_index(value);
+        (value >> bucket_index) << bucket_index
+    }
+
+    fn highest_equivalent(&self, value: u64) -> u64 {
+        self.lowest_equivalent(value).saturating_add(self.equivalent_range(value) - 1)
+    }
+
+    fn median_equivalent(&self, value: u64) -> u64 {
+        self.lowest_equivalent(value).saturating_add(self.equivalent_range(value) / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_values_are_exact() {
+        let mut hist = Histogram::new(3, 1_000_000);
+        for v in 1..=1000 {
+            hist.record(v);
+        }
+
+        assert_eq!(hist.len(), 1000);
+        assert_eq!(hist.min(), 1);
+        assert_eq!(hist.max(), 1000);
+        assert_eq!(hist.value_at_percentile(50.0), 500);
+        assert_eq!(hist.value_at_percentile(99.0), 990);
+        assert_eq!(hist.value_at_percentile(100.0), 1000);
+    }
+
+    #[test]
+    fn test_relative_error_within_precision() {
+        let mut hist = Histogram::new(3, 3_600_000_000_000);
+        let values = [1_234_567u64, 98_765_432, 5_000_000_000];
+
+        for &v in &values {
+            hist.record(v);
+            let recorded = hist.value_at_percentile(100.0);
+            let error = (recorded as f64 - v as f64).abs() / v as f64;
+            assert!(error < 0.001, "value {} recorded as {}", v, recorded);
+            hist.clear();
+        }
+    }
+
+    #[test]
+    fn test_fixed_footprint() {
+        let mut hist = Histogram::new(2, 10_000_000);
+        let buckets = hist.bucket_len();
+
+        for v in 0..100_000u64 {
+            hist.record(v * 997 % 50_000_000);
+        }
+
+        assert_eq!(hist.bucket_len(), buckets);
+        assert_eq!(hist.len(), 100_000);
+        assert!(hist.saturated_count() > 0);
+        assert_eq!(hist.max(), hist.highest_trackable());
+    }
+
+    #[test]
+    fn test_record_n_and_clear() {
+        let mut hist = Histogram::new(3, 1_000_000);
+        hist.record_n(100, 99);
+        hist.record_n(5000, 1);
+
+        assert_eq!(hist.len(), 100);
+        assert_eq!(hist.mean(), 149.0);
+        assert_eq!(hist.value_at_percentile(99.0), 100);
+        assert_eq!(hist.value_at_percentile(99.9), 5000);
+
+        hist.clear();
+        assert!(hist.is_empty());
+        assert_eq!(hist.min(), 0);
+        assert_eq!(hist.value_at_percentile(50.0), 0);
+    }
+}
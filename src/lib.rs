@@ -1,50 +1,655 @@
 //! High-precision benchmarking tools for HFT systems
 
 pub mod timing;
+pub mod clock;
+pub mod affinity;
+pub mod topology;
+pub mod numa;
+pub mod tsc_sync;
+pub mod one_way;
+pub mod disturbance;
+pub mod warmup;
 pub mod stats;
+pub mod histogram;
+pub mod confidence;
+pub mod outliers;
+#[cfg(feature = "perf")]
+pub mod perf;
+pub mod baseline;
+pub mod export;
 pub mod allocation;
+#[cfg(test)]
+mod test_util;
 pub mod calibration;
 pub mod mock_core;
 pub mod environment;
 pub mod desktop_config;
 pub mod server_config;
 
-pub use timing::{PrecisionTimer, time_function};
-pub use stats::{BenchmarkResults, BenchmarkAnalysis};
-pub use calibration::{calibrate_tsc_frequency, quick_calibrate_tsc_frequency};
-pub use allocation::{benchmark_allocations, benchmark_object_pools, benchmark_aligned_allocations};
-pub use environment::{validate_benchmark_environment, print_environment_report, EnvironmentReport};
+pub use timing::{PrecisionTimer, TimerError, TimestampSerialization, time_function, time_function_compensated};
+pub use stats::{BenchmarkResults, BenchmarkAnalysis, AnalysisOptions, PercentileMethod, CycleAnalysis, PerCallAnalysis, ReportUnit, CounterReport, CounterSummary, DisturbanceReport};
+pub use clock::Clock;
+pub use affinity::{Pinning, SchedulingPolicy, run_pinned};
+pub use topology::{Topology, NumaNode, CpuInfo, CacheInfo, CacheKind};
+pub use numa::{NumaArenaAllocator, ArenaBacking, NodeAccess, NodeAccessFailure, NumaAccessReport, benchmark_numa_access};
+pub use one_way::{LatencySink, LatencyRecorder, Stamp};
+pub use disturbance::{Disturbance, ThreadUsage, time_with_disturbance};
+pub use warmup::{Warmup, WarmupReport, warm_up};
+pub use tsc_sync::{check_tsc_sync, TscSyncConfig, TscSyncReport, SyncVerdict};
+pub use histogram::Histogram;
+pub use confidence::{ConfidenceConfig, ConfidenceIntervals};
+pub use outliers::{OutlierMethod, OutlierReport};
+#[cfg(feature = "perf")]
+pub use perf::{HardwareEvent, PerfCounters, PerfTimer, CounterValues};
+pub use baseline::{BaselineStore, RegressionThresholds, ComparisonReport, Verdict, compare_analyses};
+pub use calibration::{calibrate_tsc_frequency, quick_calibrate_tsc_frequency, detect_tsc_frequency, calibrate_timer_overhead, compare_serialization_overhead, FrequencyEstimate, FrequencySource, TimerCalibration};
+pub use export::JUnitReport;
+pub use allocation::{benchmark_allocations, benchmark_object_pools, benchmark_aligned_allocations, benchmark_page_backings, benchmark_first_touch, benchmark_random_access, PageBacking, Faulting};
+pub use environment::{validate_benchmark_environment, print_environment_report, check_tsc_invariance, EnvironmentReport, TscState};
 pub use desktop_config::{configure_for_desktop_memory_benchmarks, configure_for_desktop_cpu_benchmarks, check_desktop_suitability, DesktopSuitability};
-pub use server_config::{configure_for_server_memory_benchmarks, configure_for_server_cpu_benchmarks, check_server_environment, ServerEnvironment};
+pub use server_config::{configure_for_server_memory_benchmarks, configure_for_server_cpu_benchmarks, check_server_environment, detect_core_isolation, CoreIsolation, ServerEnvironment};
 
 pub struct SimpleBench {
     results: BenchmarkResults,
+    uncorrected: Option<BenchmarkResults>,
+    subtract_overhead: bool,
+    /// Overhead actually subtracted from each sample by the last bench call
+    overhead_subtracted_ns: Option<u64>,
+    batch_size: Option<u64>,
+    batch_target_multiple: u64,
+    clock: Option<Clock>,
+    migration_policy: MigrationPolicy,
+    migrated: u64,
+    cycles: bool,
+    track_disturbances: bool,
+    warmup: Warmup,
+    warmup_report: Option<WarmupReport>,
+    min_samples: usize,
+    max_samples: usize,
+    stop_reason: Option<StopReason>,
+    /// Applied around every bench call; the guard never lives in the
+    /// builder, so `SimpleBench` stays `Send`
+    pinning: Option<Pinning>,
+    /// Why a bench call ran unpinned despite `pinning`
+    pinning_error: Option<String>,
+    #[cfg(feature = "perf")]
+    perf: Option<perf::CounterRecorder>,
+}
+
+/// What `SimpleBench::bench` does with samples whose timer started and
+/// stopped on different CPUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MigrationPolicy {
+    /// Don't track CPUs (cheapest timer)
+    #[default]
+    Ignore,
+    /// Keep migrated samples but count them in the analysis
+    Flag,
+    /// Drop migrated samples and count them in the analysis
+    Discard,
+}
+
+/// When `SimpleBench::bench_until` stops sampling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopCondition {
+    /// After this much wall-clock time spent sampling
+    Time(std::time::Duration),
+    /// Once the 95% confidence interval of `percentile` is narrower than
+    /// `relative_width` times its midpoint (e.g. 0.02 for ±1%)
+    Precision { percentile: f64, relative_width: f64 },
+}
+
+/// Why a `SimpleBench::bench_until` run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    TimeBudget,
+    PrecisionReached,
+    /// Hit `max_samples` before the condition was met
+    MaxSamples,
+}
+
+/// Default sample bounds for `bench_until`
+const DEFAULT_MIN_SAMPLES: usize = 100;
+const DEFAULT_MAX_SAMPLES: usize = 1_000_000;
+
+/// Confidence level for `StopCondition::Precision`
+const PRECISION_LEVEL: f64 = 0.95;
+
+/// Median of 101 empty intervals timed by `time`: the cost of the timer itself
+fn empty_interval_ns(mut time: impl FnMut() -> u64) -> u64 {
+    let mut empty: Vec<u64> = (0..101).map(|_| time()).collect();
+    empty.sort_unstable();
+    empty[50]
+}
+
+/// `clock`'s calibrated timer overhead, measured on the spot if it has none
+fn timer_overhead_ns(clock: &Clock) -> u64 {
+    match clock.timer_overhead_ns() {
+        0 => empty_interval_ns(|| PrecisionTimer::start_on(clock).stop()),
+        calibrated => calibrated,
+    }
 }
 
+/// Default size of a batch relative to the timer's resolution and overhead
+const DEFAULT_BATCH_TARGET_MULTIPLE: u64 = 100;
+
+/// Upper bound on the inner-loop count chosen by `bench_batched`
+const MAX_BATCH_SIZE: u64 = 1 << 24;
+
 impl SimpleBench {
     pub fn new(name: &str) -> Self {
         Self {
             results: BenchmarkResults::new(name.to_string()),
+            uncorrected: None,
+            subtract_overhead: false,
+            overhead_subtracted_ns: None,
+            batch_size: None,
+            batch_target_multiple: DEFAULT_BATCH_TARGET_MULTIPLE,
+            clock: None,
+            migration_policy: MigrationPolicy::Ignore,
+            migrated: 0,
+            cycles: false,
+            track_disturbances: false,
+            warmup: Warmup::None,
+            warmup_report: None,
+            min_samples: DEFAULT_MIN_SAMPLES,
+            max_samples: DEFAULT_MAX_SAMPLES,
+            stop_reason: None,
+            pinning: None,
+            pinning_error: None,
+            #[cfg(feature = "perf")]
+            perf: None,
+        }
+    }
+    
+    /// Run `f` without recording before `bench` and `bench_batched` start
+    /// sampling; the analysis reports what was discarded
+    pub fn warmup(mut self, warmup: Warmup) -> Self {
+        self.warmup = warmup;
+        self
+    }
+    
+    /// Pin the calling thread as `pinning` describes during every following
+    /// bench call (warmup included), restoring its affinity, policy and
+    /// memory locking when each call returns
+    ///
+    /// The core and policy are recorded in `BenchmarkAnalysis::pinning`.
+    /// Fails without changing anything if the thread can't be pinned or the
+    /// policy can't be set (`SCHED_FIFO` and `mlockall` need privileges).
+    pub fn pinned(mut self, pinning: Pinning) -> std::io::Result<Self> {
+        // Check that it can be applied now rather than at the first bench call
+        drop(pinning.apply()?);
+        self.pinning = Some(pinning);
+        Ok(self)
+    }
+    
+    /// Apply the configured pinning for the duration of one bench call
+    ///
+    /// A failure is kept for the analysis, which then doesn't claim the run
+    /// was pinned; later calls still try to pin.
+    fn pin(&mut self) -> Option<affinity::PinGuard> {
+        let pinning = self.pinning.as_ref()?;
+        match pinning.apply() {
+            Ok(guard) => Some(guard),
+            Err(e) => {
+                self.pinning_error = Some(format!("could not pin to cpu{}: {}", pinning.cpu, e));
+                None
+            }
+        }
+    }
+    
+    /// Samples `bench_until` takes at least and at most, whatever the
+    /// stop condition says
+    pub fn sample_bounds(mut self, min_samples: usize, max_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self.max_samples = max_samples.max(min_samples).max(1);
+        self
+    }
+    
+    /// Sample until `stop` is met, within `sample_bounds` (100 to 1M by
+    /// default), instead of for a fixed iteration count
+    ///
+    /// Samples are taken like `bench`, in chunks that grow with the run so
+    /// checking the condition stays cheap for nanosecond functions without
+    /// overshooting much for millisecond ones.
+    pub fn bench_until<F, R>(mut self, stop: StopCondition, mut f: F) -> Self
+    where
+        F: FnMut() -> R,
+    {
+        self.assert_closed_loop();
+        self.assert_unbatched();
+        let _pin = self.pin();
+        let clock = self.clock();
+        self.run_warmup(&clock, &mut f);
+        let overhead_ns = self.sample_overhead_ns(&clock);
+        
+        let recorded_before = self.results.len();
+        let started = std::time::Instant::now();
+        let mut attempted = 0;
+        let mut chunk = 1;
+        let reason = loop {
+            let n = chunk.min(self.max_samples - attempted);
+            self = self.bench_samples(&clock, n, overhead_ns, &mut f);
+            attempted += n;
+            
+            let recorded = self.results.len() - recorded_before;
+            if recorded >= self.min_samples {
+                let met = match stop {
+                    StopCondition::Time(budget) => started.elapsed() >= budget,
+                    StopCondition::Precision { percentile, relative_width } => self.results
+                        .percentile_interval(percentile, PRECISION_LEVEL)
+                        .is_some_and(|ci| ci.width() as f64 <= relative_width * (ci.lower + ci.upper) as f64 / 2.0),
+                };
+                if met {
+                    break match stop {
+                        StopCondition::Time(_) => StopReason::TimeBudget,
+                        StopCondition::Precision { .. } => StopReason::PrecisionReached,
+                    };
+                }
+            }
+            if attempted >= self.max_samples {
+                break StopReason::MaxSamples;
+            }
+            
+            chunk = match stop {
+                StopCondition::Time(budget) => {
+                    // Aim for half the estimated remaining samples, at most doubling
+                    let per_sample = started.elapsed().as_secs_f64() / attempted as f64;
+                    let remaining = budget.saturating_sub(started.elapsed()).as_secs_f64();
+                    ((remaining / per_sample.max(1e-9) / 2.0) as usize).clamp(1, attempted)
+                }
+                // Re-check after every 10% of growth
+                StopCondition::Precision { .. } => (attempted / 10).max(1),
+            };
+            chunk = chunk.max(self.min_samples.saturating_sub(recorded));
+        };
+        
+        self.stop_reason = Some(reason);
+        self
+    }
+    
+    fn run_warmup<F, R>(&mut self, clock: &Clock, f: &mut F)
+    where
+        F: FnMut() -> R,
+    {
+        if self.warmup != Warmup::None {
+            self.warmup_report = Some(warm_up(&self.warmup, clock, f));
+        }
+    }
+    
+    /// Read the thread's context switch and page fault counts around every
+    /// sample, so the analysis can separate clean from disturbed samples
+    pub fn track_disturbances(mut self) -> Self {
+        self.track_disturbances = true;
+        self
+    }
+    
+    fn usage_before(&self) -> Option<ThreadUsage> {
+        if self.track_disturbances { ThreadUsage::now() } else { None }
+    }
+    
+    /// Record a sample, with its disturbance if `before` was read
+    fn record(&mut self, value: u64, before: Option<ThreadUsage>) {
+        match before.and_then(|before| Some(ThreadUsage::now()?.since(&before))) {
+            Some(disturbance) => self.results.record_with_disturbance(value, &disturbance),
+            None => self.results.record(value),
+        }
+    }
+    
+    /// Read `counters` around every sample and report their per-sample
+    /// (per-call for `bench_batched`) distribution next to the latencies
+    ///
+    /// Counters that could not be opened are listed in the report rather
+    /// than failing the run.
+    #[cfg(feature = "perf")]
+    pub fn with_perf_counters(mut self, counters: PerfCounters) -> Self {
+        self.perf = Some(perf::CounterRecorder::new(counters));
+        self
+    }
+    
+    #[cfg(feature = "perf")]
+    fn counters_before(&self) -> Option<perf::CounterValues> {
+        self.perf.as_ref().and_then(|perf| perf.read())
+    }
+    
+    #[cfg(not(feature = "perf"))]
+    fn counters_before(&self) -> Option<()> {
+        None
+    }
+    
+    #[cfg(feature = "perf")]
+    fn counters_after(&mut self, before: Option<perf::CounterValues>, calls: u64) {
+        if let Some(perf) = &mut self.perf {
+            perf.record_since(before, calls);
         }
     }
     
+    #[cfg(not(feature = "perf"))]
+    fn counters_after(&mut self, _before: Option<()>, _calls: u64) {}
+    
+    /// Record raw counter ticks and convert them to nanoseconds only at
+    /// analysis time, keeping sub-nanosecond precision; the analysis also
+    /// reports the statistics in cycles
+    pub fn in_cycles(mut self) -> Self {
+        self.cycles = true;
+        self
+    }
+    
+    /// `ns` in the unit samples are recorded in
+    fn to_sample_unit(&self, clock: &Clock, ns: u64) -> u64 {
+        if self.cycles { clock.ns_to_cycles(ns) } else { ns }
+    }
+    
+    /// Track the CPU each `bench` sample starts and stops on
+    ///
+    /// Samples where the counter went backwards are always dropped once
+    /// tracking is on, since their value is meaningless.
+    pub fn on_migration(mut self, policy: MigrationPolicy) -> Self {
+        self.migration_policy = policy;
+        self
+    }
+    
+    /// Time with `clock` instead of the process-wide calibration
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+    
+    fn clock(&self) -> Clock {
+        self.clock.clone().unwrap_or_else(Clock::global)
+    }
+    
+    /// How many times the timer's resolution (or overhead, if larger) each
+    /// batch in `bench_batched` must take
+    pub fn batch_target_multiple(mut self, multiple: u64) -> Self {
+        self.batch_target_multiple = multiple.max(1);
+        self
+    }
+    
+    /// Subtract the timer overhead from samples recorded by `bench`,
+    /// `bench_until` and `bench_batched`
+    ///
+    /// Uses the overhead from `calibrate_timer_overhead` (or the `Clock`'s
+    /// timer calibration), measuring it on the spot when there is none.
+    /// Migration-tracked benches use a different (`rdtscp`) timer and always
+    /// measure its overhead. The value used is reported in
+    /// `BenchmarkAnalysis::overhead_subtracted_ns`.
+    pub fn subtract_timer_overhead(mut self) -> Self {
+        self.subtract_overhead = true;
+        self
+    }
+    
     pub fn bench<F, R>(mut self, iterations: usize, mut f: F) -> Self
     where
         F: FnMut() -> R,
     {
+        self.assert_closed_loop();
+        self.assert_unbatched();
+        let _pin = self.pin();
+        let clock = self.clock();
+        self.run_warmup(&clock, &mut f);
+        let overhead_ns = self.sample_overhead_ns(&clock);
+        self.bench_samples(&clock, iterations, overhead_ns, f)
+    }
+    
+    /// Closed-loop samples can't join open-loop ones, whose `uncorrected`
+    /// distribution would then describe a different sample set
+    fn assert_closed_loop(&self) {
+        assert!(self.uncorrected.is_none(), "closed-loop benches can't add to open-loop samples");
+    }
+    
+    /// The analysis divides every sample by the bench-wide batch size, so
+    /// single-call samples can't join batched ones
+    fn assert_unbatched(&self) {
+        assert!(self.batch_size.is_none(), "unbatched benches can't add to batched samples");
+    }
+    
+    /// Overhead of the timer `bench_samples` will use, measured once per
+    /// bench call and recorded for the analysis; 0 unless subtracting
+    fn sample_overhead_ns(&mut self, clock: &Clock) -> u64 {
+        if !self.subtract_overhead {
+            return 0;
+        }
+        let overhead_ns = if self.migration_policy == MigrationPolicy::Ignore {
+            timer_overhead_ns(clock)
+        } else {
+            // The calibrated overhead is for the fenced timer, not the tracked one
+            empty_interval_ns(|| PrecisionTimer::start_tracked_on(clock).stop_checked().unwrap_or(0))
+        };
+        self.overhead_subtracted_ns = Some(overhead_ns);
+        overhead_ns
+    }
+    
+    /// `bench` without the pinning and warmup around it, subtracting
+    /// `overhead_ns` from every sample
+    fn bench_samples<F, R>(mut self, clock: &Clock, iterations: usize, overhead_ns: u64, mut f: F) -> Self
+    where
+        F: FnMut() -> R,
+    {
+        if self.migration_policy != MigrationPolicy::Ignore {
+            return self.bench_tracked(clock, iterations, overhead_ns, f);
+        }
+        
+        let overhead = self.to_sample_unit(clock, overhead_ns);
         for _ in 0..iterations {
-            let (_, elapsed) = time_function(&mut f);
-            self.results.record(elapsed);
+            let usage = self.usage_before();
+            let before = self.counters_before();
+            let (_, elapsed) = if self.cycles {
+                clock.time_cycles(&mut f)
+            } else {
+                clock.time(&mut f)
+            };
+            self.counters_after(before, 1);
+            self.record(elapsed.saturating_sub(overhead), usage);
         }
         self
     }
     
+    fn bench_tracked<F, R>(mut self, clock: &Clock, iterations: usize, overhead_ns: u64, mut f: F) -> Self
+    where
+        F: FnMut() -> R,
+    {
+        let overhead = self.to_sample_unit(clock, overhead_ns);
+        for _ in 0..iterations {
+            let usage = self.usage_before();
+            let before = self.counters_before();
+            let timer = PrecisionTimer::start_tracked_on(clock);
+            std::hint::black_box(f());
+            let stopped = if self.cycles { timer.stop_checked_cycles() } else { timer.stop_checked() };
+            self.counters_after(before, 1);
+            match stopped {
+                Ok(elapsed) => self.record(elapsed.saturating_sub(overhead), usage),
+                Err(TimerError::Migrated { elapsed_ns, cycles, .. }) => {
+                    self.migrated += 1;
+                    if self.migration_policy == MigrationPolicy::Flag {
+                        let elapsed = if self.cycles { cycles } else { elapsed_ns };
+                        self.record(elapsed.saturating_sub(overhead), usage);
+                    }
+                }
+                Err(TimerError::Backwards { .. }) => self.migrated += 1,
+            }
+        }
+        self
+    }
+    
+    /// Time `f` in batches for operations too fast to time individually
+    ///
+    /// The inner-loop count is doubled until one batch takes at least
+    /// `batch_target_multiple` times the timer resolution or overhead
+    /// (measured on the spot if not calibrated), then each of `samples` batches records its total.
+    /// The analysis divides by the batch size, keeping sub-nanosecond
+    /// per-call values in `per_call`. The result is a distribution of batch
+    /// means, which hides per-call tails; the analysis records this in
+    /// `batch_size`.
+    ///
+    /// Later `bench_batched` calls on the same bench reuse the first call's
+    /// batch size. Panics if unbatched samples were already recorded.
+    pub fn bench_batched<F, R>(mut self, samples: usize, mut f: F) -> Self
+    where
+        F: FnMut() -> R,
+    {
+        self.assert_closed_loop();
+        assert!(
+            self.batch_size.is_some() || self.results.is_empty(),
+            "bench_batched can't add to unbatched samples"
+        );
+        let _pin = self.pin();
+        let clock = self.clock();
+        let overhead = timer_overhead_ns(&clock);
+        self.run_warmup(&clock, &mut f);
+        let floor = clock.resolution_ns().max(overhead).max(1);
+        let target_ns = floor.saturating_mul(self.batch_target_multiple);
+        
+        // Compensate with the same overhead the batch was sized against;
+        // batches are timed in the unit samples are recorded in
+        let compensation = if self.subtract_overhead { overhead } else { 0 };
+        let cycles = self.cycles;
+        let compensation_in_unit = self.to_sample_unit(&clock, compensation);
+        let target = self.to_sample_unit(&clock, target_ns);
+        let run_batch = |f: &mut F, batch: u64| {
+            let timer = PrecisionTimer::start_on(&clock);
+            for _ in 0..batch {
+                std::hint::black_box(f());
+            }
+            let elapsed = if cycles { timer.stop_cycles() } else { timer.stop() };
+            elapsed.saturating_sub(compensation_in_unit)
+        };
+        
+        // Best of a few runs, so one preempted probe doesn't settle the size
+        let batch = self.batch_size.unwrap_or_else(|| {
+            let mut batch = 1u64;
+            while batch < MAX_BATCH_SIZE
+                && (0..5).map(|_| run_batch(&mut f, batch)).min().unwrap_or(0) < target
+            {
+                batch *= 2;
+            }
+            batch
+        });
+        
+        for _ in 0..samples {
+            let usage = self.usage_before();
+            let before = self.counters_before();
+            let elapsed = run_batch(&mut f, batch);
+            self.counters_after(before, batch);
+            self.record(elapsed, usage);
+        }
+        self.batch_size = Some(batch);
+        if self.subtract_overhead {
+            self.overhead_subtracted_ns = Some(compensation);
+        }
+        self
+    }
+    
+    /// Open-loop run: issue `iterations` calls on a fixed schedule of
+    /// `target_rate_hz`
+    ///
+    /// Latency is measured from each call's intended start rather than its
+    /// actual start; calls that fall behind are issued immediately, still
+    /// measured from their slot, so queueing delay shows up in the tail the
+    /// way it would in production (as in wrk2). Every sample is a real call.
+    /// The uncorrected service time is kept alongside and reported in
+    /// `BenchmarkAnalysis::uncorrected`.
+    ///
+    /// Disturbances, perf counters and the migration policy apply as in
+    /// `bench`; a discarded sample is dropped from both distributions.
+    ///
+    /// Slots are computed from the counter frequency without rounding the
+    /// interval, so rates above 1 GHz stay open-loop. Panics if
+    /// `target_rate_hz` is 0 or closed-loop samples were already recorded.
+    pub fn bench_open_loop<F, R>(mut self, target_rate_hz: u64, iterations: usize, mut f: F) -> Self
+    where
+        F: FnMut() -> R,
+    {
+        assert!(target_rate_hz > 0, "bench_open_loop needs a nonzero rate");
+        assert!(
+            self.uncorrected.is_some() || self.results.is_empty(),
+            "bench_open_loop can't add to closed-loop samples"
+        );
+        let _pin = self.pin();
+        let clock = self.clock();
+        // The schedule runs on counter ticks, converted only when not in cycles
+        let cycles = self.cycles;
+        let from_ticks = |ticks: u64| if cycles { ticks } else { clock.cycles_to_ns(ticks) };
+        let ticks_per_second = clock.frequency_khz() as u128 * 1000;
+        let mut uncorrected = self.uncorrected.take()
+            .unwrap_or_else(|| BenchmarkResults::new(self.results.name().to_string()));
+        
+        let schedule_start = clock.now();
+        for slot in 0..iterations as u128 {
+            let intended = schedule_start + (slot * ticks_per_second / target_rate_hz as u128) as u64;
+            while clock.now() < intended {
+                std::hint::spin_loop();
+            }
+            
+            let usage = self.usage_before();
+            let before = self.counters_before();
+            let (actual, start_cpu) = clock.now_and_cpu();
+            std::hint::black_box(f());
+            let (end, stop_cpu) = clock.now_and_cpu();
+            self.counters_after(before, 1);
+            
+            if self.migration_policy != MigrationPolicy::Ignore {
+                if end < actual {
+                    self.migrated += 1;
+                    continue;
+                }
+                if start_cpu != stop_cpu {
+                    self.migrated += 1;
+                    if self.migration_policy == MigrationPolicy::Discard {
+                        continue;
+                    }
+                }
+            }
+            self.record(from_ticks(end.saturating_sub(intended)), usage);
+            uncorrected.record(from_ticks(end.saturating_sub(actual)));
+        }
+        self.uncorrected = Some(uncorrected);
+        self
+    }
+    
     pub fn report(self) {
-        println!("{}", self.results.analyze().summary());
+        println!("{}", self.analyze().summary());
     }
     
     pub fn analyze(self) -> BenchmarkAnalysis {
-        self.results.analyze()
+        self.analyze_with(&AnalysisOptions::default())
+    }
+    
+    /// Analyze the recorded samples, flagging them against the calibrated
+    /// timer resolution unless `options` supplies one
+    pub fn analyze_with(self, options: &AnalysisOptions) -> BenchmarkAnalysis {
+        let clock = self.clock();
+        let mut options = options.clone();
+        let resolution = clock.resolution_ns();
+        if options.resolution_ns.is_none() && resolution > 0 {
+            options.resolution_ns = Some(resolution);
+        }
+        
+        let in_unit = |results: BenchmarkResults| {
+            let results = if self.cycles { results.in_cycles(clock.frequency_khz()) } else { results };
+            match self.batch_size {
+                Some(batch) => results.per_call(batch),
+                None => results,
+            }
+        };
+        let mut analysis = in_unit(self.results).analyze_with(&options);
+        analysis.uncorrected = self.uncorrected.map(|results| Box::new(in_unit(results).analyze_with(&options)));
+        analysis.warmup = self.warmup_report;
+        analysis.stop_reason = self.stop_reason;
+        // A run that was only pinned for some calls isn't reported as pinned
+        if self.pinning_error.is_none() {
+            analysis.pinning = self.pinning;
+        }
+        analysis.pinning_error = self.pinning_error;
+        if self.migration_policy != MigrationPolicy::Ignore {
+            analysis.migrated_samples = Some(self.migrated);
+        }
+        analysis.overhead_subtracted_ns = self.overhead_subtracted_ns;
+        #[cfg(feature = "perf")]
+        {
+            analysis.hardware_counters = self.perf.map(perf::CounterRecorder::report);
+        }
+        analysis
     }
 }
 
@@ -77,6 +682,388 @@ mod tests {
         assert_eq!(analysis.name, "chain_test");
     }
     
+    #[test]
+    fn test_open_loop_bench() {
+        let analysis = SimpleBench::new("open_loop")
+            .bench_open_loop(100_000, 200, || {
+                (0..10).sum::<i32>()
+            })
+            .analyze();
+        
+        assert_eq!(analysis.count, 200);
+        assert_eq!(analysis.synthetic_samples, None);
+        let uncorrected = analysis.uncorrected.as_ref().expect("uncorrected analysis");
+        assert_eq!(uncorrected.count, analysis.count);
+        assert!(analysis.p50 >= uncorrected.p50);
+        assert!(analysis.summary().contains("uncorrected"));
+    }
+    
+    #[test]
+    fn test_open_loop_applies_tracking() {
+        let analysis = SimpleBench::new("open_loop_tracked")
+            .track_disturbances()
+            .on_migration(MigrationPolicy::Discard)
+            .bench_open_loop(100_000, 100, || std::hint::black_box(1))
+            .bench_open_loop(100_000, 100, || std::hint::black_box(1))
+            .analyze();
+        
+        let migrated = analysis.migrated_samples.expect("tracking enabled");
+        assert_eq!(analysis.count as u64 + migrated, 200);
+        assert_eq!(analysis.uncorrected.as_ref().unwrap().count, analysis.count);
+        #[cfg(target_os = "linux")]
+        {
+            let report = analysis.disturbances.as_ref().expect("disturbance report");
+            assert_eq!(report.clean_samples + report.disturbed_samples, analysis.count);
+        }
+    }
+    
+    #[test]
+    #[should_panic(expected = "closed-loop")]
+    fn test_open_loop_refuses_closed_loop_samples() {
+        SimpleBench::new("mixed").bench(10, || 1).bench_open_loop(100_000, 10, || 1);
+    }
+    
+    #[test]
+    #[should_panic(expected = "open-loop")]
+    fn test_closed_loop_refuses_open_loop_samples() {
+        SimpleBench::new("mixed").bench_open_loop(100_000, 10, || 1).bench(10, || 1);
+    }
+    
+    #[test]
+    fn test_open_loop_measures_stalls_from_schedule() {
+        let mut calls = 0;
+        let analysis = SimpleBench::new("open_loop_stall")
+            .bench_open_loop(10_000, 50, || {
+                calls += 1;
+                if calls == 10 {
+                    std::thread::sleep(std::time::Duration::from_millis(2));
+                }
+            })
+            .analyze();
+        
+        // A 2ms stall at 100us intervals makes the next ~20 calls late; they
+        // are still issued and measured from their slots
+        assert_eq!(calls, 50);
+        assert_eq!(analysis.count, 50);
+        assert!(analysis.p95 >= 1_000_000);
+        assert!(analysis.uncorrected.unwrap().p95 < 1_000_000);
+    }
+    
+    #[test]
+    fn test_open_loop_above_one_ghz() {
+        // Sub-nanosecond slots: the schedule runs ahead of the calls, so the
+        // backlog grows instead of the run turning closed-loop
+        let analysis = SimpleBench::new("open_loop_fast")
+            .bench_open_loop(2_000_000_000, 1000, || std::hint::black_box(1))
+            .analyze();
+        let uncorrected = analysis.uncorrected.as_ref().unwrap();
+        assert!(analysis.p50 > 5 * uncorrected.p50.max(1), "{} vs {}", analysis.p50, uncorrected.p50);
+    }
+    
+    #[test]
+    fn test_subtract_timer_overhead() {
+        quick_calibrate_tsc_frequency();
+        calibrate_timer_overhead();
+        
+        let analysis = SimpleBench::new("compensated")
+            .subtract_timer_overhead()
+            .bench(200, || std::hint::black_box(1 + 1))
+            .analyze();
+        
+        assert_eq!(analysis.count, 200);
+        assert!(analysis.overhead_subtracted_ns.is_some());
+        assert!(analysis.resolution_ns.is_some());
+        assert!(analysis.summary().contains("subtracted"));
+        
+        // An uncalibrated clock has its overhead measured rather than taken as 0
+        let uncalibrated = SimpleBench::new("uncalibrated")
+            .with_clock(Clock::from_frequency_mhz(3000))
+            .subtract_timer_overhead()
+            .bench(10, || std::hint::black_box(1))
+            .analyze();
+        assert!(uncalibrated.overhead_subtracted_ns.is_some_and(|overhead| overhead > 0));
+        
+        // Nothing is subtracted from open-loop latencies, so nothing is reported
+        let open_loop = SimpleBench::new("open_loop_uncompensated")
+            .subtract_timer_overhead()
+            .bench_open_loop(100_000, 10, || 1)
+            .analyze();
+        assert_eq!(open_loop.overhead_subtracted_ns, None);
+    }
+    
+    #[test]
+    fn test_bench_batched() {
+        quick_calibrate_tsc_frequency();
+        
+        let analysis = SimpleBench::new("batched_add")
+            .batch_target_multiple(20)
+            .bench_batched(100, || std::hint::black_box(3u64).wrapping_mul(7))
+            .analyze_with(&AnalysisOptions::default().with_resolution_ns(50));
+        
+        assert_eq!(analysis.count, 100);
+        let batch = analysis.batch_size.expect("batch size recorded");
+        assert!(batch > 1, "a trivial op should need batching, got {batch}");
+        assert!(analysis.p50 < 1000);
+        assert!(analysis.per_call.as_ref().is_some_and(|per_call| per_call.p50 > 0.0));
+        assert!(!analysis.below_resolution());
+        assert!(analysis.summary().contains("ops/sample"));
+        
+        // Without calibration the overhead measured for sizing is the one subtracted
+        let analysis = SimpleBench::new("batched_uncalibrated")
+            .with_clock(Clock::from_frequency_mhz(crate::mock_core::cpu_frequency_mhz()))
+            .subtract_timer_overhead()
+            .bench_batched(10, || std::hint::black_box(1))
+            .analyze();
+        assert!(analysis.overhead_subtracted_ns.is_some_and(|overhead| overhead > 0));
+    }
+    
+    #[test]
+    #[should_panic(expected = "unbatched")]
+    fn test_batched_refuses_unbatched_samples() {
+        SimpleBench::new("mixed").bench(10, || 1).bench_batched(10, || 1);
+    }
+    
+    #[test]
+    fn test_batched_calls_share_batch_size() {
+        let bench = SimpleBench::new("batched_twice")
+            .batch_target_multiple(1)
+            .bench_batched(10, || std::hint::black_box(1));
+        let batch = bench.batch_size;
+        let bench = bench.bench_batched(10, || std::hint::black_box(2));
+        assert_eq!(bench.batch_size, batch);
+        
+        let unbatched = std::panic::catch_unwind(|| bench.bench(10, || 1));
+        assert!(unbatched.is_err());
+    }
+    
+    #[test]
+    fn test_bench_with_clock() {
+        let clock = Clock::from_frequency_mhz(3000)
+            .with_timer_calibration(TimerCalibration {
+                overhead_min_ns: 10,
+                overhead_median_ns: 12,
+                overhead_p99_ns: 20,
+                resolution_ns: 5_000,
+            });
+        
+        let analysis = SimpleBench::new("own_clock")
+            .with_clock(clock)
+            .subtract_timer_overhead()
+            .bench(50, || std::hint::black_box(1))
+            .analyze();
+        
+        assert_eq!(analysis.count, 50);
+        assert_eq!(analysis.overhead_subtracted_ns, Some(12));
+        assert_eq!(analysis.resolution_ns, Some(5_000));
+    }
+    
+    #[test]
+    fn test_bench_in_cycles() {
+        let analysis = SimpleBench::new("cycles")
+            .with_clock(Clock::from_frequency_mhz(3000))
+            .in_cycles()
+            .bench(100, || std::hint::black_box(5u64).wrapping_mul(3))
+            .analyze();
+        
+        assert_eq!(analysis.count, 100);
+        let cycles = analysis.cycles.as_ref().expect("cycle statistics");
+        assert_eq!(cycles.frequency_khz, 3_000_000);
+        assert_eq!(analysis.p50, (cycles.p50 / 3.0).round() as u64);
+        assert!(analysis.summary_in(ReportUnit::Cycles).contains("cycles"));
+        assert!(SimpleBench::new("ns").bench(10, || 1).analyze().cycles.is_none());
+    }
+    
+    #[test]
+    fn test_cycles_recorded_without_ns_round_trip() {
+        // At a nominal 1 THz every nanosecond is 1000 ticks, so samples that
+        // went through nanoseconds would all be multiples of 1000
+        let clock = Clock::from_frequency_khz(1_000_000_000);
+        let raw_ticks = |cycles: &CycleAnalysis, scale: f64| {
+            [cycles.min, cycles.p50, cycles.p95, cycles.max]
+                .iter()
+                .any(|v| !((v * scale).round() as u64).is_multiple_of(1000))
+        };
+        
+        let open_loop = SimpleBench::new("open_loop_cycles")
+            .with_clock(clock.clone())
+            .in_cycles()
+            .bench_open_loop(1_000_000, 20, || std::hint::black_box(1))
+            .analyze();
+        assert!(raw_ticks(open_loop.cycles.as_ref().unwrap(), 1.0));
+        
+        let batched = SimpleBench::new("batched_cycles")
+            .with_clock(clock)
+            .in_cycles()
+            .batch_target_multiple(1)
+            .bench_batched(10, || std::hint::black_box(1))
+            .analyze();
+        let batch = batched.batch_size.unwrap() as f64;
+        assert!(raw_ticks(batched.cycles.as_ref().unwrap(), batch));
+    }
+    
+    #[cfg(feature = "perf")]
+    #[test]
+    fn test_bench_with_perf_counters() {
+        let counters = PerfCounters::open(&[HardwareEvent::Instructions, HardwareEvent::BranchMisses]);
+        let available = counters.events().len();
+        
+        let analysis = SimpleBench::new("counted")
+            .with_perf_counters(counters)
+            .bench(100, || (0..100u64).map(std::hint::black_box).sum::<u64>())
+            .analyze();
+        
+        let report = analysis.hardware_counters.as_ref().expect("counter report");
+        assert_eq!(report.counters.len(), available);
+        assert_eq!(report.counters.len() + report.unavailable.len(), 2);
+        if let Some(instructions) = report.get("instructions") {
+            assert!(instructions.p50 >= 100.0);
+        }
+        assert!(analysis.summary().contains("per sample") || analysis.summary().contains("counters unavailable"));
+    }
+    
+    #[test]
+    fn test_track_disturbances() {
+        let mut calls = 0;
+        let analysis = SimpleBench::new("disturbed")
+            .track_disturbances()
+            .bench(50, || {
+                calls += 1;
+                if calls % 10 == 0 {
+                    std::thread::sleep(std::time::Duration::from_micros(200));
+                }
+            })
+            .analyze();
+        
+        #[cfg(target_os = "linux")]
+        {
+            let report = analysis.disturbances.as_ref().expect("disturbance report");
+            assert_eq!(report.clean_samples + report.disturbed_samples, 50);
+            assert!(report.disturbed_samples >= 5);
+            assert!(report.totals.voluntary_switches >= 5);
+            assert!(report.disturbed.p50 > report.clean.p50);
+            assert!(analysis.summary().contains("disturbed:"));
+        }
+        assert!(SimpleBench::new("plain").bench(5, || 1).analyze().disturbances.is_none());
+    }
+    
+    #[test]
+    fn test_warmup_is_discarded_and_reported() {
+        let mut calls = 0;
+        let analysis = SimpleBench::new("warm")
+            .warmup(Warmup::Iterations(500))
+            .bench(100, || calls += 1)
+            .analyze();
+        
+        assert_eq!(calls, 600);
+        assert_eq!(analysis.count, 100);
+        let warmup = analysis.warmup.as_ref().expect("warmup report");
+        assert_eq!(warmup.discarded, 500);
+        assert_eq!(warmup.trajectory.len(), 10);
+        assert!(analysis.summary().contains("warmup: 500 calls discarded"));
+        
+        assert!(SimpleBench::new("cold").bench(5, || 1).analyze().warmup.is_none());
+    }
+    
+    #[test]
+    fn test_bench_until_time_budget() {
+        let budget = std::time::Duration::from_millis(20);
+        let started = std::time::Instant::now();
+        let analysis = SimpleBench::new("timed")
+            .bench_until(StopCondition::Time(budget), || std::hint::black_box(7u64).pow(3))
+            .analyze();
+        
+        assert_eq!(analysis.stop_reason, Some(StopReason::TimeBudget));
+        assert!(started.elapsed() >= budget);
+        assert!(started.elapsed() < budget * 20);
+        assert!(analysis.count >= 100);
+        
+        // A slow function still gets the minimum
+        let analysis = SimpleBench::new("slow")
+            .sample_bounds(5, 50)
+            .bench_until(StopCondition::Time(std::time::Duration::ZERO), || std::thread::sleep(std::time::Duration::from_micros(100)))
+            .analyze();
+        assert_eq!(analysis.count, 5);
+    }
+    
+    #[test]
+    fn test_bench_until_precise() {
+        let analysis = SimpleBench::new("precise")
+            .bench_until(StopCondition::Precision { percentile: 50.0, relative_width: 0.2 }, || {
+                (0..100u64).map(std::hint::black_box).sum::<u64>()
+            })
+            .analyze();
+        assert_eq!(analysis.stop_reason, Some(StopReason::PrecisionReached));
+        assert!(analysis.count >= 100);
+        
+        let capped = SimpleBench::new("capped")
+            .sample_bounds(10, 200)
+            .bench_until(StopCondition::Precision { percentile: 99.9, relative_width: 0.001 }, || 1)
+            .analyze();
+        assert_eq!(capped.stop_reason, Some(StopReason::MaxSamples));
+        assert_eq!(capped.count, 200);
+        assert!(capped.summary().contains("sample limit"));
+    }
+    
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pinned_bench() {
+        let cpus = affinity::allowed_cpus().unwrap();
+        let cpu = *cpus.last().unwrap();
+        
+        let analysis = SimpleBench::new("pinned")
+            .pinned(Pinning::cpu(cpu))
+            .unwrap()
+            .bench(50, || assert_eq!(affinity::current_cpu(), Some(cpu)))
+            .analyze();
+        
+        assert_eq!(analysis.pinning, Some(Pinning::cpu(cpu)));
+        assert!(analysis.summary().contains(&format!("pinned to cpu{cpu}")));
+        assert_eq!(affinity::allowed_cpus().unwrap(), cpus);
+        
+        assert!(SimpleBench::new("nowhere").pinned(Pinning::cpu(100_000)).is_err());
+        assert_eq!(affinity::allowed_cpus().unwrap(), cpus);
+        
+        // A call that can't be pinned still runs, but the analysis says so
+        let mut bench = SimpleBench::new("lost").pinned(Pinning::cpu(cpu)).unwrap();
+        bench.pinning = Some(Pinning::cpu(100_000));
+        let analysis = bench.bench(10, || ()).analyze();
+        assert_eq!(analysis.pinning, None);
+        assert!(analysis.pinning_error.as_deref().is_some_and(|e| e.contains("cpu100000")));
+        assert!(analysis.summary().contains("WARNING: not pinned throughout"));
+        assert_eq!(affinity::allowed_cpus().unwrap(), cpus);
+        
+        // Pinning is applied per bench call, so a configured bench can move threads
+        fn assert_send<T: Send>(_: &T) {}
+        let bench = SimpleBench::new("sendable").pinned(Pinning::cpu(cpu)).unwrap();
+        assert_send(&bench);
+        assert_eq!(affinity::allowed_cpus().unwrap(), cpus);
+    }
+    
+    #[test]
+    fn test_migration_tracking() {
+        quick_calibrate_tsc_frequency();
+        
+        let analysis = SimpleBench::new("tracked")
+            .on_migration(MigrationPolicy::Discard)
+            .bench(100, || std::hint::black_box(2 + 2))
+            .analyze();
+        
+        let migrated = analysis.migrated_samples.expect("tracking enabled");
+        assert_eq!(analysis.count as u64 + migrated, 100);
+        
+        let untracked = SimpleBench::new("untracked").bench(10, || 1).analyze();
+        assert_eq!(untracked.migrated_samples, None);
+        
+        // The tracked timer's own overhead is measured, even on an uncalibrated clock
+        let compensated = SimpleBench::new("tracked_compensated")
+            .with_clock(Clock::from_frequency_mhz(crate::mock_core::cpu_frequency_mhz()))
+            .on_migration(MigrationPolicy::Flag)
+            .subtract_timer_overhead()
+            .bench(10, || 1)
+            .analyze();
+        assert!(compensated.overhead_subtracted_ns.is_some_and(|overhead| overhead > 0));
+    }
+    
     #[test]
     fn test_time_function() {
         quick_calibrate_tsc_frequency();
@@ -2,14 +2,44 @@
 
 use std::sync::atomic::{AtomicU64, Ordering};
 
-static FREQUENCY_MHZ: AtomicU64 = AtomicU64::new(3000); // Default 3GHz
+static FREQUENCY_KHZ: AtomicU64 = AtomicU64::new(3_000_000); // Default 3GHz
 
 pub fn cpu_frequency_mhz() -> u64 {
-    FREQUENCY_MHZ.load(Ordering::Relaxed)
+    (cpu_frequency_khz() + 500) / 1000
 }
 
 pub fn set_cpu_frequency_mhz(freq: u64) {
-    FREQUENCY_MHZ.store(freq, Ordering::Relaxed);
+    set_cpu_frequency_khz(freq * 1000);
+}
+
+/// Counter frequency at full precision, as used for tick conversions
+pub fn cpu_frequency_khz() -> u64 {
+    FREQUENCY_KHZ.load(Ordering::Relaxed)
+}
+
+pub fn set_cpu_frequency_khz(freq: u64) {
+    FREQUENCY_KHZ.store(freq, Ordering::Relaxed);
+}
+
+static TIMER_OVERHEAD_NS: AtomicU64 = AtomicU64::new(0); // Unknown until calibrated
+static TIMER_RESOLUTION_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Median cost of an empty `PrecisionTimer` start/stop pair
+pub fn timer_overhead_ns() -> u64 {
+    TIMER_OVERHEAD_NS.load(Ordering::Relaxed)
+}
+
+pub fn set_timer_overhead_ns(overhead: u64) {
+    TIMER_OVERHEAD_NS.store(overhead, Ordering::Relaxed);
+}
+
+/// Smallest nonzero interval the timer can report
+pub fn timer_resolution_ns() -> u64 {
+    TIMER_RESOLUTION_NS.load(Ordering::Relaxed)
+}
+
+pub fn set_timer_resolution_ns(resolution: u64) {
+    TIMER_RESOLUTION_NS.store(resolution, Ordering::Relaxed);
 }
 
 /// Mock timestamp type for benchmarking
@@ -232,22 +262,5 @@ impl<T> ObjectPool<T> {
     }
 }
 
-/// Mock NUMA arena allocator for benchmarking
-pub struct NumaArenaAllocator {
-    node_id: usize,
-}
-
-impl NumaArenaAllocator {
-    pub fn new(node_id: usize) -> Self {
-        Self { node_id }
-    }
-    
-    pub fn allocate(&self, size: usize) -> Vec<u8> {
-        // Just use regular allocation for the mock
-        vec![0u8; size]
-    }
-    
-    pub fn node_id(&self) -> usize {
-        self.node_id
-    }
-}
\ No newline at end of file
+/// NUMA-bound arena allocator; see `crate::numa`
+pub use crate::numa::NumaArenaAllocator;
@@ -0,0 +1,505 @@
+//! NUMA-bound memory arenas and local vs remote access benchmarks
+//!
+//! An arena is one anonymous mapping bound to a node with `mbind` and
+//! pre-faulted, so every allocation from it is served from that node's
+//! memory. On kernels or machines without NUMA the binding is skipped and
+//! the arena behaves like plain memory on the only node.
+
+use std::cell::Cell;
+use std::io;
+use std::ptr::NonNull;
+
+use crate::topology::Topology;
+use crate::{BenchmarkAnalysis, BenchmarkResults};
+
+/// Arena size used by `NumaArenaAllocator::new`
+const DEFAULT_ARENA_BYTES: usize = 64 << 20;
+
+/// Huge page size requested from `MAP_HUGETLB` (with `MAP_HUGE_2MB`, so a
+/// 1 GiB default hugetlb size doesn't change it)
+const HUGE_PAGE_BYTES: usize = 2 << 20;
+
+/// How an arena's memory is backed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArenaBacking {
+    Normal,
+    /// Regular mapping with `MADV_HUGEPAGE`, promoted by the kernel when it can
+    TransparentHugePages,
+    /// Reserved huge pages (`MAP_HUGETLB`)
+    HugeTlb,
+}
+
+/// Bump allocator over a mapping bound to one NUMA node
+///
+/// Allocations borrow the arena shared, so several can be live at once;
+/// `reset` borrows it mutably and so invalidates all of them.
+pub struct NumaArenaAllocator {
+    base: NonNull<u8>,
+    capacity: usize,
+    offset: Cell<usize>,
+    node_id: usize,
+    bound: bool,
+    backing: ArenaBacking,
+}
+
+// The arena owns its mapping and only hands it out through `&mut self`
+unsafe impl Send for NumaArenaAllocator {}
+
+impl NumaArenaAllocator {
+    /// A 64 MiB arena on `node_id`
+    ///
+    /// Falls back to an unbound arena if the node doesn't exist or the
+    /// kernel lacks NUMA support; check `is_bound`. Fails only if the
+    /// memory cannot be mapped at all.
+    pub fn new(node_id: usize) -> io::Result<Self> {
+        Self::with_capacity(node_id, DEFAULT_ARENA_BYTES)
+            .or_else(|_| Self::map(node_id, DEFAULT_ARENA_BYTES, false, false))
+    }
+
+    /// An arena of `capacity` bytes bound to `node_id`
+    ///
+    /// Fails with `InvalidInput` for a node the machine doesn't have.
+    pub fn with_capacity(node_id: usize, capacity: usize) -> io::Result<Self> {
+        Self::map(node_id, capacity, true, false)
+    }
+
+    /// Like `with_capacity`, backed by huge pages: reserved ones if
+    /// available, otherwise transparent huge pages
+    pub fn with_huge_pages(node_id: usize, capacity: usize) -> io::Result<Self> {
+        Self::map(node_id, capacity, true, true)
+    }
+
+    fn map(node_id: usize, capacity: usize, bind: bool, huge_pages: bool) -> io::Result<Self> {
+        if bind && !node_exists(node_id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no NUMA node {node_id}")));
+        }
+        let (base, capacity, backing) = map_anonymous(capacity.max(1), huge_pages)?;
+        let mut arena = Self { base, capacity, offset: Cell::new(0), node_id, bound: false, backing };
+        if bind {
+            arena.bound = bind_to_node(base, capacity, node_id)?;
+        }
+        arena.prefault();
+        Ok(arena)
+    }
+
+    /// Touch every page so placement (and fault cost) happens up front
+    fn prefault(&self) {
+        let page = page_size();
+        for offset in (0..self.capacity).step_by(page) {
+            unsafe { self.base.as_ptr().add(offset).write_volatile(0) };
+        }
+    }
+
+    /// `size` bytes aligned to 64 bytes, or `None` once the arena is full
+    ///
+    /// The slice borrows the arena, so it can't outlive a `reset`.
+    pub fn allocate(&self, size: usize) -> Option<&mut [u8]> {
+        self.allocate_aligned(size, 64)
+    }
+
+    /// `size` bytes aligned to `align` (a power of two)
+    // Each call hands out a range no other live slice covers
+    #[allow(clippy::mut_from_ref)]
+    pub fn allocate_aligned(&self, size: usize, align: usize) -> Option<&mut [u8]> {
+        let align = align.max(1).next_power_of_two();
+        let start = self.offset.get().checked_add(align - 1)? & !(align - 1);
+        let end = start.checked_add(size)?;
+        if end > self.capacity {
+            return None;
+        }
+        self.offset.set(end);
+        Some(unsafe { std::slice::from_raw_parts_mut(self.base.as_ptr().add(start), size) })
+    }
+
+    /// Free every allocation at once
+    pub fn reset(&mut self) {
+        self.offset.set(0);
+    }
+
+    pub fn node_id(&self) -> usize {
+        self.node_id
+    }
+
+    /// Whether the memory is actually bound to `node_id`
+    pub fn is_bound(&self) -> bool {
+        self.bound
+    }
+
+    pub fn backing(&self) -> ArenaBacking {
+        self.backing
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.capacity - self.offset.get()
+    }
+}
+
+impl Drop for NumaArenaAllocator {
+    fn drop(&mut self) {
+        unmap(self.base, self.capacity);
+    }
+}
+
+fn node_exists(node_id: usize) -> bool {
+    Topology::detect().is_ok_and(|topology| topology.node(node_id).is_some())
+}
+
+fn page_size() -> usize {
+    #[cfg(unix)]
+    {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+    #[cfg(not(unix))]
+    {
+        4096
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn map_anonymous(capacity: usize, huge_pages: bool) -> io::Result<(NonNull<u8>, usize, ArenaBacking)> {
+    let map = |len: usize, extra_flags: libc::c_int| unsafe {
+        let ptr = libc::mmap(
+            std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | extra_flags, -1, 0,
+        );
+        (ptr != libc::MAP_FAILED).then(|| NonNull::new(ptr as *mut u8)).flatten()
+    };
+
+    if huge_pages {
+        let len = capacity.div_ceil(HUGE_PAGE_BYTES) * HUGE_PAGE_BYTES;
+        if let Some(ptr) = map(len, libc::MAP_HUGETLB | libc::MAP_HUGE_2MB) {
+            return Ok((ptr, len, ArenaBacking::HugeTlb));
+        }
+    }
+    let len = capacity.div_ceil(page_size()) * page_size();
+    let ptr = map(len, 0).ok_or_else(io::Error::last_os_error)?;
+    if huge_pages && unsafe { libc::madvise(ptr.as_ptr() as *mut libc::c_void, len, libc::MADV_HUGEPAGE) } == 0 {
+        return Ok((ptr, len, ArenaBacking::TransparentHugePages));
+    }
+    Ok((ptr, len, ArenaBacking::Normal))
+}
+
+#[cfg(target_os = "linux")]
+fn unmap(base: NonNull<u8>, capacity: usize) {
+    unsafe {
+        libc::munmap(base.as_ptr() as *mut libc::c_void, capacity);
+    }
+}
+
+/// `mbind(MPOL_BIND)` the range to `node_id`; `Ok(false)` when the kernel
+/// has no NUMA support
+#[cfg(target_os = "linux")]
+fn bind_to_node(base: NonNull<u8>, capacity: usize, node_id: usize) -> io::Result<bool> {
+    const MPOL_BIND: libc::c_ulong = 2;
+    const BITS: usize = libc::c_ulong::BITS as usize;
+
+    let mut nodemask = vec![0 as libc::c_ulong; node_id / BITS + 1];
+    nodemask[node_id / BITS] |= 1 << (node_id % BITS);
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind, base.as_ptr(), capacity, MPOL_BIND, nodemask.as_ptr(),
+            (nodemask.len() * BITS + 1) as libc::c_ulong, 0 as libc::c_uint,
+        )
+    };
+    if result == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::ENOSYS) | Some(libc::EPERM) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn map_anonymous(capacity: usize, _huge_pages: bool) -> io::Result<(NonNull<u8>, usize, ArenaBacking)> {
+    let layout = std::alloc::Layout::from_size_align(capacity, page_size())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let ptr = NonNull::new(unsafe { std::alloc::alloc_zeroed(layout) }).ok_or_else(|| io::Error::from(io::ErrorKind::OutOfMemory))?;
+    Ok((ptr, capacity, ArenaBacking::Normal))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unmap(base: NonNull<u8>, capacity: usize) {
+    let layout = std::alloc::Layout::from_size_align(capacity, page_size()).unwrap();
+    unsafe { std::alloc::dealloc(base.as_ptr(), layout) };
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_to_node(_base: NonNull<u8>, _capacity: usize, _node_id: usize) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Access cost from CPUs of one node to memory of another
+#[derive(Debug, Clone)]
+pub struct NodeAccess {
+    pub cpu_node: usize,
+    pub memory_node: usize,
+    /// Dependent-load latency per access, one sample per chase of
+    /// `CHASE_HOPS` loads
+    pub latency: BenchmarkAnalysis,
+    /// Sequential read bandwidth
+    pub bandwidth_gb_per_s: f64,
+}
+
+impl NodeAccess {
+    pub fn is_local(&self) -> bool {
+        self.cpu_node == self.memory_node
+    }
+}
+
+/// A CPU node and memory node pair that could not be measured
+#[derive(Debug)]
+pub struct NodeAccessFailure {
+    pub cpu_node: usize,
+    pub memory_node: usize,
+    pub error: io::Error,
+}
+
+/// Result of a NUMA access sweep: the pairs that were measured and the
+/// ones that failed
+#[derive(Debug, Default)]
+pub struct NumaAccessReport {
+    pub accesses: Vec<NodeAccess>,
+    pub failures: Vec<NodeAccessFailure>,
+}
+
+/// Dependent loads per latency sample
+const CHASE_HOPS: usize = 1000;
+
+/// Buffer size for `benchmark_numa_access`, well past any last-level cache
+const DEFAULT_ACCESS_BYTES: usize = 256 << 20;
+
+/// Measure latency and bandwidth for every pair of CPU node and memory node
+///
+/// On a single-node machine this is one local measurement.
+pub fn benchmark_numa_access() -> io::Result<NumaAccessReport> {
+    benchmark_numa_access_with(DEFAULT_ACCESS_BYTES, 1_000)
+}
+
+/// `benchmark_numa_access` with `bytes` per buffer (at least one 64-byte
+/// line) and `samples` latency samples per pair
+///
+/// Nodes without CPUs (CXL or HBM memory) are only measured as memory
+/// nodes and memoryless nodes only as CPU nodes. A pair that fails, for
+/// example because none of its node's CPUs are in this thread's affinity,
+/// is reported in `failures` and the sweep moves on.
+pub fn benchmark_numa_access_with(bytes: usize, samples: usize) -> io::Result<NumaAccessReport> {
+    check_chase_bytes(bytes)?;
+    let topology = Topology::detect()?;
+    let allowed = crate::affinity::allowed_cpus()?;
+    println!("Benchmarking NUMA access ({} nodes, {} MiB buffers)...", topology.node_count(), bytes >> 20);
+
+    let mut report = NumaAccessReport::default();
+    for (cpu_node, memory_node, cpu) in node_pairs(&topology, &allowed) {
+        let measured = cpu
+            .ok_or_else(|| no_allowed_cpu(cpu_node))
+            .and_then(|cpu| measure_on_cpu(cpu, cpu_node, memory_node, bytes, samples));
+        match measured {
+            Ok(access) => {
+                println!(
+                    "{} | {:.2} GB/s{}",
+                    access.latency.summary(), access.bandwidth_gb_per_s,
+                    if access.is_local() { " (local)" } else { " (remote)" }
+                );
+                report.accesses.push(access);
+            }
+            Err(error) => {
+                println!("cpu node {cpu_node} -> memory node {memory_node}: failed ({error})");
+                report.failures.push(NodeAccessFailure { cpu_node, memory_node, error });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// `(cpu_node, memory_node, cpu)` for every pair worth measuring, with the
+/// first CPU of `cpu_node` in `allowed`, if any
+fn node_pairs(topology: &Topology, allowed: &[usize]) -> Vec<(usize, usize, Option<usize>)> {
+    let mut pairs = Vec::new();
+    for cpu_node in topology.nodes.iter().filter(|node| !node.cpus.is_empty()) {
+        let cpu = cpu_node.cpus.iter().copied().find(|cpu| allowed.contains(cpu));
+        for memory_node in topology.nodes.iter().filter(|node| node.memory_kb != Some(0)) {
+            pairs.push((cpu_node.id, memory_node.id, cpu));
+        }
+    }
+    pairs
+}
+
+fn no_allowed_cpu(node: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("no CPU of node {node} in this thread's affinity"))
+}
+
+/// Run on a CPU of `cpu_node` that this thread may use and access memory
+/// bound to `memory_node`
+pub fn measure_node_access(
+    topology: &Topology,
+    cpu_node: usize,
+    memory_node: usize,
+    bytes: usize,
+    samples: usize,
+) -> io::Result<NodeAccess> {
+    check_chase_bytes(bytes)?;
+    let node = topology.node(cpu_node)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("no NUMA node {cpu_node}")))?;
+    let allowed = crate::affinity::allowed_cpus()?;
+    let cpu = node.cpus.iter().copied()
+        .find(|cpu| allowed.contains(cpu))
+        .ok_or_else(|| no_allowed_cpu(cpu_node))?;
+    measure_on_cpu(cpu, cpu_node, memory_node, bytes, samples)
+}
+
+fn measure_on_cpu(cpu: usize, cpu_node: usize, memory_node: usize, bytes: usize, samples: usize) -> io::Result<NodeAccess> {
+    crate::affinity::run_on_cpu(cpu, || {
+        let arena = NumaArenaAllocator::with_capacity(memory_node, bytes)?;
+        let buffer = arena.allocate(bytes).expect("arena sized for the buffer");
+        let lines = bytes / 64;
+        build_chase(buffer, lines);
+
+        let name = format!("numa_cpu{cpu_node}_mem{memory_node}_latency");
+        let mut results = BenchmarkResults::new(name);
+        let mut position = 0usize;
+        for _ in 0..samples {
+            let (next, elapsed) = crate::timing::time_function(|| chase(buffer, position, CHASE_HOPS));
+            position = next;
+            results.record((elapsed + CHASE_HOPS as u64 / 2) / CHASE_HOPS as u64);
+        }
+
+        let words: &[u64] = unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u64, bytes / 8) };
+        let started = std::time::Instant::now();
+        let sum: u64 = words.iter().fold(0u64, |acc, &w| acc.wrapping_add(w));
+        std::hint::black_box(sum);
+        let bandwidth_gb_per_s = bytes as f64 / started.elapsed().as_secs_f64().max(1e-9) / 1e9;
+
+        Ok(NodeAccess { cpu_node, memory_node, latency: results.analyze(), bandwidth_gb_per_s })
+    })?
+}
+
+/// The pointer chase needs at least one 64-byte line to follow
+fn check_chase_bytes(bytes: usize) -> io::Result<()> {
+    if bytes < 64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{bytes} bytes is less than one 64-byte line")));
+    }
+    Ok(())
+}
+
+/// Link the 64-byte lines of `buffer` into one random cycle, each line's
+/// first word holding the index of the next line
+fn build_chase(buffer: &mut [u8], lines: usize) {
+    let mut order: Vec<usize> = (0..lines).collect();
+    fastrand::shuffle(&mut order);
+    let words = buffer.as_mut_ptr() as *mut u64;
+    for i in 0..lines {
+        let (from, to) = (order[i], order[(i + 1) % lines]);
+        unsafe { words.add(from * 8).write(to as u64) };
+    }
+}
+
+/// Follow `hops` links from line `start`, returning where it ended
+#[inline(never)]
+fn chase(buffer: &[u8], start: usize, hops: usize) -> usize {
+    let words = buffer.as_ptr() as *const u64;
+    let mut line = start;
+    for _ in 0..hops {
+        line = unsafe { words.add(line * 8).read_volatile() } as usize;
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::NumaNode;
+
+    #[test]
+    fn test_arena_bump_allocation() {
+        let mut arena = NumaArenaAllocator::with_capacity(0, 64 * 1024).unwrap();
+        assert_eq!(arena.node_id(), 0);
+        assert!(arena.capacity() >= 64 * 1024);
+
+        let first = arena.allocate(100).unwrap();
+        let second = arena.allocate_aligned(10, 4096).unwrap();
+        first.fill(7);
+        assert_eq!(second.as_ptr() as usize % 4096, 0);
+        assert!(second.iter().all(|&b| b == 0));
+        assert!(second.as_ptr() as usize >= first.as_ptr() as usize + 100);
+        second.fill(9);
+        assert!(first.iter().all(|&b| b == 7));
+
+        assert!(arena.allocate(arena.capacity()).is_none());
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+        assert_eq!(arena.allocate(arena.capacity()).map(|b| b.len()), Some(arena.capacity()));
+
+        assert_eq!(
+            NumaArenaAllocator::with_capacity(4096, 4096).err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+        assert!(!NumaArenaAllocator::new(4096).unwrap().is_bound());
+    }
+
+    #[test]
+    fn test_huge_page_arena() {
+        let arena = NumaArenaAllocator::with_huge_pages(0, 4 << 20).unwrap();
+        assert!(arena.allocate(4 << 20).is_some());
+        if arena.backing() == ArenaBacking::HugeTlb {
+            assert_eq!(arena.capacity() % HUGE_PAGE_BYTES, 0);
+        }
+    }
+
+    /// Node 0 is ordinary, node 1 has CPUs but no memory, node 2 has
+    /// memory but no CPUs (CXL-style)
+    fn fixture_topology() -> Topology {
+        let node = |id, cpus: Vec<usize>, memory_kb| NumaNode { id, cpus, memory_kb, distances: vec![10, 21, 30] };
+        Topology {
+            nodes: vec![
+                node(0, vec![0, 1, 2, 3], Some(16_384_000)),
+                node(1, vec![4, 5, 6, 7], Some(0)),
+                node(2, vec![], Some(65_536_000)),
+            ],
+            cpus: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_node_pairs_skip_cpuless_and_memoryless_nodes() {
+        let topology = fixture_topology();
+        assert_eq!(
+            node_pairs(&topology, &[1, 2, 5, 6]),
+            vec![(0, 0, Some(1)), (0, 2, Some(1)), (1, 0, Some(5)), (1, 2, Some(5))]
+        );
+        // Node 1's CPUs are outside the affinity: its pairs are kept so they
+        // can be reported as failures
+        assert_eq!(
+            node_pairs(&topology, &[3]),
+            vec![(0, 0, Some(3)), (0, 2, Some(3)), (1, 0, None), (1, 2, None)]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_local_access_benchmark() {
+        let report = benchmark_numa_access_with(4 << 20, 20).unwrap();
+        let topology = Topology::detect().unwrap();
+        let allowed = crate::affinity::allowed_cpus().unwrap();
+        assert_eq!(report.accesses.len() + report.failures.len(), node_pairs(&topology, &allowed).len());
+
+        let local = report.accesses.iter().find(|a| a.is_local()).unwrap();
+        assert_eq!(local.latency.count, 20);
+        assert!(local.latency.p50 > 0);
+        assert!(local.bandwidth_gb_per_s > 0.0);
+
+        assert_eq!(
+            benchmark_numa_access_with(63, 1).err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+    }
+}
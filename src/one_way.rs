@@ -0,0 +1,237 @@
+//! One-way latency between threads from counter stamps carried in messages
+//!
+//! A producer calls `LatencySink::stamp` and embeds the `Stamp` in whatever
+//! it sends; the consumer passes the received stamp to `LatencySink::record`
+//! (or a per-thread `LatencyRecorder`), which converts the elapsed cycles
+//! with the sink's `Clock`. Stamps from different cores are only comparable
+//! if the TSCs are synchronized (see `tsc_sync`) or the clock carries
+//! per-core offsets.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::clock::Clock;
+use crate::stats::{AnalysisOptions, BenchmarkAnalysis, BenchmarkResults};
+
+/// Raw counter value taken when a message was produced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Stamp(u64);
+
+impl Stamp {
+    /// Stamp with `clock`, corrected by the current CPU's offset
+    #[inline(always)]
+    pub fn now(clock: &Clock) -> Self {
+        Self(clock.now())
+    }
+
+    pub fn from_raw(cycles: u64) -> Self {
+        Self(cycles)
+    }
+
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    /// Cycles from this stamp to `later`, `None` if `later` is earlier
+    pub fn cycles_until(self, later: Stamp) -> Option<u64> {
+        later.0.checked_sub(self.0)
+    }
+}
+
+struct SinkInner {
+    clock: Clock,
+    results: Mutex<BenchmarkResults>,
+    /// Receipts stamped before their send, a sign of unsynchronized TSCs
+    negative: AtomicU64,
+}
+
+/// Thread-safe collector of one-way latencies
+///
+/// Cloning shares the same underlying results.
+#[derive(Clone)]
+pub struct LatencySink {
+    inner: Arc<SinkInner>,
+}
+
+impl LatencySink {
+    /// Sink using the process-wide calibration
+    pub fn new(name: &str) -> Self {
+        Self::with_clock(name, Clock::global())
+    }
+
+    pub fn with_clock(name: &str, clock: Clock) -> Self {
+        Self::from_results(BenchmarkResults::new(name.to_string()), clock)
+    }
+
+    /// Sink recording into an HDR histogram, for long-running feeds
+    pub fn with_histogram(name: &str, clock: Clock, significant_digits: u8, max_trackable_ns: u64) -> Self {
+        Self::from_results(BenchmarkResults::with_histogram(name.to_string(), significant_digits, max_trackable_ns), clock)
+    }
+
+    fn from_results(results: BenchmarkResults, clock: Clock) -> Self {
+        Self {
+            inner: Arc::new(SinkInner {
+                clock,
+                results: Mutex::new(results),
+                negative: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.inner.clock
+    }
+
+    /// Stamp to embed in an outgoing message
+    #[inline(always)]
+    pub fn stamp(&self) -> Stamp {
+        Stamp::now(&self.inner.clock)
+    }
+
+    /// Record the latency from `sent` until now; `None` if `sent` appears
+    /// to be in the future, which is counted in `negative_count` instead
+    pub fn record(&self, sent: Stamp) -> Option<u64> {
+        let latency = self.latency_ns(sent, self.stamp())?;
+        self.inner.results.lock().unwrap().record(latency);
+        Some(latency)
+    }
+
+    /// A recorder that buffers on the consumer thread and flushes into this
+    /// sink when dropped, keeping the lock out of the receive path
+    pub fn recorder(&self) -> LatencyRecorder {
+        LatencyRecorder {
+            sink: self.clone(),
+            pending: Vec::with_capacity(RECORDER_BATCH),
+        }
+    }
+
+    fn latency_ns(&self, sent: Stamp, received: Stamp) -> Option<u64> {
+        match sent.cycles_until(received) {
+            Some(cycles) => Some(self.inner.clock.cycles_to_ns(cycles)),
+            None => {
+                self.inner.negative.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.results.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn negative_count(&self) -> u64 {
+        self.inner.negative.load(Ordering::Relaxed)
+    }
+
+    /// Borrow the recorded results, e.g. for export
+    pub fn with_results<R>(&self, f: impl FnOnce(&BenchmarkResults) -> R) -> R {
+        f(&self.inner.results.lock().unwrap())
+    }
+
+    pub fn analyze(&self) -> BenchmarkAnalysis {
+        self.analyze_with(&AnalysisOptions::default())
+    }
+
+    pub fn analyze_with(&self, options: &AnalysisOptions) -> BenchmarkAnalysis {
+        self.with_results(|results| results.analyze_with(options))
+    }
+}
+
+/// Latencies buffered before a `LatencyRecorder` takes the sink's lock
+const RECORDER_BATCH: usize = 1024;
+
+/// Per-consumer-thread handle onto a `LatencySink`
+pub struct LatencyRecorder {
+    sink: LatencySink,
+    pending: Vec<u64>,
+}
+
+impl LatencyRecorder {
+    #[inline(always)]
+    pub fn record(&mut self, sent: Stamp) -> Option<u64> {
+        let received = self.sink.stamp();
+        self.record_between(sent, received)
+    }
+
+    /// Record a latency whose receive stamp was taken elsewhere
+    pub fn record_between(&mut self, sent: Stamp, received: Stamp) -> Option<u64> {
+        let latency = self.sink.latency_ns(sent, received)?;
+        self.pending.push(latency);
+        if self.pending.len() >= RECORDER_BATCH {
+            self.flush();
+        }
+        Some(latency)
+    }
+
+    pub fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let mut results = self.sink.inner.results.lock().unwrap();
+        for latency in self.pending.drain(..) {
+            results.record(latency);
+        }
+    }
+}
+
+impl Drop for LatencyRecorder {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_conversion() {
+        let sink = LatencySink::with_clock("conversion", Clock::from_frequency_mhz(2000));
+        let mut recorder = sink.recorder();
+
+        assert_eq!(recorder.record_between(Stamp::from_raw(1_000), Stamp::from_raw(3_000)), Some(1_000));
+        assert_eq!(recorder.record_between(Stamp::from_raw(5_000), Stamp::from_raw(4_000)), None);
+        assert_eq!(sink.len(), 0);
+        drop(recorder);
+
+        assert_eq!(sink.len(), 1);
+        assert_eq!(sink.negative_count(), 1);
+        assert_eq!(sink.analyze().p50, 1_000);
+    }
+
+    #[test]
+    fn test_cross_thread_queue_hop() {
+        use std::sync::mpsc;
+
+        let sink = LatencySink::with_histogram("queue_hop", Clock::global(), 3, 1_000_000_000);
+        let (tx, rx) = mpsc::channel::<(u64, Stamp)>();
+
+        let consumer = {
+            let sink = sink.clone();
+            std::thread::spawn(move || {
+                let mut recorder = sink.recorder();
+                for (_, stamp) in rx {
+                    recorder.record(stamp);
+                }
+            })
+        };
+
+        for i in 0..500 {
+            tx.send((i, sink.stamp())).unwrap();
+        }
+        drop(tx);
+        consumer.join().unwrap();
+
+        assert_eq!(sink.len() as u64 + sink.negative_count(), 500);
+        let analysis = sink.analyze();
+        assert_eq!(analysis.name, "queue_hop");
+        assert!(analysis.count > 0);
+
+        assert!(sink.record(sink.stamp()).is_some());
+    }
+}
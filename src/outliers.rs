@@ -0,0 +1,280 @@
+//! Outlier detection and classification for benchmark samples
+
+use crate::histogram::Histogram;
+use crate::stats::{percentile_with, PercentileMethod};
+
+/// Scale factor making the MAD a consistent estimator of the standard
+/// deviation for normally distributed data
+const MAD_NORMAL_CONSISTENCY: f64 = 0.6745;
+
+/// The same for the mean absolute deviation (`sqrt(pi / 2)`), used when
+/// more than half the samples equal the median and the MAD is zero
+const MEAN_AD_NORMAL_CONSISTENCY: f64 = 1.253314;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlierMethod {
+    /// Tukey fences at `Q1 - k * IQR` and `Q3 + k * IQR`
+    Tukey { mild: f64, severe: f64 },
+    /// Modified z-score `0.6745 * (x - median) / MAD` (Iglewicz & Hoaglin);
+    /// falls back to the mean absolute deviation when the MAD is zero, as
+    /// with quantized timers
+    Mad { mild: f64, severe: f64 },
+}
+
+impl OutlierMethod {
+    /// Conventional Tukey fences: mild beyond 1.5 IQR, severe beyond 3 IQR
+    pub const fn tukey() -> Self {
+        Self::Tukey { mild: 1.5, severe: 3.0 }
+    }
+
+    /// Modified z-score: mild beyond 3.5, severe beyond 7
+    pub const fn mad() -> Self {
+        Self::Mad { mild: 3.5, severe: 7.0 }
+    }
+}
+
+impl Default for OutlierMethod {
+    fn default() -> Self {
+        Self::tukey()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlierClass {
+    LowSevere,
+    LowMild,
+    Normal,
+    HighMild,
+    HighSevere,
+}
+
+/// Boundaries in nanoseconds between outlier classes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fences {
+    pub low_severe: f64,
+    pub low_mild: f64,
+    pub high_mild: f64,
+    pub high_severe: f64,
+}
+
+impl Fences {
+    pub fn classify(&self, value: u64) -> OutlierClass {
+        let value = value as f64;
+        if value < self.low_severe {
+            OutlierClass::LowSevere
+        } else if value < self.low_mild {
+            OutlierClass::LowMild
+        } else if value > self.high_severe {
+            OutlierClass::HighSevere
+        } else if value > self.high_mild {
+            OutlierClass::HighMild
+        } else {
+            OutlierClass::Normal
+        }
+    }
+}
+
+/// Outlier counts for one run
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlierReport {
+    pub method: OutlierMethod,
+    pub fences: Fences,
+    pub low_severe: usize,
+    pub low_mild: usize,
+    pub high_mild: usize,
+    pub high_severe: usize,
+    /// Total samples classified
+    pub count: usize,
+}
+
+impl OutlierReport {
+    pub fn total(&self) -> usize {
+        self.low_severe + self.low_mild + self.high_mild + self.high_severe
+    }
+
+    /// Fraction of samples classified as any kind of outlier
+    pub fn fraction(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.total() as f64 / self.count as f64 }
+    }
+}
+
+pub(crate) fn fences_from_sorted(sorted: &[u64], method: OutlierMethod) -> Fences {
+    match method {
+        OutlierMethod::Tukey { mild, severe } => {
+            let q1 = percentile_with(sorted, 25.0, PercentileMethod::Linear);
+            let q3 = percentile_with(sorted, 75.0, PercentileMethod::Linear);
+            tukey_fences(q1, q3, mild, severe)
+        }
+        OutlierMethod::Mad { mild, severe } => {
+            let median = percentile_with(sorted, 50.0, PercentileMethod::Linear);
+            let mut deviations: Vec<u64> = sorted.iter()
+                .map(|&x| (x as f64 - median).abs().round() as u64)
+                .collect();
+            deviations.sort_unstable();
+            let mad = percentile_with(&deviations, 50.0, PercentileMethod::Linear);
+            let mean_ad = || sorted.iter().map(|&x| (x as f64 - median).abs()).sum::<f64>() / sorted.len() as f64;
+            mad_fences(median, mad, mean_ad, mild, severe)
+        }
+    }
+}
+
+pub(crate) fn fences_from_histogram(histogram: &Histogram, method: OutlierMethod) -> Fences {
+    match method {
+        OutlierMethod::Tukey { mild, severe } => {
+            let q1 = histogram.value_at_percentile(25.0) as f64;
+            let q3 = histogram.value_at_percentile(75.0) as f64;
+            tukey_fences(q1, q3, mild, severe)
+        }
+        OutlierMethod::Mad { mild, severe } => {
+            let median = histogram.value_at_percentile(50.0) as f64;
+            let mut deviations: Vec<(f64, u64)> = histogram.recorded_buckets()
+                .map(|(value, count)| ((value as f64 - median).abs(), count))
+                .collect();
+            deviations.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+            let half = histogram.len().div_ceil(2);
+            let mut cumulative = 0;
+            let mad = deviations.iter()
+                .find(|(_, count)| {
+                    cumulative += count;
+                    cumulative >= half
+                })
+                .map(|&(deviation, _)| deviation)
+                .unwrap_or(0.0);
+            let mean_ad = || {
+                deviations.iter().map(|&(deviation, count)| deviation * count as f64).sum::<f64>()
+                    / histogram.len() as f64
+            };
+            mad_fences(median, mad, mean_ad, mild, severe)
+        }
+    }
+}
+
+/// Count outliers among `(value, count)` pairs
+pub(crate) fn classify<I>(values: I, method: OutlierMethod, fences: Fences) -> OutlierReport
+where
+    I: IntoIterator<Item = (u64, u64)>,
+{
+    let mut report = OutlierReport {
+        method,
+        fences,
+        low_severe: 0,
+        low_mild: 0,
+        high_mild: 0,
+        high_severe: 0,
+        count: 0,
+    };
+
+    for (value, count) in values {
+        let count = count as usize;
+        report.count += count;
+        match fences.classify(value) {
+            OutlierClass::LowSevere => report.low_severe += count,
+            OutlierClass::LowMild => report.low_mild += count,
+            OutlierClass::HighMild => report.high_mild += count,
+            OutlierClass::HighSevere => report.high_severe += count,
+            OutlierClass::Normal => {}
+        }
+    }
+
+    report
+}
+
+fn tukey_fences(q1: f64, q3: f64, mild: f64, severe: f64) -> Fences {
+    let iqr = q3 - q1;
+    Fences {
+        low_severe: q1 - severe * iqr,
+        low_mild: q1 - mild * iqr,
+        high_mild: q3 + mild * iqr,
+        high_severe: q3 + severe * iqr,
+    }
+}
+
+fn mad_fences(median: f64, mad: f64, mean_ad: impl FnOnce() -> f64, mild: f64, severe: f64) -> Fences {
+    let scale = if mad > 0.0 { mad / MAD_NORMAL_CONSISTENCY } else { mean_ad() * MEAN_AD_NORMAL_CONSISTENCY };
+    Fences {
+        low_severe: median - severe * scale,
+        low_mild: median - mild * scale,
+        high_mild: median + mild * scale,
+        high_severe: median + severe * scale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_with_spikes() -> Vec<u64> {
+        let mut data: Vec<u64> = (0..1000).map(|i| 100 + i % 20).collect();
+        data.extend([10, 150, 5_000, 20_000]);
+        data.sort_unstable();
+        data
+    }
+
+    #[test]
+    fn test_tukey_classification() {
+        let data = sample_with_spikes();
+        let method = OutlierMethod::tukey();
+        let fences = fences_from_sorted(&data, method);
+        let report = classify(data.iter().map(|&v| (v, 1)), method, fences);
+
+        assert_eq!(report.count, 1004);
+        assert_eq!(report.low_severe, 1);
+        assert_eq!(report.high_mild, 0);
+        assert_eq!(report.high_severe, 3);
+        assert_eq!(report.total(), 4);
+        assert_eq!(fences.classify(110), OutlierClass::Normal);
+    }
+
+    #[test]
+    fn test_mad_classification() {
+        let data = sample_with_spikes();
+        let method = OutlierMethod::mad();
+        let fences = fences_from_sorted(&data, method);
+        let report = classify(data.iter().map(|&v| (v, 1)), method, fences);
+
+        assert_eq!(report.low_severe, 1);
+        assert_eq!(report.high_mild, 1);
+        assert_eq!(report.high_severe, 2);
+        assert!(report.fraction() < 0.01);
+    }
+
+    #[test]
+    fn test_histogram_fences_match_sorted() {
+        let data = sample_with_spikes();
+        let mut histogram = Histogram::new(3, 1_000_000);
+        for &v in &data {
+            histogram.record(v);
+        }
+
+        for method in [OutlierMethod::tukey(), OutlierMethod::mad()] {
+            let exact = classify(data.iter().map(|&v| (v, 1)), method, fences_from_sorted(&data, method));
+            let fences = fences_from_histogram(&histogram, method);
+            let report = classify(histogram.recorded_buckets(), method, fences);
+            assert_eq!(report.total(), exact.total());
+            assert_eq!(report.high_severe, exact.high_severe);
+        }
+    }
+
+    #[test]
+    fn test_mad_falls_back_when_zero() {
+        // A 41ns tick: most samples land on one value, the rest one tick away
+        let mut data: Vec<u64> = (0..1000u64).map(|i| if i.is_multiple_of(5) { 82 } else { 41 }).collect();
+        data.push(4_100);
+        data.sort_unstable();
+        let method = OutlierMethod::mad();
+
+        let fences = fences_from_sorted(&data, method);
+        let report = classify(data.iter().map(|&v| (v, 1)), method, fences);
+        assert_eq!(fences.classify(82), OutlierClass::Normal);
+        assert_eq!(report.high_severe, 1);
+        assert_eq!(report.total(), 1);
+
+        let mut histogram = Histogram::new(3, 1_000_000);
+        for &v in &data {
+            histogram.record(v);
+        }
+        let report = classify(histogram.recorded_buckets(), method, fences_from_histogram(&histogram, method));
+        assert_eq!(report.total(), 1);
+    }
+}
@@ -0,0 +1,378 @@
+//! Hardware performance counters through Linux `perf_event_open`
+//!
+//! Enabled with the `perf` cargo feature. Counters are opened as one group
+//! on the calling thread, counting user space only so they work at the
+//! default `perf_event_paranoid` level. Events the kernel or hypervisor
+//! refuses are listed in `PerfCounters::unavailable` instead of failing the
+//! benchmark. When the kernel multiplexes the group with other perf users,
+//! interval counts are scaled by the fraction of time it was scheduled.
+
+use std::io;
+
+use crate::clock::Clock;
+use crate::stats::{CounterReport, CounterSummary};
+use crate::timing::PrecisionTimer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HardwareEvent {
+    Instructions,
+    Cycles,
+    CacheMisses,
+    BranchMisses,
+    /// Data TLB read misses
+    DtlbMisses,
+}
+
+impl HardwareEvent {
+    pub const ALL: [HardwareEvent; 5] = [
+        HardwareEvent::Instructions,
+        HardwareEvent::Cycles,
+        HardwareEvent::CacheMisses,
+        HardwareEvent::BranchMisses,
+        HardwareEvent::DtlbMisses,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HardwareEvent::Instructions => "instructions",
+            HardwareEvent::Cycles => "cycles",
+            HardwareEvent::CacheMisses => "cache-misses",
+            HardwareEvent::BranchMisses => "branch-misses",
+            HardwareEvent::DtlbMisses => "dTLB-misses",
+        }
+    }
+
+    /// `perf_event_attr` type and config
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn type_and_config(self) -> (u32, u64) {
+        const PERF_TYPE_HARDWARE: u32 = 0;
+        const PERF_TYPE_HW_CACHE: u32 = 3;
+        const PERF_COUNT_HW_CACHE_DTLB: u64 = 3;
+        const PERF_COUNT_HW_CACHE_OP_READ: u64 = 0;
+        const PERF_COUNT_HW_CACHE_RESULT_MISS: u64 = 1;
+
+        match self {
+            HardwareEvent::Cycles => (PERF_TYPE_HARDWARE, 0),
+            HardwareEvent::Instructions => (PERF_TYPE_HARDWARE, 1),
+            HardwareEvent::CacheMisses => (PERF_TYPE_HARDWARE, 3),
+            HardwareEvent::BranchMisses => (PERF_TYPE_HARDWARE, 5),
+            HardwareEvent::DtlbMisses => (
+                PERF_TYPE_HW_CACHE,
+                PERF_COUNT_HW_CACHE_DTLB | PERF_COUNT_HW_CACHE_OP_READ << 8 | PERF_COUNT_HW_CACHE_RESULT_MISS << 16,
+            ),
+        }
+    }
+}
+
+/// Counter readings, or differences between two readings
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CounterValues {
+    values: Vec<(HardwareEvent, u64)>,
+    /// Nanoseconds the group was enabled
+    time_enabled: u64,
+    /// Nanoseconds the group was actually scheduled on the PMU
+    time_running: u64,
+}
+
+impl CounterValues {
+    pub fn get(&self, event: HardwareEvent) -> Option<u64> {
+        self.values.iter().find(|(e, _)| *e == event).map(|&(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (HardwareEvent, u64)> + '_ {
+        self.values.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Counts between `earlier` and this reading
+    ///
+    /// If the group was multiplexed for part of the interval the counts are
+    /// scaled up by `time_enabled / time_running`; `None` if it never ran.
+    pub fn since(&self, earlier: &CounterValues) -> Option<CounterValues> {
+        let time_enabled = self.time_enabled.wrapping_sub(earlier.time_enabled);
+        let time_running = self.time_running.wrapping_sub(earlier.time_running);
+        if time_running == 0 && time_enabled > 0 {
+            return None;
+        }
+        let scale = |count: u64| {
+            if time_running < time_enabled {
+                (count as u128 * time_enabled as u128 / time_running as u128) as u64
+            } else {
+                count
+            }
+        };
+        Some(CounterValues {
+            values: self.values.iter().zip(&earlier.values)
+                .map(|(&(event, now), &(_, then))| (event, scale(now.wrapping_sub(then))))
+                .collect(),
+            time_enabled,
+            time_running,
+        })
+    }
+}
+
+/// A group of counters on the calling thread
+pub struct PerfCounters {
+    /// Group leader first; reads return members in this order
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fds: Vec<std::os::fd::OwnedFd>,
+    events: Vec<HardwareEvent>,
+    unavailable: Vec<(HardwareEvent, String)>,
+}
+
+impl PerfCounters {
+    /// Open every `HardwareEvent`
+    pub fn open_all() -> Self {
+        Self::open(&HardwareEvent::ALL)
+    }
+
+    /// Open `events` for the calling thread; events that cannot be opened
+    /// are recorded with the reason rather than returned as an error
+    pub fn open(events: &[HardwareEvent]) -> Self {
+        let mut counters = Self { fds: Vec::new(), events: Vec::new(), unavailable: Vec::new() };
+        for &event in events {
+            if counters.events.contains(&event) {
+                continue;
+            }
+            match counters.open_event(event) {
+                Ok(fd) => {
+                    counters.fds.push(fd);
+                    counters.events.push(event);
+                }
+                Err(e) => counters.unavailable.push((event, e.to_string())),
+            }
+        }
+        counters
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_event(&self, event: HardwareEvent) -> io::Result<std::os::fd::OwnedFd> {
+        use std::os::fd::{AsRawFd, FromRawFd};
+
+        /// The first 64 bytes of `struct perf_event_attr` (PERF_ATTR_SIZE_VER0)
+        #[repr(C)]
+        #[derive(Default)]
+        struct PerfEventAttr {
+            type_: u32,
+            size: u32,
+            config: u64,
+            sample_period: u64,
+            sample_type: u64,
+            read_format: u64,
+            flags: u64,
+            wakeup_events: u32,
+            bp_type: u32,
+            config1: u64,
+        }
+
+        const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+        const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+        const PERF_FORMAT_GROUP: u64 = 1 << 3;
+        const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+        const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+        const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+        let (type_, config) = event.type_and_config();
+        let attr = PerfEventAttr {
+            type_,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config,
+            read_format: PERF_FORMAT_GROUP | PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+            flags: FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+            ..Default::default()
+        };
+        let group = self.fds.first().map_or(-1, |leader| leader.as_raw_fd());
+
+        unsafe {
+            let fd = libc::syscall(
+                libc::SYS_perf_event_open, &attr as *const PerfEventAttr, 0, -1, group, PERF_FLAG_FD_CLOEXEC,
+            ) as libc::c_int;
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(std::os::fd::OwnedFd::from_raw_fd(fd))
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn open_event(&self, _event: HardwareEvent) -> io::Result<std::os::fd::OwnedFd> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "perf_event_open is only supported on Linux"))
+    }
+
+    /// Whether any counter could be opened
+    pub fn is_available(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    /// Events being counted
+    pub fn events(&self) -> &[HardwareEvent] {
+        &self.events
+    }
+
+    /// Events that could not be opened, with the reason
+    pub fn unavailable(&self) -> &[(HardwareEvent, String)] {
+        &self.unavailable
+    }
+
+    /// Current value of every counter; `None` if nothing is counted or the
+    /// read fails
+    #[cfg(target_os = "linux")]
+    pub fn read(&self) -> Option<CounterValues> {
+        use std::os::fd::AsRawFd;
+
+        let leader = self.fds.first()?;
+        // PERF_FORMAT_GROUP layout: nr, time_enabled, time_running, then one
+        // value per member
+        let mut buffer = [0u64; 3 + HardwareEvent::ALL.len()];
+        let len = (3 + self.events.len()) * std::mem::size_of::<u64>();
+        let read = unsafe { libc::read(leader.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, len) };
+        if read != len as isize || buffer[0] as usize != self.events.len() {
+            return None;
+        }
+        Some(CounterValues {
+            values: self.events.iter().copied().zip(buffer[3..].iter().copied()).collect(),
+            time_enabled: buffer[1],
+            time_running: buffer[2],
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn read(&self) -> Option<CounterValues> {
+        None
+    }
+
+    /// Run `f` and return the counts it incurred
+    pub fn measure<F, R>(&self, f: F) -> (R, Option<CounterValues>)
+    where
+        F: FnOnce() -> R,
+    {
+        let before = self.read();
+        let result = f();
+        let after = self.read();
+        (result, before.zip(after).and_then(|(before, after)| after.since(&before)))
+    }
+}
+
+/// A `PrecisionTimer` that also reads hardware counters around the interval
+///
+/// The counters are read outside the timed region, so they add no latency
+/// to the measurement but do include the timer's own instructions.
+pub struct PerfTimer<'a> {
+    counters: &'a PerfCounters,
+    before: Option<CounterValues>,
+    timer: PrecisionTimer,
+}
+
+impl<'a> PerfTimer<'a> {
+    pub fn start(counters: &'a PerfCounters) -> Self {
+        let before = counters.read();
+        Self { counters, before, timer: PrecisionTimer::start() }
+    }
+
+    pub fn start_on(counters: &'a PerfCounters, clock: &Clock) -> Self {
+        let before = counters.read();
+        Self { counters, before, timer: PrecisionTimer::start_on(clock) }
+    }
+
+    /// Elapsed nanoseconds and the counts over the interval, if readable
+    pub fn stop(self) -> (u64, Option<CounterValues>) {
+        let elapsed = self.timer.stop();
+        let after = self.counters.read();
+        (elapsed, self.before.zip(after).and_then(|(before, after)| after.since(&before)))
+    }
+}
+
+/// Per-sample counts collected alongside `SimpleBench` samples
+pub(crate) struct CounterRecorder {
+    counters: PerfCounters,
+    samples: Vec<Vec<f64>>,
+}
+
+impl CounterRecorder {
+    pub(crate) fn new(counters: PerfCounters) -> Self {
+        let samples = vec![Vec::new(); counters.events.len()];
+        Self { counters, samples }
+    }
+
+    pub(crate) fn read(&self) -> Option<CounterValues> {
+        self.counters.read()
+    }
+
+    /// Record the counts since `before`, divided by the calls per sample
+    pub(crate) fn record_since(&mut self, before: Option<CounterValues>, calls: u64) {
+        let Some(delta) = before.zip(self.counters.read()).and_then(|(before, after)| after.since(&before)) else {
+            return;
+        };
+        for (samples, (_, count)) in self.samples.iter_mut().zip(delta.iter()) {
+            samples.push(count as f64 / calls.max(1) as f64);
+        }
+    }
+
+    pub(crate) fn report(self) -> CounterReport {
+        CounterReport {
+            counters: self.counters.events.iter().zip(self.samples)
+                .map(|(event, samples)| CounterSummary::from_samples(event.name(), samples))
+                .collect(),
+            unavailable: self.counters.unavailable.iter()
+                .map(|(event, reason)| format!("{} ({})", event.name(), reason))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_values_delta() {
+        let before = CounterValues {
+            values: vec![(HardwareEvent::Instructions, 100), (HardwareEvent::CacheMisses, 7)],
+            time_enabled: 1_000,
+            time_running: 1_000,
+        };
+        let after = CounterValues {
+            values: vec![(HardwareEvent::Instructions, 1_100), (HardwareEvent::CacheMisses, 9)],
+            time_enabled: 2_000,
+            time_running: 2_000,
+        };
+        let delta = after.since(&before).unwrap();
+        assert_eq!(delta.get(HardwareEvent::Instructions), Some(1_000));
+        assert_eq!(delta.get(HardwareEvent::CacheMisses), Some(2));
+        assert_eq!(delta.get(HardwareEvent::DtlbMisses), None);
+    }
+
+    #[test]
+    fn test_multiplexed_counts_are_scaled() {
+        let before = CounterValues { values: vec![(HardwareEvent::Cycles, 0)], time_enabled: 0, time_running: 0 };
+        let half = CounterValues { values: vec![(HardwareEvent::Cycles, 500)], time_enabled: 1_000, time_running: 500 };
+        assert_eq!(half.since(&before).unwrap().get(HardwareEvent::Cycles), Some(1_000));
+
+        // Descheduled for the whole interval: the counts are unknown
+        let idle = CounterValues { values: vec![(HardwareEvent::Cycles, 500)], time_enabled: 3_000, time_running: 500 };
+        assert_eq!(idle.since(&half), None);
+    }
+
+    #[test]
+    fn test_open_degrades_gracefully() {
+        let counters = PerfCounters::open_all();
+        assert_eq!(counters.events().len() + counters.unavailable().len(), HardwareEvent::ALL.len());
+
+        let (sum, counts) = counters.measure(|| (0..1000u64).map(std::hint::black_box).sum::<u64>());
+        assert_eq!(sum, 499_500);
+        match counts {
+            Some(counts) => {
+                if let Some(instructions) = counts.get(HardwareEvent::Instructions) {
+                    assert!(instructions >= 1000, "{instructions} instructions for 1000 iterations");
+                }
+            }
+            None => assert!(!counters.is_available()),
+        }
+
+        let (elapsed, _) = PerfTimer::start(&counters).stop();
+        assert!(elapsed < 10_000_000);
+    }
+}
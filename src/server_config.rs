@@ -2,6 +2,9 @@
 
 use std::time::Duration;
 
+use crate::topology::Topology;
+pub use crate::topology::{parse_cpu_list, parse_cpu_mask};
+
 /// Configure Criterion for server benchmarking with high precision
 pub fn configure_for_server_memory_benchmarks(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>) {
     // Server-specific configuration for memory benchmarks
@@ -53,20 +56,17 @@ pub fn configure_for_server_cpu_benchmarks(group: &mut criterion::BenchmarkGroup
 pub fn prewarm_server_subsystems() {
     println!("🔥 Pre-warming server subsystems...");
     
-    // Warm up allocators across NUMA nodes
-    for node in 0..get_numa_node_count() {
-        if let Ok(_) = std::process::Command::new("numactl")
-            .args([&format!("--cpunodebind={node}"), "--membind", &node.to_string()])
-            .args(["echo", "warming", "node"])
-            .output()
-        {
-            // Allocate memory on this NUMA node
-            let sizes = [1024, 4096, 16384, 65536];
-            for &size in &sizes {
-                let vec = vec![0u8; size];
-                std::hint::black_box(&vec);
-                drop(vec);
-            }
+    // Warm up allocators on every NUMA node: run on the node's CPUs so
+    // first-touch places the pages in its local memory
+    if let Ok(topology) = Topology::detect() {
+        for node in &topology.nodes {
+            let warm = || {
+                for &size in &[1024, 4096, 16384, 65536] {
+                    let vec = vec![1u8; size];
+                    std::hint::black_box(&vec);
+                }
+            };
+            let _ = crate::affinity::run_on_cpus(&node.cpus, warm);
         }
     }
     
@@ -139,13 +139,180 @@ pub fn check_server_environment() -> ServerEnvironment {
     if numa_nodes > 1 {
         info.insert("numa_topology".to_string(), "detected".to_string());
     }
+    if let Ok(topology) = Topology::detect() {
+        info.insert("physical_cores".to_string(), topology.physical_cores().len().to_string());
+        info.insert("llc_groups".to_string(), topology.llc_groups().len().to_string());
+    }
+    
+    // Check for cores reserved for latency-critical work
+    let isolation = detect_core_isolation();
+    info.insert("isolated_cpus".to_string(), format_cpu_list(&isolation.isolated));
+    info.insert("nohz_full_cpus".to_string(), format_cpu_list(&isolation.nohz_full));
+    info.insert("rcu_nocbs_cpus".to_string(), format_cpu_list(&isolation.rcu_nocbs));
+    info.insert("quiet_cpus".to_string(), format_cpu_list(&isolation.quiet_cpus()));
+    if let Some(cpu) = isolation.recommended_cpu() {
+        info.insert("recommended_cpu".to_string(), cpu.to_string());
+    }
+    if isolation.isolated.is_empty() {
+        warnings.push("No isolated cores (isolcpus=); benchmarks share cores with the OS".to_string());
+    } else if isolation.quiet_cpus().is_empty() {
+        warnings.push("Isolated cores still take timer ticks, RCU callbacks or IRQs".to_string());
+    }
     
     ServerEnvironment {
         is_optimal: issues.is_empty(),
         issues,
         warnings,
         info,
+        isolation,
+    }
+}
+
+/// Which cores are shielded from the kernel's own work
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CoreIsolation {
+    pub online: Vec<usize>,
+    /// Removed from the scheduler's load balancing (`isolcpus=`)
+    pub isolated: Vec<usize>,
+    /// Running without the periodic timer tick (`nohz_full=`)
+    pub nohz_full: Vec<usize>,
+    /// RCU callbacks offloaded elsewhere (`rcu_nocbs=`, implied by `nohz_full=`)
+    pub rcu_nocbs: Vec<usize>,
+    /// Number of requested IRQs routed to each online CPU
+    pub irq_counts: std::collections::BTreeMap<usize, usize>,
+}
+
+impl CoreIsolation {
+    /// Build from the contents of the sysfs/procfs files, for testing and
+    /// for inspecting another machine's dumps
+    ///
+    /// The kernel exposes no file for offloaded RCU callbacks, so they come
+    /// from the command line. A bare `rcu_nocbs` offloads nothing at boot;
+    /// `nohz_full` cores are always offloaded.
+    pub fn from_sources<'a>(
+        online: &str,
+        isolated: &str,
+        nohz_full: &str,
+        cmdline: &str,
+        irq_affinities: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let online = parse_cpu_list(online);
+        let mut irq_counts: std::collections::BTreeMap<usize, usize> = online.iter().map(|&cpu| (cpu, 0)).collect();
+        for mask in irq_affinities {
+            for cpu in parse_cpu_mask(mask) {
+                if let Some(count) = irq_counts.get_mut(&cpu) {
+                    *count += 1;
+                }
+            }
+        }
+        // Arguments after `--` belong to init, not the kernel
+        let mut rcu_nocbs = cmdline.split_whitespace()
+            .take_while(|&arg| arg != "--")
+            .filter_map(|arg| arg.strip_prefix("rcu_nocbs="))
+            .last()
+            .map(|list| parse_kernel_cpu_list(list, &online))
+            .unwrap_or_default();
+        let nohz_full = parse_cpu_list(nohz_full);
+        rcu_nocbs.extend(&nohz_full);
+        rcu_nocbs.sort_unstable();
+        rcu_nocbs.dedup();
+        
+        Self {
+            online,
+            isolated: parse_cpu_list(isolated),
+            nohz_full,
+            rcu_nocbs,
+            irq_counts,
+        }
+    }
+    
+    /// Isolated, tickless, RCU-offloaded cores that no IRQ is routed to
+    pub fn quiet_cpus(&self) -> Vec<usize> {
+        self.online.iter().copied()
+            .filter(|&cpu| self.quietness(cpu) == (true, true, true, 0))
+            .collect()
+    }
+    
+    /// The online core with the least kernel interference
+    ///
+    /// Isolation counts most, then no tick, then offloaded RCU, then the
+    /// fewest IRQs. CPU 0 handles most housekeeping, so ties go to the
+    /// highest-numbered core.
+    pub fn recommended_cpu(&self) -> Option<usize> {
+        self.online.iter().copied()
+            .max_by_key(|&cpu| {
+                let (isolated, tickless, rcu_offloaded, irqs) = self.quietness(cpu);
+                (isolated, tickless, rcu_offloaded, std::cmp::Reverse(irqs), cpu)
+            })
+    }
+    
+    fn quietness(&self, cpu: usize) -> (bool, bool, bool, usize) {
+        (
+            self.isolated.contains(&cpu),
+            self.nohz_full.contains(&cpu),
+            self.rcu_nocbs.contains(&cpu),
+            self.irq_counts.get(&cpu).copied().unwrap_or(0),
+        )
+    }
+}
+
+/// Parse a boot-parameter CPU list, which may also say `all` or use `N`
+/// for the last CPU
+fn parse_kernel_cpu_list(list: &str, online: &[usize]) -> Vec<usize> {
+    let last = online.last().copied().unwrap_or(0);
+    if list == "all" {
+        return online.to_vec();
     }
+    parse_cpu_list(&list.replace('N', &last.to_string()))
+}
+
+/// Read isolation settings from the running kernel
+///
+/// Missing files (e.g. no `nohz_full` support) read as empty.
+pub fn detect_core_isolation() -> CoreIsolation {
+    let read = |path: &str| std::fs::read_to_string(path).unwrap_or_default();
+    let irq_affinities: Vec<String> = std::fs::read_dir("/proc/irq")
+        .map(|entries| {
+            entries.flatten()
+                .filter_map(|entry| read_irq_affinity(&entry.path()))
+                .collect()
+        })
+        .unwrap_or_default();
+    
+    CoreIsolation::from_sources(
+        &read("/sys/devices/system/cpu/online"),
+        &read("/sys/devices/system/cpu/isolated"),
+        &read("/sys/devices/system/cpu/nohz_full"),
+        &read("/proc/cmdline"),
+        irq_affinities.iter().map(String::as_str),
+    )
+}
+
+/// CPUs an IRQ is actually delivered to, or `None` if no driver has
+/// requested it
+///
+/// `smp_affinity` is only the requested mask; the interrupt controller
+/// usually targets a single CPU from it, which `effective_affinity` reports.
+/// Older kernels lack that file, so fall back to the requested mask. IRQs
+/// without a handler (no `/proc/irq/N/<name>` directory) still carry the
+/// default all-CPU mask there and would count against every core.
+fn read_irq_affinity(irq: &std::path::Path) -> Option<String> {
+    let has_handler = std::fs::read_dir(irq).ok()?
+        .flatten()
+        .any(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()));
+    if !has_handler {
+        return None;
+    }
+    std::fs::read_to_string(irq.join("effective_affinity"))
+        .or_else(|_| std::fs::read_to_string(irq.join("smp_affinity")))
+        .ok()
+}
+
+fn format_cpu_list(cpus: &[usize]) -> String {
+    if cpus.is_empty() {
+        return "none".to_string();
+    }
+    cpus.iter().map(|cpu| cpu.to_string()).collect::<Vec<_>>().join(",")
 }
 
 /// Server environment assessment
@@ -154,6 +321,7 @@ pub struct ServerEnvironment {
     pub issues: Vec<String>,
     pub warnings: Vec<String>,
     pub info: std::collections::HashMap<String, String>,
+    pub isolation: CoreIsolation,
 }
 
 impl ServerEnvironment {
@@ -195,7 +363,15 @@ impl ServerEnvironment {
             println!("   - Ensure bare metal (no virtualization)");
             println!("   - Set CPU governor to 'performance'");
             println!("   - Stop non-essential services");
-            println!("   - Use CPU isolation and NUMA binding");
+            println!("   - Use CPU isolation and NUMA binding (SimpleBench::pinned / run_pinned)");
+        }
+        
+        if let Some(cpu) = self.isolation.recommended_cpu() {
+            let quiet = self.isolation.quiet_cpus().contains(&cpu);
+            println!(
+                "\n🎯 Best core for benchmarks: cpu{cpu}{} (SimpleBench::pinned(Pinning::cpu({cpu})))",
+                if quiet { ", fully quiet" } else { "" }
+            );
         }
         
         println!("=======================================");
@@ -257,19 +433,57 @@ fn get_load_average() -> f64 {
 }
 
 fn get_numa_node_count() -> usize {
-    std::process::Command::new("numactl")
-        .args(["--hardware"])
-        .output()
-        .ok()
-        .and_then(|output| {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            output_str.lines()
-                .find(|line| line.contains("available:"))
-                .and_then(|line| {
-                    line.split_whitespace()
-                        .nth(1)
-                        .and_then(|s| s.parse().ok())
-                })
-        })
-        .unwrap_or(1)
-}
\ No newline at end of file
+    Topology::detect().map_or(1, |topology| topology.node_count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+    
+    #[test]
+    fn test_quiet_core_selection() {
+        // cpus 2-3 isolated and tickless, so RCU-offloaded too, IRQs on both
+        let isolation = CoreIsolation::from_sources(
+            "0-3",
+            "2-3",
+            "2-3",
+            "quiet isolcpus=2-3 nohz_full=2-3 rcu_nocbs=3",
+            ["3", "1", "4", "f"],
+        );
+        assert_eq!(isolation.rcu_nocbs, vec![2, 3]);
+        assert_eq!(isolation.irq_counts[&0], 3);
+        assert_eq!(isolation.irq_counts[&2], 2);
+        assert_eq!(isolation.irq_counts[&3], 1);
+        assert_eq!(isolation.quiet_cpus(), Vec::<usize>::new());
+        assert_eq!(isolation.recommended_cpu(), Some(3));
+        
+        let all = CoreIsolation::from_sources("0-3", "", "", "rcu_nocbs=all", ["1"]);
+        assert_eq!(all.rcu_nocbs, vec![0, 1, 2, 3]);
+        let tail = CoreIsolation::from_sources("0-3", "", "", "rcu_nocbs=2-N -- rcu_nocbs=0", ["1"]);
+        assert_eq!(tail.rcu_nocbs, vec![2, 3]);
+        let strided = CoreIsolation::from_sources("0-7", "", "", "rcu_nocbs=1-N:1/2", ["1"]);
+        assert_eq!(strided.rcu_nocbs, vec![1, 3, 5, 7]);
+        let bare = CoreIsolation::from_sources("0-3", "", "3", "rcu_nocbs nohz_full=3", ["1"]);
+        assert_eq!(bare.rcu_nocbs, vec![3]);
+        
+        let plain = CoreIsolation::from_sources("0-3", "", "", "", ["f"]);
+        assert_eq!(plain.recommended_cpu(), Some(3));
+        #[cfg(target_os = "linux")]
+        assert!(!detect_core_isolation().online.is_empty());
+    }
+    
+    #[test]
+    fn test_irq_affinity_prefers_effective_mask() {
+        let irq = TempPath::new("irq");
+        std::fs::create_dir_all(&irq).unwrap();
+        std::fs::write(irq.join("smp_affinity"), "f\n").unwrap();
+        assert_eq!(read_irq_affinity(&irq), None);
+        
+        std::fs::create_dir_all(irq.join("eth0-rx-0")).unwrap();
+        assert_eq!(read_irq_affinity(&irq).as_deref(), Some("f\n"));
+        
+        std::fs::write(irq.join("effective_affinity"), "4\n").unwrap();
+        assert_eq!(read_irq_affinity(&irq).as_deref(), Some("4\n"));
+    }
+}
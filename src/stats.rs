@@ -3,38 +3,424 @@
 extern crate alloc;
 use alloc::vec::Vec;
 use alloc::string::String;
+use crate::histogram::Histogram;
+use crate::disturbance::Disturbance;
+use crate::warmup::WarmupReport;
+use crate::affinity::{Pinning, SchedulingPolicy};
+use crate::confidence::{self, ConfidenceConfig, ConfidenceIntervals, Interval};
+use crate::outliers::{self, OutlierClass, OutlierMethod, OutlierReport};
+
+/// Optional extras computed by `BenchmarkResults::analyze_with`
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisOptions {
+    /// Compute confidence intervals for the mean and every percentile
+    pub confidence: Option<ConfidenceConfig>,
+    /// Estimator for all percentiles of raw results; histogram results
+    /// always use the histogram's bucket boundaries
+    pub percentile_method: PercentileMethod,
+    /// Additional percentiles (0-100) reported in `BenchmarkAnalysis::percentiles`
+    pub percentiles: Vec<f64>,
+    /// Classify samples into `BenchmarkAnalysis::outliers` this way; off by
+    /// default
+    pub outlier_method: Option<OutlierMethod>,
+    /// Also analyze the samples with every outlier removed (classifying
+    /// with Tukey fences if no method is set)
+    pub trim_outliers: bool,
+    /// Timer resolution used to flag analyses too fast to measure
+    pub resolution_ns: Option<u64>,
+}
+
+impl AnalysisOptions {
+    pub fn with_confidence(mut self, config: ConfidenceConfig) -> Self {
+        self.confidence = Some(config);
+        self
+    }
+    
+    pub fn with_percentile_method(mut self, method: PercentileMethod) -> Self {
+        self.percentile_method = method;
+        self
+    }
+    
+    pub fn with_percentiles(mut self, percentiles: &[f64]) -> Self {
+        self.percentiles = percentiles.to_vec();
+        self
+    }
+    
+    pub fn with_outlier_method(mut self, method: OutlierMethod) -> Self {
+        self.outlier_method = Some(method);
+        self
+    }
+    
+    pub fn with_resolution_ns(mut self, resolution_ns: u64) -> Self {
+        self.resolution_ns = Some(resolution_ns);
+        self
+    }
+    
+    /// Attach a second analysis, `BenchmarkAnalysis::trimmed`, computed
+    /// without the samples classified as outliers
+    pub fn with_trimmed(mut self) -> Self {
+        self.trim_outliers = true;
+        self
+    }
+    
+    /// Method to classify outliers with, if they are classified at all
+    fn outlier_classification(&self) -> Option<OutlierMethod> {
+        self.outlier_method.or_else(|| self.trim_outliers.then(OutlierMethod::default))
+    }
+}
+
+/// Sample quantile definition used to compute percentiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PercentileMethod {
+    /// Sample at index `round(p * (n - 1))`; the historical behaviour
+    #[default]
+    NearestRank,
+    /// Linear interpolation between closest ranks (Hyndman–Fan type 7)
+    Linear,
+    /// Hyndman–Fan sample quantile definition 1-9 (out-of-range values are clamped)
+    HyndmanFan(u8),
+}
+
+/// An arbitrary percentile requested through `AnalysisOptions::percentiles`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PercentileValue {
+    pub percentile: f64,
+    pub value: f64,
+}
+
+/// Most values one `record_corrected` call backfills
+pub const MAX_BACKFILL: u64 = 1 << 20;
+
+/// How recorded samples are stored
+enum Storage {
+    /// Every sample is kept; percentiles are exact
+    Raw(Vec<u64>),
+    /// Samples are bucketed into a fixed-size histogram; memory is bounded
+    Histogram(Histogram),
+}
 
 pub struct BenchmarkResults {
-    measurements: Vec<u64>,
+    storage: Storage,
     name: String,
+    /// Set when samples are raw counter ticks rather than nanoseconds
+    tick_frequency_khz: Option<u64>,
+    /// Samples from `record_with_disturbance`, split clean/disturbed
+    partition: Option<Box<Partition>>,
+    /// Set when each sample is the total of this many calls
+    batch_size: Option<u64>,
+    /// Values backfilled by `record_corrected` rather than measured
+    synthetic: u64,
+    /// The backfilled values themselves in raw mode, kept apart from the
+    /// measured samples
+    backfilled: Vec<u64>,
+}
+
+/// Samples split by whether the thread was disturbed while they were taken
+struct Partition {
+    clean: BenchmarkResults,
+    disturbed: BenchmarkResults,
+    totals: Disturbance,
 }
 
 impl BenchmarkResults {
     pub fn new(name: String) -> Self {
         Self {
-            measurements: Vec::with_capacity(10000),
+            storage: Storage::Raw(Vec::with_capacity(10000)),
             name,
+            tick_frequency_khz: None,
+            partition: None,
+            batch_size: None,
+            synthetic: 0,
+            backfilled: Vec::new(),
         }
     }
     
+    /// Record into an HDR histogram instead of a sample vector
+    ///
+    /// Memory stays fixed regardless of sample count, which suits multi-hour
+    /// soak runs. Percentiles are accurate to `significant_digits` and values
+    /// above `max_trackable_ns` are clamped.
+    pub fn with_histogram(name: String, significant_digits: u8, max_trackable_ns: u64) -> Self {
+        Self {
+            storage: Storage::Histogram(Histogram::new(significant_digits, max_trackable_ns)),
+            name,
+            tick_frequency_khz: None,
+            partition: None,
+            batch_size: None,
+            synthetic: 0,
+            backfilled: Vec::new(),
+        }
+    }
+    
+    /// Treat recorded values as counter ticks at `frequency_khz`
+    ///
+    /// Samples are stored unconverted and only turned into (fractional)
+    /// nanoseconds at analysis time; the analysis also keeps the statistics
+    /// in cycles. For histogram results the trackable maximum is in ticks.
+    pub fn in_cycles(mut self, frequency_khz: u64) -> Self {
+        self.tick_frequency_khz = Some(frequency_khz.max(1));
+        self
+    }
+    
+    /// Counter frequency when recording ticks
+    pub fn tick_frequency_khz(&self) -> Option<u64> {
+        self.tick_frequency_khz
+    }
+    
+    /// Treat recorded values as totals over `batch_size` calls
+    ///
+    /// The analysis divides by the batch size only after computing the
+    /// statistics, so per-call values below one nanosecond (or tick) keep
+    /// their precision in `BenchmarkAnalysis::per_call`.
+    pub fn per_call(mut self, batch_size: u64) -> Self {
+        self.batch_size = Some(batch_size.max(1));
+        self
+    }
+    
+    /// Calls per sample, when recording batch totals
+    pub fn batch_size(&self) -> Option<u64> {
+        self.batch_size
+    }
+    
+    #[inline(always)]
     pub fn record(&mut self, nanoseconds: u64) {
-        self.measurements.push(nanoseconds);
+        match &mut self.storage {
+            Storage::Raw(measurements) => measurements.push(nanoseconds),
+            Storage::Histogram(histogram) => histogram.record(nanoseconds),
+        }
+    }
+    
+    /// Record a sample along with the context switches and page faults
+    /// that happened while it was taken
+    ///
+    /// The analysis then compares samples with and without disturbances;
+    /// samples recorded with plain `record` are left out of that split.
+    pub fn record_with_disturbance(&mut self, nanoseconds: u64, disturbance: &Disturbance) {
+        self.record(nanoseconds);
+        let partition = match &mut self.partition {
+            Some(partition) => partition,
+            None => {
+                let partition = Partition {
+                    clean: self.empty_like(format!("{} (clean)", self.name)),
+                    disturbed: self.empty_like(format!("{} (disturbed)", self.name)),
+                    totals: Disturbance::default(),
+                };
+                self.partition.insert(Box::new(partition))
+            }
+        };
+        partition.totals.add(disturbance);
+        if disturbance.is_clean() {
+            partition.clean.record(nanoseconds);
+        } else {
+            partition.disturbed.record(nanoseconds);
+        }
+    }
+    
+    /// Empty results with the same storage and unit as these
+    fn empty_like(&self, name: String) -> BenchmarkResults {
+        let storage = match &self.storage {
+            Storage::Raw(_) => Storage::Raw(Vec::new()),
+            Storage::Histogram(h) => Storage::Histogram(Histogram::new(h.significant_digits(), h.highest_trackable())),
+        };
+        BenchmarkResults { storage, name, tick_frequency_khz: self.tick_frequency_khz, partition: None, batch_size: self.batch_size, synthetic: 0, backfilled: Vec::new() }
     }
     
     pub fn len(&self) -> usize {
-        self.measurements.len()
+        match &self.storage {
+            Storage::Raw(measurements) => measurements.len() + self.backfilled.len(),
+            Storage::Histogram(histogram) => histogram.len() as usize,
+        }
     }
     
     pub fn is_empty(&self) -> bool {
-        self.measurements.is_empty()
+        self.len() == 0
+    }
+    
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// Measured samples in recording order when recording in raw mode;
+    /// values backfilled by `record_corrected` are left out
+    pub fn samples(&self) -> Option<&[u64]> {
+        match &self.storage {
+            Storage::Raw(measurements) => Some(measurements),
+            Storage::Histogram(_) => None,
+        }
+    }
+    
+    /// Values backfilled by `record_corrected`; the histogram, `len` and the
+    /// analysis include them
+    pub fn synthetic_samples(&self) -> u64 {
+        self.synthetic
+    }
+    
+    /// Measured and backfilled raw values together
+    fn raw_values<'a>(&'a self, measurements: &'a [u64]) -> std::borrow::Cow<'a, [u64]> {
+        if self.backfilled.is_empty() {
+            std::borrow::Cow::Borrowed(measurements)
+        } else {
+            std::borrow::Cow::Owned([measurements, &self.backfilled].concat())
+        }
+    }
+    
+    /// Underlying histogram when recording in histogram mode
+    pub fn histogram(&self) -> Option<&Histogram> {
+        match &self.storage {
+            Storage::Raw(_) => None,
+            Storage::Histogram(histogram) => Some(histogram),
+        }
     }
     
     pub fn analyze(&self) -> BenchmarkAnalysis {
-        if self.measurements.is_empty() {
+        self.analyze_with(&AnalysisOptions::default())
+    }
+    
+    /// Compute arbitrary percentiles (0-100) with the given estimator
+    pub fn percentiles(&self, percentiles: &[f64], method: PercentileMethod) -> Vec<PercentileValue> {
+        match &self.storage {
+            Storage::Raw(measurements) => {
+                let mut sorted = self.raw_values(measurements).into_owned();
+                sorted.sort_unstable();
+                percentile_values(&sorted, percentiles, method)
+            }
+            Storage::Histogram(histogram) => histogram_percentile_values(histogram, percentiles),
+        }
+    }
+    
+    /// Distribution-free confidence interval for percentile `p` (0-100) at
+    /// `level`; `None` while there are too few samples to bound it
+    pub fn percentile_interval(&self, p: f64, level: f64) -> Option<Interval> {
+        match &self.storage {
+            Storage::Raw(measurements) => {
+                let mut sorted = self.raw_values(measurements).into_owned();
+                sorted.sort_unstable();
+                confidence::percentile_interval_sorted(&sorted, p, level)
+            }
+            Storage::Histogram(histogram) => confidence::percentile_interval_histogram(histogram, p, level),
+        }
+    }
+    
+    /// Classify every sample as a low/high, mild/severe outlier or normal
+    pub fn classify_outliers(&self, method: OutlierMethod) -> OutlierReport {
+        match &self.storage {
+            Storage::Raw(measurements) => {
+                let mut sorted = self.raw_values(measurements).into_owned();
+                sorted.sort_unstable();
+                let fences = outliers::fences_from_sorted(&sorted, method);
+                outliers::classify(sorted.iter().map(|&v| (v, 1)), method, fences)
+            }
+            Storage::Histogram(histogram) => {
+                let fences = outliers::fences_from_histogram(histogram, method);
+                outliers::classify(histogram.recorded_buckets(), method, fences)
+            }
+        }
+    }
+    
+    pub fn analyze_with(&self, options: &AnalysisOptions) -> BenchmarkAnalysis {
+        let analysis = match &self.storage {
+            Storage::Raw(measurements) => self.analyze_raw(&self.raw_values(measurements), options),
+            Storage::Histogram(histogram) => self.analyze_histogram(histogram, options),
+        };
+        let analysis = match self.tick_frequency_khz {
+            Some(_) => analysis.converted_from_cycles(),
+            None => analysis,
+        };
+        let mut analysis = match self.batch_size {
+            Some(batch) => analysis.divided_into_calls(batch),
+            None => analysis,
+        };
+        analysis.disturbances = self.partition.as_ref().map(|partition| DisturbanceReport {
+            clean_samples: partition.clean.len(),
+            disturbed_samples: partition.disturbed.len(),
+            totals: partition.totals,
+            clean: Box::new(partition.clean.analyze_with(options)),
+            disturbed: Box::new(partition.disturbed.analyze_with(options)),
+        });
+        if self.synthetic > 0 {
+            analysis.count -= self.synthetic as usize;
+            analysis.synthetic_samples = Some(self.synthetic);
+        }
+        analysis
+    }
+    
+    fn analyze_histogram(&self, histogram: &Histogram, options: &AnalysisOptions) -> BenchmarkAnalysis {
+        if histogram.is_empty() {
             return BenchmarkAnalysis::empty(self.name.clone());
         }
         
-        let mut sorted = self.measurements.clone();
+        let outlier_method = options.outlier_classification();
+        let fences = outlier_method.map(|method| outliers::fences_from_histogram(histogram, method));
+        let trimmed = fences.filter(|_| options.trim_outliers).map(|fences| {
+            let mut kept = Histogram::new(histogram.significant_digits(), histogram.highest_trackable());
+            for (value, count) in histogram.recorded_buckets() {
+                if fences.classify(value) == OutlierClass::Normal {
+                    kept.record_n(value, count);
+                }
+            }
+            let trimmed = BenchmarkResults {
+                storage: Storage::Histogram(kept),
+                name: format!("{} (trimmed)", self.name),
+                tick_frequency_khz: self.tick_frequency_khz,
+                partition: None,
+                batch_size: self.batch_size,
+                synthetic: 0,
+                backfilled: Vec::new(),
+            };
+            Box::new(trimmed.analyze_with(&AnalysisOptions { outlier_method, trim_outliers: false, ..options.clone() }))
+        });
+        
+        BenchmarkAnalysis {
+            name: self.name.clone(),
+            count: histogram.len() as usize,
+            min: histogram.min(),
+            max: histogram.max(),
+            mean: histogram.mean() as u64,
+            p50: histogram.value_at_percentile(50.0),
+            p95: histogram.value_at_percentile(95.0),
+            p99: histogram.value_at_percentile(99.0),
+            p999: histogram.value_at_percentile(99.9),
+            std_dev: histogram.std_dev(),
+            cycles: self.tick_frequency_khz.map(|frequency_khz| CycleAnalysis {
+                frequency_khz,
+                min: histogram.min() as f64,
+                max: histogram.max() as f64,
+                mean: histogram.mean(),
+                p50: histogram.value_at_percentile(50.0) as f64,
+                p95: histogram.value_at_percentile(95.0) as f64,
+                p99: histogram.value_at_percentile(99.0) as f64,
+                p999: histogram.value_at_percentile(99.9) as f64,
+                std_dev: histogram.std_dev(),
+                percentiles: histogram_percentile_values(histogram, &options.percentiles),
+            }),
+            hardware_counters: None,
+            disturbances: None,
+            warmup: None,
+            stop_reason: None,
+            pinning: None,
+            pinning_error: None,
+            uncorrected: None,
+            confidence: options.confidence.as_ref()
+                .map(|config| confidence::from_histogram(histogram, config)),
+            percentiles: histogram_percentile_values(histogram, &options.percentiles),
+            outliers: outlier_method.zip(fences)
+                .map(|(method, fences)| outliers::classify(histogram.recorded_buckets(), method, fences)),
+            trimmed,
+            resolution_ns: options.resolution_ns,
+            overhead_subtracted_ns: None,
+            batch_size: None,
+            per_call: None,
+            migrated_samples: None,
+            synthetic_samples: None,
+        }
+    }
+    
+    fn analyze_raw(&self, measurements: &[u64], options: &AnalysisOptions) -> BenchmarkAnalysis {
+        if measurements.is_empty() {
+            return BenchmarkAnalysis::empty(self.name.clone());
+        }
+        
+        let mut sorted = measurements.to_vec();
         sorted.sort_unstable();
         
         let len = sorted.len();
@@ -47,6 +433,28 @@ impl BenchmarkResults {
                 diff * diff
             })
             .sum::<f64>() / len as f64;
+        let std_dev = variance.sqrt();
+        let method = options.percentile_method;
+        let [p50, p95, p99, p999] = [50.0, 95.0, 99.0, 99.9].map(|p| percentile_with(&sorted, p, method));
+        
+        let outlier_method = options.outlier_classification();
+        let fences = outlier_method.map(|method| outliers::fences_from_sorted(&sorted, method));
+        let trimmed = fences.filter(|_| options.trim_outliers).map(|fences| {
+            let kept: Vec<u64> = sorted.iter()
+                .copied()
+                .filter(|&v| fences.classify(v) == OutlierClass::Normal)
+                .collect();
+            let trimmed = BenchmarkResults {
+                storage: Storage::Raw(kept),
+                name: format!("{} (trimmed)", self.name),
+                tick_frequency_khz: self.tick_frequency_khz,
+                partition: None,
+                batch_size: self.batch_size,
+                synthetic: 0,
+                backfilled: Vec::new(),
+            };
+            Box::new(trimmed.analyze_with(&AnalysisOptions { outlier_method, trim_outliers: false, ..options.clone() }))
+        });
         
         BenchmarkAnalysis {
             name: self.name.clone(),
@@ -54,16 +462,187 @@ impl BenchmarkResults {
             min: sorted[0],
             max: sorted[len - 1],
             mean,
-            p50: percentile(&sorted, 50.0),
-            p95: percentile(&sorted, 95.0),
-            p99: percentile(&sorted, 99.0),
-            p999: percentile(&sorted, 99.9),
-            std_dev: variance.sqrt(),
+            p50: p50.round() as u64,
+            p95: p95.round() as u64,
+            p99: p99.round() as u64,
+            p999: p999.round() as u64,
+            std_dev,
+            cycles: self.tick_frequency_khz.map(|frequency_khz| CycleAnalysis {
+                frequency_khz,
+                min: sorted[0] as f64,
+                max: sorted[len - 1] as f64,
+                mean: sum as f64 / len as f64,
+                p50,
+                p95,
+                p99,
+                p999,
+                std_dev,
+                percentiles: percentile_values(&sorted, &options.percentiles, method),
+            }),
+            hardware_counters: None,
+            disturbances: None,
+            warmup: None,
+            stop_reason: None,
+            pinning: None,
+            pinning_error: None,
+            uncorrected: None,
+            confidence: options.confidence.as_ref()
+                .map(|config| confidence::from_sorted(&sorted, sum as f64 / len as f64, std_dev, config, method)),
+            percentiles: percentile_values(&sorted, &options.percentiles, method),
+            outliers: outlier_method.zip(fences)
+                .map(|(method, fences)| outliers::classify(sorted.iter().map(|&v| (v, 1)), method, fences)),
+            trimmed,
+            resolution_ns: options.resolution_ns,
+            overhead_subtracted_ns: None,
+            batch_size: None,
+            per_call: None,
+            migrated_samples: None,
+            synthetic_samples: None,
+        }
+    }
+    
+    /// Record a sample taken at a fixed expected interval, backfilling the
+    /// samples a stalled call prevented from being issued
+    ///
+    /// Mirrors HdrHistogram's `recordValueWithExpectedInterval`: a value of
+    /// `n * interval` also records `(n-1) * interval`, ..., `interval`.
+    /// At most `MAX_BACKFILL` values are added per call; longer stalls are
+    /// backfilled at a coarser, evenly spaced step. Returns the number of
+    /// backfilled samples, which the analysis reports as `synthetic_samples`
+    /// rather than in `count` and `samples` leaves out.
+    pub fn record_corrected(&mut self, nanoseconds: u64, expected_interval_ns: u64) -> u64 {
+        self.record(nanoseconds);
+        if expected_interval_ns == 0 || nanoseconds / expected_interval_ns < 2 {
+            return 0;
+        }
+        
+        let missing_calls = nanoseconds / expected_interval_ns - 1;
+        let step = expected_interval_ns.saturating_mul(missing_calls.div_ceil(MAX_BACKFILL));
+        let mut backfilled = 0;
+        let mut missing = nanoseconds - step;
+        while missing >= expected_interval_ns {
+            match &mut self.storage {
+                Storage::Raw(_) => self.backfilled.push(missing),
+                Storage::Histogram(histogram) => histogram.record(missing),
+            }
+            backfilled += 1;
+            missing = missing.saturating_sub(step);
         }
+        self.synthetic += backfilled;
+        backfilled
     }
     
     pub fn clear(&mut self) {
-        self.measurements.clear();
+        match &mut self.storage {
+            Storage::Raw(measurements) => measurements.clear(),
+            Storage::Histogram(histogram) => histogram.clear(),
+        }
+        self.synthetic = 0;
+        self.backfilled.clear();
+        self.partition = None;
+    }
+}
+
+/// Unit used by `BenchmarkAnalysis::summary_in`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportUnit {
+    Nanoseconds,
+    Cycles,
+    Both,
+}
+
+/// Statistics in counter ticks, unrounded
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleAnalysis {
+    pub frequency_khz: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub p999: f64,
+    pub std_dev: f64,
+    pub percentiles: Vec<PercentileValue>,
+}
+
+impl CycleAnalysis {
+    /// Convert a tick count to fractional nanoseconds
+    pub fn to_ns(&self, cycles: f64) -> f64 {
+        cycles * 1_000_000.0 / self.frequency_khz as f64
+    }
+}
+
+/// Per-call statistics of batched samples in unrounded nanoseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerCallAnalysis {
+    pub batch_size: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub p999: f64,
+    pub std_dev: f64,
+}
+
+/// Distribution of one hardware counter across samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterSummary {
+    pub name: String,
+    pub mean: f64,
+    pub p50: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl CounterSummary {
+    pub fn from_samples(name: &str, mut samples: Vec<f64>) -> Self {
+        samples.sort_unstable_by(f64::total_cmp);
+        let at = |p: f64| match samples.len() {
+            0 => 0.0,
+            len => samples[((p / 100.0 * (len - 1) as f64).round() as usize).min(len - 1)],
+        };
+        Self {
+            name: name.to_string(),
+            mean: if samples.is_empty() { 0.0 } else { samples.iter().sum::<f64>() / samples.len() as f64 },
+            p50: at(50.0),
+            p99: at(99.0),
+            max: samples.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Hardware counters collected next to the latency samples
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CounterReport {
+    pub counters: Vec<CounterSummary>,
+    /// Requested counters that could not be opened, with the reason
+    pub unavailable: Vec<String>,
+}
+
+impl CounterReport {
+    pub fn get(&self, name: &str) -> Option<&CounterSummary> {
+        self.counters.iter().find(|c| c.name == name)
+    }
+}
+
+/// Samples split by whether a context switch or page fault hit them
+#[derive(Debug, Clone)]
+pub struct DisturbanceReport {
+    pub clean_samples: usize,
+    pub disturbed_samples: usize,
+    /// Switches and faults summed over all samples
+    pub totals: Disturbance,
+    pub clean: Box<BenchmarkAnalysis>,
+    pub disturbed: Box<BenchmarkAnalysis>,
+}
+
+impl DisturbanceReport {
+    pub fn disturbed_fraction(&self) -> f64 {
+        let total = self.clean_samples + self.disturbed_samples;
+        if total == 0 { 0.0 } else { self.disturbed_samples as f64 / total as f64 }
     }
 }
 
@@ -79,9 +658,53 @@ pub struct BenchmarkAnalysis {
     pub p99: u64,
     pub p999: u64,
     pub std_dev: f64,
+    /// Service-time distribution of an open-loop run, before coordinated
+    /// omission correction; the main fields then hold the corrected values
+    pub uncorrected: Option<Box<BenchmarkAnalysis>>,
+    /// Confidence intervals, when requested through `AnalysisOptions`
+    pub confidence: Option<ConfidenceIntervals>,
+    /// Extra percentiles requested through `AnalysisOptions::percentiles`
+    pub percentiles: Vec<PercentileValue>,
+    /// Outlier counts under `AnalysisOptions::outlier_method`, when set
+    pub outliers: Option<OutlierReport>,
+    /// The same analysis with outliers removed, when requested
+    pub trimmed: Option<Box<BenchmarkAnalysis>>,
+    /// The same statistics in counter ticks, for results recorded with
+    /// `BenchmarkResults::in_cycles`; the nanosecond fields are converted
+    /// from these
+    pub cycles: Option<CycleAnalysis>,
+    /// Hardware counter statistics per sample, when collected with the
+    /// `perf` feature
+    pub hardware_counters: Option<CounterReport>,
+    /// Clean vs disturbed split, for samples recorded with
+    /// `BenchmarkResults::record_with_disturbance`
+    pub disturbances: Option<DisturbanceReport>,
+    /// Calls run before sampling started, when a warmup was configured
+    pub warmup: Option<WarmupReport>,
+    /// Why a `SimpleBench::bench_until` run stopped
+    pub stop_reason: Option<crate::StopReason>,
+    /// Core and scheduling policy the samples were taken with, when pinned
+    pub pinning: Option<Pinning>,
+    /// Why pinning was requested but some samples ran unpinned
+    pub pinning_error: Option<String>,
+    /// Timer resolution the samples were taken with, when known
+    pub resolution_ns: Option<u64>,
+    /// Timer overhead already subtracted from every sample
+    pub overhead_subtracted_ns: Option<u64>,
+    /// Calls per sample when measured in batches; values are then per-call
+    /// means of each batch, not individual call latencies
+    pub batch_size: Option<u64>,
+    /// Unrounded per-call statistics of batched results, which keep
+    /// sub-nanosecond precision the integer fields round away
+    pub per_call: Option<PerCallAnalysis>,
+    /// Samples that started and stopped on different CPUs, when tracked
+    pub migrated_samples: Option<u64>,
+    /// Values `BenchmarkResults::record_corrected` backfilled; they shape
+    /// the distribution but are not included in `count`
+    pub synthetic_samples: Option<u64>,
 }
 
-fn percentile(sorted_data: &[u64], p: f64) -> u64 {
+pub(crate) fn percentile(sorted_data: &[u64], p: f64) -> u64 {
     let len = sorted_data.len();
     if len == 0 { return 0; }
     if len == 1 { return sorted_data[0]; }
@@ -90,6 +713,167 @@ fn percentile(sorted_data: &[u64], p: f64) -> u64 {
     sorted_data[index.min(len - 1)]
 }
 
+/// Percentile `p` (0-100) of sorted data using the given estimator
+pub fn percentile_with(sorted_data: &[u64], p: f64, method: PercentileMethod) -> f64 {
+    let len = sorted_data.len();
+    if len == 0 { return 0.0; }
+    if len == 1 { return sorted_data[0] as f64; }
+    
+    let q = (p / 100.0).clamp(0.0, 1.0);
+    match method {
+        PercentileMethod::NearestRank => percentile(sorted_data, p) as f64,
+        PercentileMethod::Linear => hyndman_fan(sorted_data, q, 7),
+        PercentileMethod::HyndmanFan(kind) => hyndman_fan(sorted_data, q, kind.clamp(1, 9)),
+    }
+}
+
+/// Hyndman & Fan (1996) sample quantile of type `kind` for `q` in [0, 1]
+fn hyndman_fan(sorted_data: &[u64], q: f64, kind: u8) -> f64 {
+    let n = sorted_data.len() as f64;
+    let m = match kind {
+        1 | 2 | 4 => 0.0,
+        3 => -0.5,
+        5 => 0.5,
+        6 => q,
+        7 => 1.0 - q,
+        8 => (q + 1.0) / 3.0,
+        _ => q / 4.0 + 0.375,
+    };
+    
+    let h = n * q + m;
+    let j = h.floor();
+    let g = h - j;
+    let gamma = match kind {
+        1 => if g > 0.0 { 1.0 } else { 0.0 },
+        2 => if g > 0.0 { 1.0 } else { 0.5 },
+        3 => if g == 0.0 && (j as i64) % 2 == 0 { 0.0 } else { 1.0 },
+        _ => g,
+    };
+    
+    // `j` is a 1-based rank; ranks outside the sample clamp to min/max
+    let at = |rank: f64| sorted_data[(rank.clamp(1.0, n) as usize) - 1] as f64;
+    (1.0 - gamma) * at(j) + gamma * at(j + 1.0)
+}
+
+fn percentile_values(sorted_data: &[u64], percentiles: &[f64], method: PercentileMethod) -> Vec<PercentileValue> {
+    percentiles.iter()
+        .map(|&p| PercentileValue { percentile: p, value: percentile_with(sorted_data, p, method) })
+        .collect()
+}
+
+fn histogram_percentile_values(histogram: &Histogram, percentiles: &[f64]) -> Vec<PercentileValue> {
+    percentiles.iter()
+        .map(|&p| PercentileValue { percentile: p, value: histogram.value_at_percentile(p) as f64 })
+        .collect()
+}
+
+/// Two-sided p-value of Welch's t-test for a difference in means
+///
+/// Works from summary statistics only, so it can compare a fresh analysis
+/// against a persisted baseline without the raw samples.
+pub fn welch_t_test(a: &BenchmarkAnalysis, b: &BenchmarkAnalysis) -> f64 {
+    if a.count < 2 || b.count < 2 {
+        return 1.0;
+    }
+    
+    let (n1, n2) = (a.count as f64, b.count as f64);
+    // Sample variance from the population std_dev stored in the analysis
+    let v1 = a.std_dev * a.std_dev * n1 / (n1 - 1.0) / n1;
+    let v2 = b.std_dev * b.std_dev * n2 / (n2 - 1.0) / n2;
+    let diff = a.mean as f64 - b.mean as f64;
+    
+    if v1 + v2 == 0.0 {
+        return if diff == 0.0 { 1.0 } else { 0.0 };
+    }
+    
+    let t = diff / (v1 + v2).sqrt();
+    let df = (v1 + v2).powi(2) / (v1 * v1 / (n1 - 1.0) + v2 * v2 / (n2 - 1.0));
+    
+    regularized_incomplete_beta(df / (df + t * t), df / 2.0, 0.5)
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos approximation, g = 7
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    
+    if x < 0.5 {
+        let pi = core::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    
+    let x = x - 1.0;
+    let mut sum = COEFFS[0];
+    for (i, &c) in COEFFS.iter().enumerate().skip(1) {
+        sum += c / (x + i as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2.0 * core::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        ln_front.exp() * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - ln_front.exp() * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY { d = TINY; }
+    d = 1.0 / d;
+    let mut result = d;
+    
+    for m in 1..300 {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        
+        let numerator = m * (b - m) * x / ((a + m2 - 1.0) * (a + m2));
+        d = 1.0 + numerator * d;
+        if d.abs() < TINY { d = TINY; }
+        c = 1.0 + numerator / c;
+        if c.abs() < TINY { c = TINY; }
+        d = 1.0 / d;
+        result *= d * c;
+        
+        let numerator = -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0));
+        d = 1.0 + numerator * d;
+        if d.abs() < TINY { d = TINY; }
+        c = 1.0 + numerator / c;
+        if c.abs() < TINY { c = TINY; }
+        d = 1.0 / d;
+        let delta = d * c;
+        result *= delta;
+        
+        if (delta - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    
+    result
+}
+
 impl BenchmarkAnalysis {
     pub fn empty(name: String) -> Self {
         Self {
@@ -103,13 +887,309 @@ impl BenchmarkAnalysis {
             p99: 0,
             p999: 0,
             std_dev: 0.0,
+            hardware_counters: None,
+            disturbances: None,
+            warmup: None,
+            stop_reason: None,
+            pinning: None,
+            pinning_error: None,
+            uncorrected: None,
+            confidence: None,
+            percentiles: Vec::new(),
+            outliers: None,
+            trimmed: None,
+            cycles: None,
+            resolution_ns: None,
+            overhead_subtracted_ns: None,
+            batch_size: None,
+            per_call: None,
+            migrated_samples: None,
+            synthetic_samples: None,
+        }
+    }
+    /// Turn an analysis computed on ticks into nanoseconds, using the
+    /// unrounded values in `cycles`
+    fn converted_from_cycles(mut self) -> Self {
+        let Some(cycles) = &self.cycles else {
+            return self;
+        };
+        let scale = cycles.to_ns(1.0);
+        let ns = |ticks: f64| (ticks * scale).round() as u64;
+        let interval = |i: Interval| Interval { lower: ns(i.lower as f64), upper: ns(i.upper as f64) };
+        
+        self.min = ns(cycles.min);
+        self.max = ns(cycles.max);
+        self.mean = ns(cycles.mean);
+        self.p50 = ns(cycles.p50);
+        self.p95 = ns(cycles.p95);
+        self.p99 = ns(cycles.p99);
+        self.p999 = ns(cycles.p999);
+        self.std_dev = cycles.std_dev * scale;
+        for value in &mut self.percentiles {
+            value.value *= scale;
+        }
+        if let Some(ci) = &mut self.confidence {
+            ci.mean = interval(ci.mean);
+            ci.p50 = interval(ci.p50);
+            ci.p95 = interval(ci.p95);
+            ci.p99 = interval(ci.p99);
+            ci.p999 = interval(ci.p999);
+        }
+        if let Some(outliers) = &mut self.outliers {
+            outliers.fences.low_severe *= scale;
+            outliers.fences.low_mild *= scale;
+            outliers.fences.high_mild *= scale;
+            outliers.fences.high_severe *= scale;
+        }
+        self
+    }
+    
+    /// Turn an analysis of batch totals into per-call values
+    fn divided_into_calls(mut self, batch: u64) -> Self {
+        let calls = batch as f64;
+        let exact = |value: u64, cycles: Option<f64>| match (&self.cycles, cycles) {
+            (Some(c), Some(ticks)) => c.to_ns(ticks),
+            _ => value as f64,
+        } / calls;
+        let c = self.cycles.as_ref();
+        self.per_call = Some(PerCallAnalysis {
+            batch_size: batch,
+            min: exact(self.min, c.map(|c| c.min)),
+            max: exact(self.max, c.map(|c| c.max)),
+            mean: exact(self.mean, c.map(|c| c.mean)),
+            p50: exact(self.p50, c.map(|c| c.p50)),
+            p95: exact(self.p95, c.map(|c| c.p95)),
+            p99: exact(self.p99, c.map(|c| c.p99)),
+            p999: exact(self.p999, c.map(|c| c.p999)),
+            std_dev: self.std_dev / calls,
+        });
+        
+        let per_call = |value: u64| (value as f64 / calls).round() as u64;
+        let interval = |i: Interval| Interval { lower: per_call(i.lower), upper: per_call(i.upper) };
+        let exact = self.per_call.as_ref().unwrap();
+        self.min = exact.min.round() as u64;
+        self.max = exact.max.round() as u64;
+        self.mean = exact.mean.round() as u64;
+        self.p50 = exact.p50.round() as u64;
+        self.p95 = exact.p95.round() as u64;
+        self.p99 = exact.p99.round() as u64;
+        self.p999 = exact.p999.round() as u64;
+        self.std_dev = exact.std_dev;
+        self.batch_size = Some(batch);
+        for value in &mut self.percentiles {
+            value.value /= calls;
+        }
+        if let Some(cycles) = &mut self.cycles {
+            for stat in [&mut cycles.min, &mut cycles.max, &mut cycles.mean, &mut cycles.p50, &mut cycles.p95,
+                         &mut cycles.p99, &mut cycles.p999, &mut cycles.std_dev] {
+                *stat /= calls;
+            }
+            for value in &mut cycles.percentiles {
+                value.value /= calls;
+            }
+        }
+        if let Some(ci) = &mut self.confidence {
+            ci.mean = interval(ci.mean);
+            ci.p50 = interval(ci.p50);
+            ci.p95 = interval(ci.p95);
+            ci.p99 = interval(ci.p99);
+            ci.p999 = interval(ci.p999);
+        }
+        if let Some(outliers) = &mut self.outliers {
+            outliers.fences.low_severe /= calls;
+            outliers.fences.low_mild /= calls;
+            outliers.fences.high_mild /= calls;
+            outliers.fences.high_severe /= calls;
         }
+        self
     }
+    
+    /// Summary in nanoseconds, with cycles too when they were recorded
     pub fn summary(&self) -> String {
-        format!(
-            "{}: {} samples, mean={:>6}ns, p50={:>6}ns, p95={:>6}ns, p99={:>6}ns, p99.9={:>6}ns, std_dev={:>6.1}ns",
-            self.name, self.count, self.mean, self.p50, self.p95, self.p99, self.p999, self.std_dev
-        )
+        self.summary_in(if self.cycles.is_some() { ReportUnit::Both } else { ReportUnit::Nanoseconds })
+    }
+    
+    /// Summary with the headline statistics in `unit`; cycles are only
+    /// available for results recorded with `in_cycles`, otherwise
+    /// nanoseconds are shown
+    pub fn summary_in(&self, unit: ReportUnit) -> String {
+        let mut summary = match (&self.cycles, unit) {
+            (Some(cycles), ReportUnit::Nanoseconds | ReportUnit::Both) => format!(
+                "{}: {} samples, mean={:>8.2}ns, p50={:>8.2}ns, p95={:>8.2}ns, p99={:>8.2}ns, p99.9={:>8.2}ns, std_dev={:>6.2}ns",
+                self.name, self.count, cycles.to_ns(cycles.mean), cycles.to_ns(cycles.p50), cycles.to_ns(cycles.p95),
+                cycles.to_ns(cycles.p99), cycles.to_ns(cycles.p999), cycles.to_ns(cycles.std_dev)
+            ),
+            (Some(cycles), ReportUnit::Cycles) => format!(
+                "{}: {} samples, mean={:.1} cycles, p50={:.1} cycles, p95={:.1} cycles, p99={:.1} cycles, p99.9={:.1} cycles, std_dev={:.1} cycles",
+                self.name, self.count, cycles.mean, cycles.p50, cycles.p95, cycles.p99, cycles.p999, cycles.std_dev
+            ),
+            (None, _) => match &self.per_call {
+                Some(per_call) => format!(
+                    "{}: {} samples, mean={:>8.2}ns, p50={:>8.2}ns, p95={:>8.2}ns, p99={:>8.2}ns, p99.9={:>8.2}ns, std_dev={:>6.2}ns",
+                    self.name, self.count, per_call.mean, per_call.p50, per_call.p95, per_call.p99, per_call.p999, per_call.std_dev
+                ),
+                None => format!(
+                    "{}: {} samples, mean={:>6}ns, p50={:>6}ns, p95={:>6}ns, p99={:>6}ns, p99.9={:>6}ns, std_dev={:>6.1}ns",
+                    self.name, self.count, self.mean, self.p50, self.p95, self.p99, self.p999, self.std_dev
+                ),
+            },
+        };
+        
+        match (&self.cycles, unit) {
+            (Some(cycles), ReportUnit::Cycles) => {
+                for value in &cycles.percentiles {
+                    summary.push_str(&format!(", p{}={:.1} cycles", value.percentile, value.value));
+                }
+            }
+            _ => {
+                for value in &self.percentiles {
+                    summary.push_str(&format!(", p{}={:.1}ns", value.percentile, value.value));
+                }
+            }
+        }
+        
+        if let (Some(cycles), ReportUnit::Both) = (&self.cycles, unit) {
+            summary.push_str(&format!(
+                " | cycles @ {:.3} GHz: mean={:.1}, p50={:.1}, p99={:.1}, p99.9={:.1}",
+                cycles.frequency_khz as f64 / 1_000_000.0, cycles.mean, cycles.p50, cycles.p99, cycles.p999
+            ));
+        }
+        
+        if let Some(outliers) = self.outliers.as_ref().filter(|o| o.total() > 0) {
+            summary.push_str(&format!(
+                ", outliers={} ({:.1}%: low {}/{}, high {}/{} mild/severe)",
+                outliers.total(), outliers.fraction() * 100.0,
+                outliers.low_mild, outliers.low_severe, outliers.high_mild, outliers.high_severe
+            ));
+        }
+        
+        if let Some(batch) = self.batch_size {
+            summary.push_str(&format!(" | batched: {batch} ops/sample (per-call means, not per-call tails)"));
+        }
+        
+        if let Some(report) = &self.hardware_counters {
+            if !report.counters.is_empty() {
+                let counters: Vec<String> = report.counters.iter()
+                    .map(|c| format!("{} {:.1} (p99 {:.1})", c.name, c.mean, c.p99))
+                    .collect();
+                summary.push_str(&format!(" | per sample: {}", counters.join(", ")));
+            }
+            if !report.unavailable.is_empty() {
+                summary.push_str(&format!(" | counters unavailable: {}", report.unavailable.join(", ")));
+            }
+        }
+        
+        if let Some(pinning) = &self.pinning {
+            summary.push_str(&format!(" | pinned to cpu{}", pinning.cpu));
+            if let SchedulingPolicy::Fifo { priority } = pinning.policy {
+                summary.push_str(&format!(", SCHED_FIFO {priority}"));
+            }
+            if pinning.lock_memory {
+                summary.push_str(", memory locked");
+            }
+        }
+        if let Some(error) = &self.pinning_error {
+            summary.push_str(&format!(" | WARNING: not pinned throughout, {error}"));
+        }
+        
+        match self.stop_reason {
+            Some(crate::StopReason::MaxSamples) => summary.push_str(" | WARNING: stopped at the sample limit before the target was met"),
+            Some(crate::StopReason::TimeBudget) => summary.push_str(" | stopped: time budget"),
+            Some(crate::StopReason::PrecisionReached) => summary.push_str(" | stopped: target precision reached"),
+            None => {}
+        }
+        
+        if let Some(warmup) = &self.warmup {
+            summary.push_str(&format!(" | warmup: {} calls discarded", warmup.discarded));
+            match warmup.converged {
+                Some(true) => summary.push_str(" (steady state reached)"),
+                Some(false) => summary.push_str(" (WARNING: no steady state before the limit)"),
+                None => {}
+            }
+        }
+        
+        if let Some(report) = &self.disturbances {
+            let totals = &report.totals;
+            summary.push_str(&format!(
+                " | disturbed: {} samples ({:.1}%: {} voluntary/{} involuntary switches, {} minor/{} major faults)",
+                report.disturbed_samples, report.disturbed_fraction() * 100.0, totals.voluntary_switches,
+                totals.involuntary_switches, totals.minor_faults, totals.major_faults
+            ));
+            if report.disturbed_samples > 0 && report.clean_samples > 0 {
+                summary.push_str(&format!(
+                    ", p99 clean={}ns vs disturbed={}ns", report.clean.p99, report.disturbed.p99
+                ));
+            }
+        }
+        
+        if let Some(migrated) = self.migrated_samples.filter(|&m| m > 0) {
+            summary.push_str(&format!(" | {migrated} samples crossed CPUs"));
+        }
+        
+        if let Some(synthetic) = self.synthetic_samples {
+            summary.push_str(&format!(" (+{synthetic} backfilled)"));
+        }
+        
+        if let Some(overhead) = self.overhead_subtracted_ns {
+            summary.push_str(&format!(", timer overhead {overhead}ns subtracted"));
+        }
+        
+        if self.below_resolution() {
+            summary.push_str(&format!(
+                " | WARNING: p50 below timer resolution ({}ns), consider batching",
+                self.resolution_ns.unwrap_or(0)
+            ));
+        }
+        
+        if let Some(uncorrected) = &self.uncorrected {
+            summary.push_str(&format!(
+                " | uncorrected: p50={}ns, p99={}ns, p99.9={}ns",
+                uncorrected.p50, uncorrected.p99, uncorrected.p999
+            ));
+        }
+        
+        if let Some(trimmed) = &self.trimmed {
+            summary.push_str(&format!(
+                " | trimmed: {} samples, mean={}ns, p99={}ns, p99.9={}ns",
+                trimmed.count, trimmed.mean, trimmed.p99, trimmed.p999
+            ));
+        }
+        
+        if let Some(ci) = &self.confidence {
+            summary.push_str(&format!(
+                " | {:.0}% CI: mean=[{}, {}]ns, p50=[{}, {}]ns, p95=[{}, {}]ns, p99=[{}, {}]ns, p99.9=[{}, {}]ns",
+                ci.level * 100.0,
+                ci.mean.lower, ci.mean.upper, ci.p50.lower, ci.p50.upper, ci.p95.lower, ci.p95.upper,
+                ci.p99.lower, ci.p99.upper, ci.p999.lower, ci.p999.upper
+            ));
+            if !ci.insufficient_samples.is_empty() {
+                let flagged: Vec<String> = ci.insufficient_samples.iter().map(|p| format!("p{p}")).collect();
+                summary.push_str(&format!(" (too few samples for {})", flagged.join(", ")));
+            }
+        }
+        
+        summary
+    }
+    
+    /// Value of percentile `p`, from the extra percentiles or the fixed fields
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        if let Some(value) = self.percentiles.iter().find(|v| v.percentile == p) {
+            return Some(value.value);
+        }
+        [(50.0, self.p50), (95.0, self.p95), (99.0, self.p99), (99.9, self.p999)]
+            .iter()
+            .find(|(fixed, _)| *fixed == p)
+            .map(|&(_, value)| value as f64)
+    }
+    
+    /// Whether the median (per batch, if batched) is smaller than the timer
+    /// can resolve, making the distribution mostly quantization noise
+    pub fn below_resolution(&self) -> bool {
+        let per_sample = match &self.per_call {
+            Some(per_call) => per_call.p50 * per_call.batch_size as f64,
+            None => self.p50 as f64 * self.batch_size.unwrap_or(1) as f64,
+        };
+        self.count > 0 && self.resolution_ns.is_some_and(|resolution| per_sample < resolution as f64)
     }
     
     pub fn meets_target(&self, target_p99_ns: u64) -> bool {
@@ -198,4 +1278,387 @@ mod tests {
         assert!(summary.contains("2 samples"));
         assert!(summary.contains("mean"));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_histogram_mode_matches_raw() {
+        let mut raw = BenchmarkResults::new("raw".to_string());
+        let mut hist = BenchmarkResults::with_histogram("hist".to_string(), 3, 1_000_000_000);
+        
+        for i in 1..=10_000u64 {
+            let value = (i * 7919) % 20_000 + 50;
+            raw.record(value);
+            hist.record(value);
+        }
+        
+        let exact = raw.analyze();
+        let approx = hist.analyze();
+        
+        assert_eq!(approx.count, exact.count);
+        assert_eq!(approx.min, exact.min);
+        assert_eq!(approx.max, exact.max);
+        assert_eq!(approx.mean, exact.mean);
+        for (a, e) in [(approx.p50, exact.p50), (approx.p99, exact.p99), (approx.p999, exact.p999)] {
+            let error = (a as f64 - e as f64).abs() / e as f64;
+            assert!(error < 0.005, "histogram percentile {} vs exact {}", a, e);
+        }
+        assert!((approx.std_dev - exact.std_dev).abs() / exact.std_dev < 0.01);
+    }
+    
+    #[test]
+    fn test_histogram_mode_clear() {
+        let mut results = BenchmarkResults::with_histogram("hist_clear".to_string(), 2, 1_000_000);
+        results.record(100);
+        results.record(200);
+        
+        assert_eq!(results.len(), 2);
+        assert!(results.histogram().is_some());
+        
+        results.clear();
+        assert!(results.is_empty());
+        assert_eq!(results.analyze().count, 0);
+    }
+    
+    #[test]
+    fn test_record_corrected_backfills() {
+        let mut results = BenchmarkResults::new("corrected".to_string());
+        
+        assert_eq!(results.record_corrected(50, 100), 0);
+        assert_eq!(results.record_corrected(150, 100), 0);
+        assert_eq!(results.record_corrected(1000, 100), 9);
+        
+        let analysis = results.analyze();
+        assert_eq!(analysis.count, 3);
+        assert_eq!(analysis.synthetic_samples, Some(9));
+        assert_eq!(analysis.max, 1000);
+        assert_eq!(analysis.min, 50);
+        assert!(analysis.summary().contains("+9 backfilled"));
+        
+        let mut hist = BenchmarkResults::with_histogram("corrected_hist".to_string(), 3, 1_000_000);
+        assert_eq!(hist.record_corrected(1000, 100), 9);
+        assert_eq!(hist.len(), 10);
+        let analysis = hist.analyze();
+        assert_eq!(analysis.min, 100);
+        assert_eq!((analysis.count, analysis.synthetic_samples), (1, Some(9)));
+        
+        // Backfilled values shape the statistics but aren't samples
+        assert_eq!(results.samples(), Some(&[50, 150, 1000][..]));
+        assert_eq!(results.len(), 12);
+        assert_eq!(results.synthetic_samples(), 9);
+        
+        // No overflow for huge intervals, and long stalls stay bounded
+        assert_eq!(results.record_corrected(u64::MAX, u64::MAX / 2 + 1), 0);
+        let mut stalled = BenchmarkResults::new("stalled".to_string());
+        let backfilled = stalled.record_corrected(1_000_000_000, 10);
+        assert!((MAX_BACKFILL / 2..=MAX_BACKFILL).contains(&backfilled), "{backfilled}");
+        assert_eq!(stalled.analyze().max, 1_000_000_000);
+        
+        hist.clear();
+        hist.record(5);
+        assert_eq!(hist.analyze().synthetic_samples, None);
+    }
+    
+    #[test]
+    fn test_summary_includes_uncorrected() {
+        let mut results = BenchmarkResults::new("open_loop".to_string());
+        results.record(500);
+        
+        let mut analysis = results.analyze();
+        assert!(!analysis.summary().contains("uncorrected"));
+        
+        analysis.uncorrected = Some(Box::new(BenchmarkAnalysis::empty("open_loop".to_string())));
+        assert!(analysis.summary().contains("uncorrected: p50=0ns"));
+    }
+    
+    #[test]
+    fn test_welch_t_test() {
+        let mut a = BenchmarkResults::new("a".to_string());
+        let mut b = BenchmarkResults::new("b".to_string());
+        let mut c = BenchmarkResults::new("c".to_string());
+        for i in 0..500u64 {
+            a.record(100 + i % 20);
+            b.record(100 + (i * 7) % 20);
+            c.record(150 + i % 20);
+        }
+        
+        let (a, b, c) = (a.analyze(), b.analyze(), c.analyze());
+        assert!(welch_t_test(&a, &b) > 0.5);
+        assert!(welch_t_test(&a, &c) < 1e-6);
+        assert_eq!(welch_t_test(&a, &BenchmarkAnalysis::empty("none".to_string())), 1.0);
+    }
+    
+    #[test]
+    fn test_incomplete_beta_known_values() {
+        // Student t with 10 df: P(|T| > 2.228) = 0.05
+        let df = 10.0;
+        let t: f64 = 2.228;
+        let p = regularized_incomplete_beta(df / (df + t * t), df / 2.0, 0.5);
+        assert!((p - 0.05).abs() < 1e-3, "p = {}", p);
+        
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+    }
+    
+    #[test]
+    fn test_analyze_with_confidence() {
+        let mut results = BenchmarkResults::new("ci_test".to_string());
+        for i in 1..=500 {
+            results.record(i);
+        }
+        
+        assert!(results.analyze().confidence.is_none());
+        
+        let options = AnalysisOptions::default().with_confidence(ConfidenceConfig::order_statistic(0.95));
+        let analysis = results.analyze_with(&options);
+        let ci = analysis.confidence.as_ref().expect("confidence intervals");
+        
+        assert!(ci.p50.contains(analysis.p50));
+        assert!(ci.mean.contains(analysis.mean));
+        // 500 samples resolve p95 but not the p99 or p99.9 upper bounds
+        assert_eq!(ci.insufficient_samples, vec![99.0, 99.9]);
+        
+        let summary = analysis.summary();
+        assert!(summary.contains("95% CI"));
+        assert!(summary.contains("too few samples for p99, p99.9"));
+    }
+    
+    #[test]
+    fn test_percentile_methods() {
+        let data: Vec<u64> = (1..=10).collect();
+        
+        assert_eq!(percentile_with(&data, 50.0, PercentileMethod::NearestRank), 6.0);
+        assert_eq!(percentile_with(&data, 50.0, PercentileMethod::Linear), 5.5);
+        assert_eq!(percentile_with(&data, 90.0, PercentileMethod::Linear), 9.1);
+        
+        // Reference values from R's quantile(1:10, 0.5, type = k)
+        let expected_median = [5.0, 5.5, 5.0, 5.0, 5.5, 5.5, 5.5, 5.5, 5.5];
+        for (kind, &expected) in (1..=9).zip(expected_median.iter()) {
+            let value = percentile_with(&data, 50.0, PercentileMethod::HyndmanFan(kind));
+            assert!((value - expected).abs() < 1e-9, "type {} median = {}", kind, value);
+        }
+        
+        // quantile(1:10, 0.25, type = k)
+        let expected_q1 = [3.0, 3.0, 2.0, 2.5, 3.0, 2.75, 3.25, 2.916_666_666_666_667, 2.9375];
+        for (kind, &expected) in (1..=9).zip(expected_q1.iter()) {
+            let value = percentile_with(&data, 25.0, PercentileMethod::HyndmanFan(kind));
+            assert!((value - expected).abs() < 1e-9, "type {} q1 = {}", kind, value);
+        }
+        
+        assert_eq!(percentile_with(&data, 0.0, PercentileMethod::HyndmanFan(6)), 1.0);
+        assert_eq!(percentile_with(&data, 100.0, PercentileMethod::HyndmanFan(6)), 10.0);
+        assert_eq!(percentile_with(&[], 50.0, PercentileMethod::Linear), 0.0);
+    }
+    
+    #[test]
+    fn test_requested_percentiles() {
+        let mut results = BenchmarkResults::new("tail".to_string());
+        for i in 1..=100_000 {
+            results.record(i);
+        }
+        
+        let options = AnalysisOptions::default()
+            .with_percentile_method(PercentileMethod::Linear)
+            .with_percentiles(&[99.99, 99.999]);
+        let analysis = results.analyze_with(&options);
+        
+        assert_eq!(analysis.percentiles.len(), 2);
+        assert!((analysis.percentile(99.99).unwrap() - 99_990.000_1).abs() < 1e-6);
+        assert_eq!(analysis.percentile(99.0), Some(analysis.p99 as f64));
+        assert_eq!(analysis.percentile(12.5), None);
+        assert!(analysis.summary().contains("p99.999="));
+        
+        let direct = results.percentiles(&[50.0], PercentileMethod::Linear);
+        assert_eq!(direct[0].value, 50_000.5);
+        
+        let mut hist = BenchmarkResults::with_histogram("tail_hist".to_string(), 3, 1_000_000);
+        for i in 1..=1000 {
+            hist.record(i);
+        }
+        assert_eq!(hist.analyze_with(&options).percentile(99.99), Some(1000.0));
+    }
+    
+    #[test]
+    fn test_outliers_and_trimmed_analysis() {
+        let mut results = BenchmarkResults::new("interrupts".to_string());
+        for i in 0..995 {
+            results.record(100 + i % 10);
+        }
+        for _ in 0..5 {
+            results.record(50_000);
+        }
+        
+        let report = results.classify_outliers(OutlierMethod::tukey());
+        assert_eq!(report.high_severe, 5);
+        assert_eq!(report.total(), 5);
+        
+        // Classification is opt-in, so default summaries are unchanged
+        let plain = results.analyze();
+        assert!(plain.outliers.is_none() && plain.trimmed.is_none());
+        assert!(!plain.summary().contains("outliers"));
+        
+        let analysis = results.analyze_with(&AnalysisOptions::default().with_trimmed());
+        assert_eq!(analysis.outliers.as_ref().unwrap().high_severe, 5);
+        assert_eq!(analysis.p999, 50_000);
+        
+        let trimmed = analysis.trimmed.as_ref().expect("trimmed analysis");
+        assert_eq!(trimmed.name, "interrupts (trimmed)");
+        assert_eq!(trimmed.count, 995);
+        assert!(trimmed.p999 < 110);
+        assert!(trimmed.trimmed.is_none());
+        
+        let summary = analysis.summary();
+        assert!(summary.contains("outliers=5"));
+        assert!(summary.contains("trimmed: 995 samples"));
+        
+        let mut hist = BenchmarkResults::with_histogram("interrupts_hist".to_string(), 3, 1_000_000);
+        for i in 0..995 {
+            hist.record(100 + i % 10);
+        }
+        hist.record(50_000);
+        let analysis = hist.analyze_with(&AnalysisOptions::default().with_outlier_method(OutlierMethod::mad()).with_trimmed());
+        assert_eq!(analysis.outliers.unwrap().high_severe, 1);
+        assert_eq!(analysis.trimmed.unwrap().max, 109);
+    }
+    
+    #[test]
+    fn test_below_resolution_flag() {
+        let mut results = BenchmarkResults::new("tiny".to_string());
+        for i in 0..100 {
+            results.record(i % 3);
+        }
+        
+        assert!(!results.analyze().below_resolution());
+        
+        let analysis = results.analyze_with(&AnalysisOptions::default().with_resolution_ns(41));
+        assert_eq!(analysis.resolution_ns, Some(41));
+        assert!(analysis.below_resolution());
+        assert!(analysis.summary().contains("below timer resolution (41ns)"));
+        
+        assert!(!results.analyze_with(&AnalysisOptions::default().with_resolution_ns(1)).below_resolution());
+    }
+    
+    #[test]
+    fn test_cycles_converted_at_analysis() {
+        // At 3 GHz each tick is a third of a nanosecond
+        let mut results = BenchmarkResults::new("ticks".to_string()).in_cycles(3_000_000);
+        for ticks in 1..=10 {
+            results.record(ticks);
+        }
+        
+        let options = AnalysisOptions::default().with_percentiles(&[90.0]).with_confidence(ConfidenceConfig::default());
+        let analysis = results.analyze_with(&options);
+        let cycles = analysis.cycles.as_ref().unwrap();
+        assert_eq!(cycles.mean, 5.5);
+        assert!((cycles.to_ns(cycles.mean) - 5.5 / 3.0).abs() < 1e-9);
+        assert_eq!(analysis.mean, 2);
+        assert_eq!(analysis.max, 3);
+        assert!((analysis.percentiles[0].value - cycles.percentiles[0].value / 3.0).abs() < 1e-9);
+        assert!(analysis.confidence.as_ref().unwrap().mean.upper <= 4);
+        
+        assert!(analysis.summary().contains("mean=    1.83ns"));
+        assert!(analysis.summary().contains("cycles @ 3.000 GHz"));
+        assert!(analysis.summary_in(ReportUnit::Cycles).contains("mean=5.5 cycles"));
+        assert!(!analysis.summary_in(ReportUnit::Nanoseconds).contains("GHz"));
+        
+        assert!(BenchmarkResults::new("ns".to_string()).analyze().cycles.is_none());
+    }
+    
+    #[test]
+    fn test_batch_totals_keep_sub_ns_precision() {
+        // 1000 calls of ~0.3ns each per sample
+        let mut results = BenchmarkResults::new("batched".to_string()).per_call(1000);
+        for total in [290, 300, 300, 310, 300] {
+            results.record(total);
+        }
+        
+        let analysis = results.analyze();
+        let per_call = analysis.per_call.as_ref().unwrap();
+        assert_eq!(per_call.batch_size, 1000);
+        assert!((per_call.p50 - 0.3).abs() < 1e-9);
+        assert!((per_call.max - 0.31).abs() < 1e-9);
+        assert_eq!(analysis.p50, 0);
+        assert_eq!(analysis.batch_size, Some(1000));
+        assert!(analysis.summary().contains("p50=    0.30ns"));
+        
+        // Ticks are converted before dividing: 3000 ticks at 3 GHz over 1000 calls
+        let mut ticks = BenchmarkResults::new("batched_ticks".to_string()).in_cycles(3_000_000).per_call(1000);
+        ticks.record(3000);
+        let analysis = ticks.analyze();
+        assert!((analysis.per_call.as_ref().unwrap().mean - 1.0).abs() < 1e-9);
+        assert_eq!(analysis.cycles.as_ref().unwrap().mean, 3.0);
+    }
+    
+    #[test]
+    fn test_disturbance_partition() {
+        let quiet = Disturbance::default();
+        let preempted = Disturbance { involuntary_switches: 1, ..Default::default() };
+        
+        let mut results = BenchmarkResults::with_histogram("split".to_string(), 3, 1_000_000);
+        for i in 0..90 {
+            results.record_with_disturbance(100 + i % 5, &quiet);
+        }
+        for _ in 0..10 {
+            results.record_with_disturbance(50_000, &preempted);
+        }
+        results.record(100);
+        
+        let analysis = results.analyze();
+        let report = analysis.disturbances.as_ref().unwrap();
+        assert_eq!(analysis.count, 101);
+        assert_eq!((report.clean_samples, report.disturbed_samples), (90, 10));
+        assert_eq!(report.totals.involuntary_switches, 10);
+        assert!(report.clean.p99 < 110);
+        assert_eq!(report.disturbed.name, "split (disturbed)");
+        assert!(analysis.summary().contains("disturbed: 10 samples (10.0%"));
+    }
+    
+    #[test]
+    fn test_clear_resets_disturbance_partition() {
+        let preempted = Disturbance { involuntary_switches: 1, ..Default::default() };
+        let mut results = BenchmarkResults::new("cleared".to_string());
+        results.record_with_disturbance(50_000, &preempted);
+        results.clear();
+        
+        results.record_with_disturbance(100, &Disturbance::default());
+        let report = results.analyze().disturbances.unwrap();
+        assert_eq!((report.clean_samples, report.disturbed_samples), (1, 0));
+        assert_eq!(report.totals, Disturbance::default());
+        
+        results.clear();
+        results.record(100);
+        assert!(results.analyze().disturbances.is_none());
+    }
+    
+    #[test]
+    fn test_percentile_interval() {
+        let mut raw = BenchmarkResults::new("raw".to_string());
+        let mut hist = BenchmarkResults::with_histogram("hist".to_string(), 3, 1_000_000);
+        assert!(raw.percentile_interval(50.0, 0.95).is_none());
+        for i in 1..=1000 {
+            raw.record(i);
+            hist.record(i);
+        }
+        
+        let median = raw.percentile_interval(50.0, 0.95).unwrap();
+        assert!(median.contains(500));
+        assert!(median.width() < 100);
+        assert_eq!(hist.percentile_interval(50.0, 0.95), Some(median));
+        // 1000 samples cannot bound p99.9 at 95%
+        assert!(raw.percentile_interval(99.9, 0.95).is_none());
+    }
+    
+    #[test]
+    fn test_counter_report_in_summary() {
+        let summary = CounterSummary::from_samples("cache-misses", vec![3.0, 1.0, 2.0, 10.0]);
+        assert_eq!(summary.mean, 4.0);
+        assert_eq!(summary.p50, 3.0);
+        assert_eq!(summary.max, 10.0);
+        
+        let mut analysis = BenchmarkAnalysis::empty("counted".to_string());
+        analysis.hardware_counters = Some(CounterReport {
+            counters: vec![summary],
+            unavailable: vec!["dTLB-misses (No such file or directory)".to_string()],
+        });
+        let text = analysis.summary();
+        assert!(text.contains("cache-misses 4.0 (p99 10.0)"));
+        assert!(text.contains("counters unavailable: dTLB-misses"));
+    }
+}
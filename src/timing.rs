@@ -1,7 +1,37 @@
 //! High-resolution timing utilities using CPU timestamp counter
 
 #[cfg(target_arch = "x86_64")]
-use core::arch::x86_64::{_rdtsc, _mm_lfence, _mm_mfence};
+use core::arch::x86_64::{__cpuid, __rdtscp, _rdtsc, _mm_lfence, _mm_mfence};
+
+/// How a timestamp read is ordered against surrounding instructions
+///
+/// Stronger serialization keeps the measured code from leaking across the
+/// read at the cost of a larger, and sometimes noisier, timer overhead.
+/// On aarch64 every mode reads the virtual counter the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampSerialization {
+    /// `mfence; rdtsc; lfence`: also drains pending stores
+    #[default]
+    MfenceLfence,
+    /// `rdtscp; lfence`: waits for prior instructions, blocks later ones
+    RdtscpLfence,
+    /// `cpuid; rdtsc`: fully serializing, but slow and trapped by hypervisors
+    CpuidRdtsc,
+    /// `lfence; rdtsc; lfence`: orders instructions but not stores
+    LfenceOnly,
+    /// Bare `rdtsc`, free to be reordered
+    None,
+}
+
+impl TimestampSerialization {
+    pub const ALL: [TimestampSerialization; 5] = [
+        Self::MfenceLfence,
+        Self::RdtscpLfence,
+        Self::CpuidRdtsc,
+        Self::LfenceOnly,
+        Self::None,
+    ];
+}
 
 /// Read timestamp counter for the current architecture
 #[cfg(target_arch = "x86_64")]
@@ -19,6 +49,56 @@ unsafe fn read_timestamp_with_fences() -> u64 {
     read_timestamp()
 }
 
+/// Read the timestamp counter with the given serialization
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub fn read_timestamp_serialized(serialization: TimestampSerialization) -> u64 {
+    unsafe {
+        match serialization {
+            TimestampSerialization::MfenceLfence => read_timestamp_with_fences(),
+            TimestampSerialization::RdtscpLfence => {
+                let mut aux = 0u32;
+                let tsc = __rdtscp(&mut aux);
+                _mm_lfence();
+                tsc
+            }
+            TimestampSerialization::CpuidRdtsc => {
+                core::hint::black_box(__cpuid(0));
+                _rdtsc()
+            }
+            TimestampSerialization::LfenceOnly => {
+                _mm_lfence();
+                let tsc = _rdtsc();
+                _mm_lfence();
+                tsc
+            }
+            TimestampSerialization::None => _rdtsc(),
+        }
+    }
+}
+
+/// Read the TSC together with the CPU it was read on (`rdtscp`'s TSC_AUX,
+/// which Linux sets to `node << 12 | cpu`)
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub fn read_timestamp_and_cpu() -> (u64, usize) {
+    let mut aux = 0u32;
+    let tsc = unsafe { __rdtscp(&mut aux) };
+    (tsc, (aux & 0xfff) as usize)
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub fn read_timestamp_and_cpu() -> (u64, usize) {
+    (read_timestamp(), crate::affinity::current_cpu().unwrap_or(0))
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub fn read_timestamp_serialized(_serialization: TimestampSerialization) -> u64 {
+    read_timestamp()
+}
+
 #[cfg(target_arch = "aarch64")]
 #[inline(always)]
 pub fn read_timestamp() -> u64 {
@@ -44,9 +124,53 @@ fn get_counter_frequency() -> u64 {
     }
 }
 
+/// `rdtscp; lfence`, returning the counter and the CPU it was read on
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn read_tracked() -> (u64, usize) {
+    let stamp = read_timestamp_and_cpu();
+    unsafe { _mm_lfence() };
+    stamp
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+fn read_tracked() -> (u64, usize) {
+    read_timestamp_and_cpu()
+}
+
+/// Why `PrecisionTimer::stop_checked` rejected a measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerError {
+    /// Started and stopped on different CPUs, whose TSCs may disagree;
+    /// `elapsed_ns` and `cycles` are what `stop` and `stop_cycles` would
+    /// have returned
+    Migrated { start_cpu: usize, stop_cpu: usize, elapsed_ns: u64, cycles: u64 },
+    /// The stop stamp was lower than the start stamp
+    Backwards { start_cpu: usize, stop_cpu: usize, cycles: u64 },
+}
+
+impl std::fmt::Display for TimerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimerError::Migrated { start_cpu, stop_cpu, elapsed_ns, .. } => {
+                write!(f, "thread migrated from CPU {start_cpu} to CPU {stop_cpu} during a {elapsed_ns}ns measurement")
+            }
+            TimerError::Backwards { start_cpu, stop_cpu, cycles } => {
+                write!(f, "counter went back {cycles} cycles between CPU {start_cpu} and CPU {stop_cpu}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimerError {}
+
 pub struct PrecisionTimer {
     start: u64,
-    frequency_mhz: u64,
+    frequency_khz: u64,
+    serialization: TimestampSerialization,
+    /// CPU the start stamp was read on, for timers started with `start_tracked`
+    start_cpu: Option<usize>,
 }
 
 impl PrecisionTimer {
@@ -57,40 +181,163 @@ impl PrecisionTimer {
             
             Self {
                 start,
-                frequency_mhz: crate::mock_core::cpu_frequency_mhz(),
+                frequency_khz: crate::mock_core::cpu_frequency_khz(),
+                serialization: TimestampSerialization::MfenceLfence,
+                start_cpu: None,
             }
         }
     }
     
+    /// Start a timer using `clock`'s frequency and serialization instead of
+    /// the global calibration
+    #[inline(always)]
+    pub fn start_on(clock: &crate::clock::Clock) -> Self {
+        let serialization = clock.serialization();
+        Self {
+            start: read_timestamp_serialized(serialization),
+            frequency_khz: clock.frequency_khz(),
+            serialization,
+            start_cpu: None,
+        }
+    }
+    
+    /// Start a timer whose start and stop reads use `serialization`
+    #[inline(always)]
+    pub fn start_with(serialization: TimestampSerialization) -> Self {
+        Self {
+            start: read_timestamp_serialized(serialization),
+            frequency_khz: crate::mock_core::cpu_frequency_khz(),
+            serialization,
+            start_cpu: None,
+        }
+    }
+    
+    /// Start a timer that records which CPU it started on, so
+    /// `stop_checked` can detect migration (uses `rdtscp; lfence`)
+    #[inline(always)]
+    pub fn start_tracked() -> Self {
+        Self::start_tracked_with_frequency(crate::mock_core::cpu_frequency_khz())
+    }
+    
+    /// `start_tracked` using `clock`'s frequency
+    #[inline(always)]
+    pub fn start_tracked_on(clock: &crate::clock::Clock) -> Self {
+        Self::start_tracked_with_frequency(clock.frequency_khz())
+    }
+    
+    #[inline(always)]
+    fn start_tracked_with_frequency(frequency_khz: u64) -> Self {
+        let (start, cpu) = read_tracked();
+        Self {
+            start,
+            frequency_khz,
+            serialization: TimestampSerialization::RdtscpLfence,
+            start_cpu: Some(cpu),
+        }
+    }
+    
+    /// Elapsed nanoseconds; 0 if the counter appears to have gone backwards
     #[inline(always)]
     pub fn stop(self) -> u64 {
+        let end = self.read_end();
+        self.elapsed_ns(end)
+    }
+    
+    /// Elapsed counter ticks, unconverted; 0 if the counter went backwards
+    ///
+    /// Pair with `BenchmarkResults::in_cycles` to defer the conversion to
+    /// nanoseconds until analysis.
+    #[inline(always)]
+    pub fn stop_cycles(self) -> u64 {
+        self.read_end().saturating_sub(self.start)
+    }
+    
+    #[inline(always)]
+    fn read_end(&self) -> u64 {
         unsafe {
-            let end = read_timestamp_with_fences();
-            
-            let cycles = end - self.start;
-            if self.frequency_mhz == 0 {
-                cycles
+            if self.serialization == TimestampSerialization::MfenceLfence {
+                read_timestamp_with_fences()
             } else {
-                #[cfg(target_arch = "x86_64")]
-                {
-                    (cycles * 1000) / self.frequency_mhz
-                }
-                #[cfg(target_arch = "aarch64")]
-                {
-                    let counter_freq = get_counter_frequency();
-                    if counter_freq > 0 {
-                        if cycles == 0 {
-                            0
-                        } else {
-                            std::cmp::max(1, (cycles * 1_000_000_000) / counter_freq)
-                        }
-                    } else {
-                        (cycles * 1000) / self.frequency_mhz
-                    }
+                read_timestamp_serialized(self.serialization)
+            }
+        }
+    }
+    
+    /// Stop, rejecting the measurement if the thread changed CPU or the
+    /// counter went backwards
+    ///
+    /// Only timers from `start_tracked` know their starting CPU; for other
+    /// timers the stop CPU is compared against itself and only backwards
+    /// counters are caught.
+    #[inline(always)]
+    pub fn stop_checked(self) -> Result<u64, TimerError> {
+        let (end, stop_cpu) = read_tracked();
+        self.checked_elapsed_ns(end, stop_cpu)
+    }
+    
+    /// `stop_checked` returning counter ticks on success
+    #[inline(always)]
+    pub fn stop_checked_cycles(self) -> Result<u64, TimerError> {
+        let (end, stop_cpu) = read_tracked();
+        self.checked_elapsed_ns(end, stop_cpu).map(|_| end - self.start)
+    }
+    
+    fn checked_elapsed_ns(&self, end: u64, stop_cpu: usize) -> Result<u64, TimerError> {
+        let start_cpu = self.start_cpu.unwrap_or(stop_cpu);
+        if end < self.start {
+            return Err(TimerError::Backwards { start_cpu, stop_cpu, cycles: self.start - end });
+        }
+        let elapsed_ns = self.elapsed_ns(end);
+        if start_cpu != stop_cpu {
+            return Err(TimerError::Migrated { start_cpu, stop_cpu, elapsed_ns, cycles: end - self.start });
+        }
+        Ok(elapsed_ns)
+    }
+    
+    #[inline(always)]
+    fn elapsed_ns(&self, end: u64) -> u64 {
+        // Lower end stamps come from migrating to a core whose TSC lags
+        let cycles = end.saturating_sub(self.start);
+        if self.frequency_khz == 0 {
+            return cycles;
+        }
+        
+        #[cfg(target_arch = "x86_64")]
+        {
+            scale(cycles, 1_000_000, self.frequency_khz)
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            let counter_freq = get_counter_frequency();
+            if counter_freq > 0 {
+                if cycles == 0 {
+                    0
+                } else {
+                    std::cmp::max(1, scale(cycles, 1_000_000_000, counter_freq))
                 }
+            } else {
+                scale(cycles, 1_000_000, self.frequency_khz)
             }
         }
     }
+    
+    /// Like `stop`, minus the calibrated overhead of the timer itself
+    ///
+    /// Subtracts nothing until `calibrate_timer_overhead` has run.
+    #[inline(always)]
+    pub fn stop_compensated(self) -> u64 {
+        self.stop().saturating_sub(crate::mock_core::timer_overhead_ns())
+    }
+}
+
+/// `value * multiplier / divisor`, widening to 128 bits only when the
+/// product overflows (intervals beyond ~1.7 hours at 3GHz)
+#[inline(always)]
+fn scale(value: u64, multiplier: u64, divisor: u64) -> u64 {
+    match value.checked_mul(multiplier) {
+        Some(product) => product / divisor,
+        None => (value as u128 * multiplier as u128 / divisor as u128).min(u64::MAX as u128) as u64,
+    }
 }
 
 pub fn time_function<F, R>(f: F) -> (R, u64)
@@ -103,6 +350,17 @@ where
     (result, elapsed)
 }
 
+/// `time_function` with the calibrated timer overhead subtracted
+pub fn time_function_compensated<F, R>(f: F) -> (R, u64)
+where
+    F: FnOnce() -> R,
+{
+    let timer = PrecisionTimer::start();
+    let result = f();
+    let elapsed = timer.stop_compensated();
+    (result, elapsed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,6 +384,42 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_long_intervals_and_migration() {
+        let timer = |start, start_cpu| PrecisionTimer {
+            start,
+            frequency_khz: 3_000_000,
+            serialization: TimestampSerialization::RdtscpLfence,
+            start_cpu,
+        };
+        
+        // Three hours at 3GHz overflows cycles * 1_000_000 in 64 bits
+        let three_hours = 3 * 3600 * 3_000_000_000u64;
+        assert_eq!(timer(0, None).elapsed_ns(three_hours), 3 * 3600 * 1_000_000_000);
+        assert_eq!(scale(u64::MAX, 1000, 1), u64::MAX);
+        
+        // A 2999.5 MHz clock converts exactly rather than as 3000 MHz
+        let clock = crate::clock::Clock::from_frequency_khz(2_999_500);
+        let timer_on_clock = PrecisionTimer { start: 0, ..PrecisionTimer::start_on(&clock) };
+        assert_eq!(timer_on_clock.elapsed_ns(2_999_500_000), 1_000_000_000);
+        
+        assert_eq!(timer(1_000, Some(2)).elapsed_ns(400), 0);
+        assert_eq!(
+            timer(1_000, Some(2)).checked_elapsed_ns(400, 2),
+            Err(TimerError::Backwards { start_cpu: 2, stop_cpu: 2, cycles: 600 })
+        );
+        assert_eq!(
+            timer(0, Some(0)).checked_elapsed_ns(3_000, 1),
+            Err(TimerError::Migrated { start_cpu: 0, stop_cpu: 1, elapsed_ns: 1_000, cycles: 3_000 })
+        );
+        assert_eq!(timer(0, Some(1)).checked_elapsed_ns(3_000, 1), Ok(1_000));
+        
+        let tracked = PrecisionTimer::start_tracked();
+        let _ = (0..10).sum::<i32>();
+        // The unpinned test thread may be migrated between start and stop
+        assert!(matches!(tracked.stop_checked(), Ok(_) | Err(TimerError::Migrated { .. })));
+    }
+    
     #[test]
     fn test_time_function() {
         calibrate_tsc_frequency();
@@ -0,0 +1,337 @@
+//! CPU, cache and NUMA topology read from sysfs
+//!
+//! Everything comes from `/sys/devices/system/{cpu,node}`, so no external
+//! tools such as `numactl` are needed. Machines without NUMA support in the
+//! kernel are reported as a single node holding every CPU.
+
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    Data,
+    Instruction,
+    Unified,
+}
+
+/// One cache as seen from a CPU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheInfo {
+    pub level: u8,
+    pub kind: CacheKind,
+    pub size_bytes: u64,
+    pub line_size: u32,
+    /// CPUs sharing this cache instance
+    pub shared_cpus: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuInfo {
+    pub id: usize,
+    pub node: usize,
+    pub package: usize,
+    pub core_id: usize,
+    /// Hardware threads on the same core, including this one
+    pub smt_siblings: Vec<usize>,
+    pub caches: Vec<CacheInfo>,
+}
+
+impl CpuInfo {
+    /// The highest-level cache, usually the shared L3
+    pub fn last_level_cache(&self) -> Option<&CacheInfo> {
+        self.caches.iter().max_by_key(|cache| cache.level)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumaNode {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+    pub memory_kb: Option<u64>,
+    /// Relative access cost to each node, in `Topology::nodes` order (10 =
+    /// local)
+    pub distances: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topology {
+    pub nodes: Vec<NumaNode>,
+    /// Online CPUs in id order
+    pub cpus: Vec<CpuInfo>,
+}
+
+impl Topology {
+    /// Read the running machine's topology
+    pub fn detect() -> io::Result<Self> {
+        Self::from_sysfs(Path::new("/sys/devices/system"))
+    }
+
+    /// Read a topology from a sysfs tree rooted at `root` (normally
+    /// `/sys/devices/system`), e.g. a copy taken from another machine
+    pub fn from_sysfs(root: &Path) -> io::Result<Self> {
+        let cpu_root = root.join("cpu");
+        let online = parse_cpu_list(&std::fs::read_to_string(cpu_root.join("online"))?);
+
+        let mut nodes = read_nodes(&root.join("node"));
+        if nodes.is_empty() {
+            nodes.push(NumaNode { id: 0, cpus: online.clone(), memory_kb: None, distances: vec![10] });
+        }
+
+        let cpus = online.iter()
+            .map(|&id| {
+                let dir = cpu_root.join(format!("cpu{id}"));
+                let topology = dir.join("topology");
+                CpuInfo {
+                    id,
+                    node: nodes.iter().find(|node| node.cpus.contains(&id)).map_or(0, |node| node.id),
+                    package: read_number(&topology.join("physical_package_id")).unwrap_or(0) as usize,
+                    core_id: read_number(&topology.join("core_id")).unwrap_or(id as u64) as usize,
+                    smt_siblings: read_list(&topology.join("thread_siblings_list")).unwrap_or_else(|| vec![id]),
+                    caches: read_caches(&dir.join("cache")),
+                }
+            })
+            .collect();
+
+        Ok(Self { nodes, cpus })
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn cpu(&self, cpu: usize) -> Option<&CpuInfo> {
+        self.cpus.iter().find(|info| info.id == cpu)
+    }
+
+    pub fn node(&self, node: usize) -> Option<&NumaNode> {
+        self.nodes.iter().find(|info| info.id == node)
+    }
+
+    pub fn node_of_cpu(&self, cpu: usize) -> Option<usize> {
+        self.cpu(cpu).map(|info| info.node)
+    }
+
+    /// Distance from node `from` to node `to`, if both exist
+    pub fn distance(&self, from: usize, to: usize) -> Option<u32> {
+        let to_index = self.nodes.iter().position(|node| node.id == to)?;
+        self.node(from)?.distances.get(to_index).copied()
+    }
+
+    /// One CPU per physical core (the lowest-numbered SMT sibling)
+    pub fn physical_cores(&self) -> Vec<usize> {
+        self.cpus.iter()
+            .filter(|info| info.smt_siblings.first().is_none_or(|&first| first == info.id))
+            .map(|info| info.id)
+            .collect()
+    }
+
+    /// Groups of CPUs sharing a last-level cache
+    pub fn llc_groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for info in &self.cpus {
+            let group = info.last_level_cache().map_or_else(|| vec![info.id], |cache| cache.shared_cpus.clone());
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        groups
+    }
+}
+
+fn read_nodes(node_root: &Path) -> Vec<NumaNode> {
+    let Ok(online) = std::fs::read_to_string(node_root.join("online")) else {
+        return Vec::new();
+    };
+    parse_cpu_list(&online).into_iter()
+        .map(|id| {
+            let dir = node_root.join(format!("node{id}"));
+            let memory_kb = std::fs::read_to_string(dir.join("meminfo")).ok().and_then(|meminfo| {
+                meminfo.lines()
+                    .find(|line| line.contains("MemTotal:"))
+                    .and_then(|line| line.split_whitespace().rev().nth(1))
+                    .and_then(|kb| kb.parse().ok())
+            });
+            let distances = std::fs::read_to_string(dir.join("distance"))
+                .map(|line| line.split_whitespace().filter_map(|d| d.parse().ok()).collect())
+                .unwrap_or_default();
+            NumaNode {
+                id,
+                cpus: read_list(&dir.join("cpulist")).unwrap_or_default(),
+                memory_kb,
+                distances,
+            }
+        })
+        .collect()
+}
+
+fn read_caches(cache_root: &Path) -> Vec<CacheInfo> {
+    let mut caches = Vec::new();
+    for index in 0.. {
+        let dir = cache_root.join(format!("index{index}"));
+        let Some(level) = read_number(&dir.join("level")) else {
+            break;
+        };
+        let kind = match std::fs::read_to_string(dir.join("type")).unwrap_or_default().trim() {
+            "Data" => CacheKind::Data,
+            "Instruction" => CacheKind::Instruction,
+            _ => CacheKind::Unified,
+        };
+        caches.push(CacheInfo {
+            level: level as u8,
+            kind,
+            size_bytes: std::fs::read_to_string(dir.join("size")).ok().and_then(|s| parse_size(&s)).unwrap_or(0),
+            line_size: read_number(&dir.join("coherency_line_size")).unwrap_or(64) as u32,
+            shared_cpus: read_list(&dir.join("shared_cpu_list")).unwrap_or_default(),
+        });
+    }
+    caches
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_list(path: &Path) -> Option<Vec<usize>> {
+    std::fs::read_to_string(path).ok().map(|list| parse_cpu_list(&list))
+}
+
+/// Parse a sysfs size such as `32K` or `8M`
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (digits, multiplier) = match size.chars().last()? {
+        'K' => (&size[..size.len() - 1], 1 << 10),
+        'M' => (&size[..size.len() - 1], 1 << 20),
+        'G' => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+    digits.parse::<u64>().ok().map(|n| n * multiplier)
+}
+
+/// Parse a kernel CPU list such as `0-3,8,10-11`
+pub fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        // Boot parameters may add a group suffix: `0-7:2/4` takes the first
+        // 2 CPUs of every block of 4 starting at 0
+        let (range, group) = match part.split_once(':') {
+            Some((range, group)) => (range, group.split_once('/')),
+            None => (part, None),
+        };
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) else {
+                    continue;
+                };
+                match group.map(|(used, size)| (used.parse::<usize>(), size.parse::<usize>())) {
+                    None => cpus.extend(start..=end),
+                    Some((Ok(used), Ok(size))) if size > 0 => {
+                        cpus.extend((start..=end).filter(|cpu| (cpu - start) % size < used));
+                    }
+                    Some(_) => {}
+                }
+            }
+            None => cpus.extend(range.parse::<usize>().ok()),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    cpus
+}
+
+/// Parse a hex CPU mask such as `ff` or `00000000,0000000f`
+pub fn parse_cpu_mask(mask: &str) -> Vec<usize> {
+    let digits: Vec<u32> = mask.trim().chars()
+        .filter(|&c| c != ',')
+        .filter_map(|c| c.to_digit(16))
+        .collect();
+    let mut cpus = Vec::new();
+    for (position, digit) in digits.iter().rev().enumerate() {
+        for bit in 0..4 {
+            if digit & (1 << bit) != 0 {
+                cpus.push(position * 4 + bit);
+            }
+        }
+    }
+    cpus
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn test_cpu_list_and_mask_parsing() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list(""), Vec::<usize>::new());
+        assert_eq!(parse_cpu_list("2-5:2/4"), vec![2, 3]);
+        assert_eq!(parse_cpu_list("0-15:1/4,20"), vec![0, 4, 8, 12, 20]);
+        assert_eq!(parse_cpu_mask("ff\n"), (0..8).collect::<Vec<_>>());
+        assert_eq!(parse_cpu_mask("00000001,00000006"), vec![1, 2, 32]);
+        assert_eq!(parse_size("32K\n"), Some(32 * 1024));
+        assert_eq!(parse_size("107520K"), Some(107520 * 1024));
+    }
+
+    /// Two nodes, two cores per node, two threads per core; L3 per node
+    fn write_fake_sysfs(root: &Path) {
+        let write = |path: &str, contents: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("cpu/online", "0-7\n");
+        write("node/online", "0-1\n");
+        for node in 0..2 {
+            write(&format!("node/node{node}/cpulist"), &format!("{}-{}\n", node * 4, node * 4 + 3));
+            write(&format!("node/node{node}/distance"), if node == 0 { "10 21\n" } else { "21 10\n" });
+            write(&format!("node/node{node}/meminfo"), &format!("Node {node} MemTotal:       16384000 kB\n"));
+        }
+        for cpu in 0..8 {
+            let core = cpu / 2;
+            let siblings = format!("{}-{}\n", core * 2, core * 2 + 1);
+            let llc = if cpu < 4 { "0-3\n" } else { "4-7\n" };
+            write(&format!("cpu/cpu{cpu}/topology/physical_package_id"), &format!("{}\n", cpu / 4));
+            write(&format!("cpu/cpu{cpu}/topology/core_id"), &format!("{}\n", core % 2));
+            write(&format!("cpu/cpu{cpu}/topology/thread_siblings_list"), &siblings);
+            for (index, (level, kind, size, shared)) in
+                [(1, "Data", "48K", siblings.as_str()), (3, "Unified", "32M", llc)].into_iter().enumerate()
+            {
+                let dir = format!("cpu/cpu{cpu}/cache/index{index}");
+                write(&format!("{dir}/level"), &format!("{level}\n"));
+                write(&format!("{dir}/type"), kind);
+                write(&format!("{dir}/size"), size);
+                write(&format!("{dir}/coherency_line_size"), "64\n");
+                write(&format!("{dir}/shared_cpu_list"), shared);
+            }
+        }
+    }
+
+    #[test]
+    fn test_topology_from_sysfs_tree() {
+        let root = TempPath::new("topology");
+        write_fake_sysfs(&root);
+        let topology = Topology::from_sysfs(&root).unwrap();
+
+        assert_eq!(topology.node_count(), 2);
+        assert_eq!(topology.node(1).unwrap().cpus, vec![4, 5, 6, 7]);
+        assert_eq!(topology.node(0).unwrap().memory_kb, Some(16_384_000));
+        assert_eq!(topology.distance(0, 1), Some(21));
+        assert_eq!(topology.node_of_cpu(6), Some(1));
+        assert_eq!(topology.cpu(5).unwrap().smt_siblings, vec![4, 5]);
+        assert_eq!(topology.physical_cores(), vec![0, 2, 4, 6]);
+        assert_eq!(topology.llc_groups(), vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        assert_eq!(topology.cpu(0).unwrap().last_level_cache().unwrap().size_bytes, 32 << 20);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_detect() {
+        let topology = Topology::detect().unwrap();
+        assert!(topology.node_count() >= 1);
+        assert!(!topology.cpus.is_empty());
+        for info in &topology.cpus {
+            assert!(info.smt_siblings.contains(&info.id));
+            assert!(topology.node(info.node).is_some());
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Cross-core TSC synchronization checks
+//!
+//! A latency measured with a start stamp from one core and an end stamp from
+//! another is only meaningful if both cores' TSCs agree. This module bounds
+//! the skew between every pair of CPUs with a ping-pong and turns the
+//! resulting matrix into a verdict.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::timing::{read_timestamp_serialized, TimestampSerialization};
+
+/// Bounds on the TSC of one CPU minus the TSC of another, in cycles
+///
+/// Each reply stamp was taken between the request's send and receive stamps
+/// in real time, so every round bounds the skew; the bounds are the
+/// tightest across all rounds. `lower > upper` means the stamps violated
+/// causality, which synchronized TSCs cannot produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SkewEstimate {
+    pub lower_cycles: i64,
+    pub upper_cycles: i64,
+}
+
+impl SkewEstimate {
+    pub const ZERO: SkewEstimate = SkewEstimate { lower_cycles: 0, upper_cycles: 0 };
+
+    pub fn skew_cycles(&self) -> i64 {
+        self.lower_cycles + (self.upper_cycles - self.lower_cycles) / 2
+    }
+
+    /// Half the width of the bounds
+    pub fn uncertainty_cycles(&self) -> u64 {
+        self.upper_cycles.abs_diff(self.lower_cycles) / 2
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.lower_cycles <= self.upper_cycles
+    }
+
+    /// Whether zero skew lies within the bounds widened by `tolerance_cycles`
+    pub fn allows_zero(&self, tolerance_cycles: u64) -> bool {
+        let tolerance = tolerance_cycles as i64;
+        self.is_consistent() && self.lower_cycles - tolerance <= 0 && self.upper_cycles + tolerance >= 0
+    }
+
+    /// The same bounds seen from the other CPU
+    pub fn reversed(&self) -> Self {
+        Self { lower_cycles: -self.upper_cycles, upper_cycles: -self.lower_cycles }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TscSyncConfig {
+    /// Ping-pong rounds per pair
+    pub rounds: usize,
+    /// Skew tolerated beyond the measured bounds before failing
+    pub tolerance_cycles: u64,
+}
+
+impl Default for TscSyncConfig {
+    fn default() -> Self {
+        Self {
+            rounds: 1000,
+            tolerance_cycles: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncVerdict {
+    Synchronized,
+    Unsynchronized,
+    /// Only one CPU available, so there is nothing to compare
+    SingleCpu,
+}
+
+/// Pairwise skew between the checked CPUs
+#[derive(Debug, Clone, PartialEq)]
+pub struct TscSyncReport {
+    pub cpus: Vec<usize>,
+    /// `skew[i][j]` bounds TSC of `cpus[j]` minus TSC of `cpus[i]`
+    pub skew: Vec<Vec<SkewEstimate>>,
+    pub tolerance_cycles: u64,
+    pub verdict: SyncVerdict,
+}
+
+impl TscSyncReport {
+    fn from_matrix(cpus: Vec<usize>, skew: Vec<Vec<SkewEstimate>>, tolerance_cycles: u64) -> Self {
+        let verdict = if cpus.len() < 2 {
+            SyncVerdict::SingleCpu
+        } else if skew.iter().flatten().all(|s| s.allows_zero(tolerance_cycles)) {
+            SyncVerdict::Synchronized
+        } else {
+            SyncVerdict::Unsynchronized
+        };
+        Self { cpus, skew, tolerance_cycles, verdict }
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        self.verdict != SyncVerdict::Unsynchronized
+    }
+
+    /// Skew of CPU `to` relative to CPU `from`, if both were checked
+    pub fn skew_between(&self, from: usize, to: usize) -> Option<SkewEstimate> {
+        let i = self.cpus.iter().position(|&cpu| cpu == from)?;
+        let j = self.cpus.iter().position(|&cpu| cpu == to)?;
+        Some(self.skew[i][j])
+    }
+
+    /// The pair whose skew bound lies furthest from zero
+    pub fn worst_pair(&self) -> Option<(usize, usize, SkewEstimate)> {
+        // How far the bounds are from admitting zero skew
+        let distance = |s: &SkewEstimate| s.lower_cycles.max(0).unsigned_abs().max(s.upper_cycles.min(0).unsigned_abs());
+
+        self.cpus.iter().enumerate()
+            .flat_map(|(i, &a)| self.cpus.iter().enumerate().skip(i + 1).map(move |(j, &b)| (i, j, a, b)))
+            .map(|(i, j, a, b)| (a, b, self.skew[i][j]))
+            .max_by_key(|(_, _, s)| distance(s))
+    }
+
+    /// The skew matrix as a table of `skew±uncertainty` cycles
+    pub fn matrix(&self) -> String {
+        let mut table = format!("{:>6}", "");
+        for cpu in &self.cpus {
+            table.push_str(&format!(" {:>14}", format!("cpu{cpu}")));
+        }
+        for (i, row) in self.skew.iter().enumerate() {
+            table.push_str(&format!("\n{:>6}", format!("cpu{}", self.cpus[i])));
+            for skew in row {
+                table.push_str(&format!(" {:>14}", format!("{}±{}", skew.skew_cycles(), skew.uncertainty_cycles())));
+            }
+        }
+        table
+    }
+
+    pub fn summary(&self) -> String {
+        match (self.verdict, self.worst_pair()) {
+            (SyncVerdict::SingleCpu, _) => "TSC sync: single CPU, nothing to compare".to_string(),
+            (verdict, Some((a, b, skew))) => format!(
+                "TSC sync: {:?} across {} CPUs, worst pair cpu{}→cpu{} skew {}±{} cycles (tolerance {})",
+                verdict, self.cpus.len(), a, b, skew.skew_cycles(), skew.uncertainty_cycles(), self.tolerance_cycles
+            ),
+            (verdict, None) => format!("TSC sync: {verdict:?}"),
+        }
+    }
+}
+
+/// Check every pair of CPUs this thread may run on
+pub fn check_tsc_sync(config: &TscSyncConfig) -> io::Result<TscSyncReport> {
+    check_tsc_sync_on(&crate::affinity::allowed_cpus()?, config)
+}
+
+/// Check every pair among `cpus`
+pub fn check_tsc_sync_on(cpus: &[usize], config: &TscSyncConfig) -> io::Result<TscSyncReport> {
+    let n = cpus.len();
+    let mut skew = vec![vec![SkewEstimate::ZERO; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let estimate = measure_skew(cpus[i], cpus[j], config.rounds)?;
+            skew[i][j] = estimate;
+            skew[j][i] = estimate.reversed();
+        }
+    }
+    Ok(TscSyncReport::from_matrix(cpus.to_vec(), skew, config.tolerance_cycles))
+}
+
+/// Bound the TSC of `cpu` minus the TSC of `reference` with a ping-pong
+///
+/// The reference thread stamps a request, a thread pinned to `cpu` stamps
+/// its reply and the reference stamps the reply's arrival.
+pub fn measure_skew(reference: usize, cpu: usize, rounds: usize) -> io::Result<SkewEstimate> {
+    let request = Arc::new(AtomicU64::new(0));
+    let reply = Arc::new(AtomicU64::new(0));
+    let reply_stamp = Arc::new(AtomicU64::new(0));
+    let failed = Arc::new(AtomicBool::new(false));
+    let rounds = rounds.max(1) as u64;
+    let shared_cpu = reference == cpu;
+
+    let responder = {
+        let (request, reply, reply_stamp, failed) = (request.clone(), reply.clone(), reply_stamp.clone(), failed.clone());
+        std::thread::spawn(move || {
+            if crate::affinity::pin_current_thread(cpu).is_err() {
+                failed.store(true, Ordering::Release);
+                return;
+            }
+            for round in 1..=rounds {
+                while request.load(Ordering::Acquire) != round {
+                    if failed.load(Ordering::Acquire) {
+                        return;
+                    }
+                    wait(shared_cpu);
+                }
+                reply_stamp.store(read_timestamp_serialized(TimestampSerialization::MfenceLfence), Ordering::Relaxed);
+                reply.store(round, Ordering::Release);
+            }
+        })
+    };
+
+    let measured = crate::affinity::run_on_cpu(reference, || {
+        let mut bounds = SkewEstimate { lower_cycles: i64::MIN, upper_cycles: i64::MAX };
+        for round in 1..=rounds {
+            let sent = read_timestamp_serialized(TimestampSerialization::MfenceLfence);
+            request.store(round, Ordering::Release);
+            while reply.load(Ordering::Acquire) != round {
+                if failed.load(Ordering::Acquire) {
+                    return None;
+                }
+                wait(shared_cpu);
+            }
+            let received = read_timestamp_serialized(TimestampSerialization::MfenceLfence);
+            let stamp = reply_stamp.load(Ordering::Relaxed);
+
+            bounds.lower_cycles = bounds.lower_cycles.max(stamp.wrapping_sub(received) as i64);
+            bounds.upper_cycles = bounds.upper_cycles.min(stamp.wrapping_sub(sent) as i64);
+        }
+        Some(bounds)
+    });
+
+    if measured.is_err() {
+        failed.store(true, Ordering::Release);
+    }
+    let _ = responder.join();
+
+    measured?.ok_or_else(|| io::Error::other(format!("could not pin a thread to CPU {cpu}")))
+}
+
+/// Spin while the other side runs on another CPU; yield when sharing one
+#[inline(always)]
+fn wait(shared_cpu: bool) {
+    if shared_cpu {
+        std::thread::yield_now();
+    } else {
+        std::hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skew_bounds_and_verdict() {
+        let synced = SkewEstimate { lower_cycles: -40, upper_cycles: 60 };
+        assert_eq!(synced.skew_cycles(), 10);
+        assert_eq!(synced.uncertainty_cycles(), 50);
+        assert!(synced.allows_zero(0));
+        assert_eq!(synced.reversed(), SkewEstimate { lower_cycles: -60, upper_cycles: 40 });
+
+        let skewed = SkewEstimate { lower_cycles: 500, upper_cycles: 700 };
+        assert!(!skewed.allows_zero(100));
+        assert!(skewed.allows_zero(500));
+        assert!(!SkewEstimate { lower_cycles: 10, upper_cycles: -10 }.allows_zero(1000));
+
+        let matrix = |s: SkewEstimate| vec![vec![SkewEstimate::ZERO, s], vec![s.reversed(), SkewEstimate::ZERO]];
+        let good = TscSyncReport::from_matrix(vec![0, 2], matrix(synced), 100);
+        assert_eq!(good.verdict, SyncVerdict::Synchronized);
+        assert_eq!(good.skew_between(2, 0), Some(synced.reversed()));
+
+        let bad = TscSyncReport::from_matrix(vec![0, 2], matrix(skewed), 100);
+        assert_eq!(bad.verdict, SyncVerdict::Unsynchronized);
+        assert_eq!(bad.worst_pair(), Some((0, 2, skewed)));
+        assert!(bad.summary().contains("Unsynchronized"));
+        assert!(bad.matrix().contains("600±100"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_ping_pong() {
+        let cpus = crate::affinity::allowed_cpus().unwrap();
+
+        // Both ends on one CPU read the same counter, so zero skew must fit
+        let same = measure_skew(cpus[0], cpus[0], 20).unwrap();
+        assert!(same.is_consistent());
+        assert!(same.allows_zero(0));
+
+        let report = check_tsc_sync(&TscSyncConfig { rounds: 50, ..Default::default() }).unwrap();
+        assert_eq!(report.cpus, cpus);
+        if cpus.len() == 1 {
+            assert_eq!(report.verdict, SyncVerdict::SingleCpu);
+        }
+    }
+}
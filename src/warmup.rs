@@ -0,0 +1,146 @@
+//! Warmup before recording samples
+//!
+//! The first calls of a benchmark pay for cold caches, lazy page faults and
+//! untrained branch predictors. A warmup runs the function without
+//! recording until it is (hopefully) in steady state.
+
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+
+/// Calls per window when tracking the warmup trajectory
+const DEFAULT_WINDOW: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Warmup {
+    /// Record from the first call
+    #[default]
+    None,
+    /// Discard a fixed number of calls
+    Iterations(usize),
+    /// Discard calls for a fixed wall-clock time
+    Time(Duration),
+    /// Discard calls until the medians of consecutive windows of `window`
+    /// calls agree within `tolerance` (relative), up to `max_iterations`
+    Auto {
+        window: usize,
+        tolerance: f64,
+        max_iterations: usize,
+    },
+}
+
+impl Warmup {
+    /// Automatic warmup: windows of 50 calls agreeing within 5%, at most
+    /// 100k calls
+    pub fn auto() -> Self {
+        Warmup::Auto { window: DEFAULT_WINDOW, tolerance: 0.05, max_iterations: 100_000 }
+    }
+}
+
+/// What a warmup discarded
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WarmupReport {
+    /// Calls run and not recorded
+    pub discarded: u64,
+    /// For `Warmup::Auto`, whether the medians settled before the limit
+    pub converged: Option<bool>,
+    /// Median latency (ns) of each consecutive window of warmup calls
+    pub trajectory: Vec<u64>,
+}
+
+/// Run `f` as `warmup` describes, timing each call with `clock`
+pub fn warm_up<F, R>(warmup: &Warmup, clock: &Clock, f: &mut F) -> WarmupReport
+where
+    F: FnMut() -> R,
+{
+    let window = match warmup {
+        Warmup::None => return WarmupReport::default(),
+        Warmup::Auto { window, .. } => (*window).max(1),
+        _ => DEFAULT_WINDOW,
+    };
+    let deadline = match warmup {
+        Warmup::Time(duration) => Some(Instant::now() + *duration),
+        _ => None,
+    };
+    let limit = match warmup {
+        Warmup::Iterations(n) => *n,
+        Warmup::Auto { max_iterations, .. } => *max_iterations,
+        _ => usize::MAX,
+    };
+    // Window medians closer than the timer can resolve count as equal
+    let slack = clock.resolution_ns();
+
+    let mut report = WarmupReport::default();
+    let mut current = Vec::with_capacity(window);
+    let mut stable_windows = 0;
+    while (report.discarded as usize) < limit && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+        let (_, elapsed) = clock.time(&mut *f);
+        report.discarded += 1;
+        current.push(elapsed);
+        if current.len() < window {
+            continue;
+        }
+
+        current.sort_unstable();
+        let median = current[current.len() / 2];
+        current.clear();
+        if let (Warmup::Auto { tolerance, .. }, Some(&previous)) = (warmup, report.trajectory.last()) {
+            let allowed = (previous as f64 * tolerance).max(slack as f64);
+            stable_windows = if median.abs_diff(previous) as f64 <= allowed { stable_windows + 1 } else { 0 };
+        }
+        report.trajectory.push(median);
+        // Two agreeing comparisons in a row, so one lucky window can't end it
+        if stable_windows >= 2 {
+            break;
+        }
+    }
+
+    if let Warmup::Auto { .. } = warmup {
+        report.converged = Some(stable_windows >= 2);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_warmups() {
+        let clock = Clock::global();
+        let mut calls = 0;
+
+        let report = warm_up(&Warmup::Iterations(120), &clock, &mut || calls += 1);
+        assert_eq!(calls, 120);
+        assert_eq!(report.discarded, 120);
+        assert_eq!(report.trajectory.len(), 2);
+        assert_eq!(report.converged, None);
+
+        let report = warm_up(&Warmup::Time(Duration::from_millis(5)), &clock, &mut || std::hint::black_box(1));
+        assert!(report.discarded > 0);
+
+        assert_eq!(warm_up(&Warmup::None, &clock, &mut || 1), WarmupReport::default());
+    }
+
+    #[test]
+    fn test_auto_warmup_waits_for_steady_state() {
+        let clock = Clock::from_frequency_mhz(crate::mock_core::cpu_frequency_mhz());
+        let mut calls = 0u64;
+
+        // Cost decays like a cache warming up: window medians differ by
+        // more than 5% until a few hundred calls in
+        let report = warm_up(&Warmup::auto(), &clock, &mut || {
+            calls += 1;
+            (0..200 + 100_000 / calls).map(std::hint::black_box).sum::<u64>()
+        });
+
+        assert_eq!(report.converged, Some(true));
+        assert!(report.discarded >= 300, "stopped after {} calls", report.discarded);
+        assert!(report.trajectory[0] > *report.trajectory.last().unwrap());
+
+        let capped = Warmup::Auto { window: 10, tolerance: 0.0, max_iterations: 25 };
+        let report = warm_up(&capped, &clock, &mut || std::thread::sleep(Duration::from_micros(fastrand::u64(1..200))));
+        assert_eq!(report.discarded, 25);
+        assert_eq!(report.converged, Some(false));
+    }
+}